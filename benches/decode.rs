@@ -0,0 +1,66 @@
+//! Fetch/decode overhead, run with `cargo bench --bench decode`.
+//!
+//! Sweeps every opcode through `ControlLogic::decode` as the CPU would, looks every opcode up in the
+//! decode table and decodes it again from the microcode without it, then runs a ROM on the microcode
+//! engine, and prints the time per decode and per instruction for each.
+
+use std::time::{Duration, Instant};
+use cpu::{Cpu, ControlLogic, Pacing};
+use cpu::components::{Flags, InstructionRegister};
+
+const SWEEPS: usize = 20;
+const CYCLES: u32 = 2_000_000;
+
+fn report(name: &str, count: u64, unit: &str, elapsed: Duration) {
+  println!("{:<10} {:>10} {:<12} {:>9.3} ms {:>9.1} ns/{}", name, count, unit,
+    elapsed.as_secs_f64() * 1e3, elapsed.as_secs_f64() * 1e9 / count as f64, unit);
+}
+
+fn main() -> cpu::Result<()> {
+  let start = Instant::now();
+  let mut logic = ControlLogic::new()?;
+  report("new", 1, "logic", start.elapsed());
+
+  let flags = Flags::new();
+  let mut ir = InstructionRegister::new();
+  let (mut decodes, mut op) = (0u64, 0u16);
+  let start = Instant::now();
+  for _ in 0..SWEEPS {
+    loop {
+      if logic.fetched() {
+        op = op.wrapping_add(1);
+        if op == 0 {
+          break
+        }
+      }
+      let _ = logic.decode(op, &flags, &mut ir);
+      decodes += 1;
+    }
+  }
+  report("decode", decodes, "decode", start.elapsed());
+
+  let (mut steps, start) = (0usize, Instant::now());
+  for _ in 0..SWEEPS {
+    for op in 0..=0xFFFF {
+      steps += logic.steps(op).unwrap_or(0);
+    }
+  }
+  report("cached", SWEEPS as u64 * 0x10000, "opcode", start.elapsed());
+
+  let (mut uncached, start) = (0usize, Instant::now());
+  for _ in 0..SWEEPS {
+    for op in 0..=0xFFFF {
+      uncached += logic.steps_uncached(op).unwrap_or(0);
+    }
+  }
+  report("uncached", SWEEPS as u64 * 0x10000, "opcode", start.elapsed());
+  assert_eq!(steps, uncached);
+
+  let rom = cpu::assemble("assets/keyboard-test.a")?;
+  let mut cpu = Cpu::new(1.0, rom)?;
+  cpu.set_pacing(Pacing::Unthrottled);
+  let start = Instant::now();
+  cpu.run(CYCLES);
+  report("run", cpu.instructions(), "instruction", start.elapsed());
+  Ok(())
+}
@@ -0,0 +1,131 @@
+extern crate cpu;
+extern crate clap;
+
+use clap::{
+  App,
+  AppSettings,
+  Arg,
+  ArgMatches,
+  SubCommand,
+};
+
+use std::fs;
+use cpu::{
+  Result,
+  Error,
+  ControlLogic,
+  control::{
+    self,
+    Definition,
+  },
+};
+
+
+fn definition(filename: Option<&str>) -> Result<Definition> {
+  match filename {
+    Some(filename) => Definition::load(filename),
+    None => Ok(Definition::built_in()),
+  }
+}
+
+fn export(prefix: &str, definition: Definition) -> Result<()> {
+  let images = ControlLogic::from_definition(definition)?.images()?;
+  control::write_images(prefix, &images)?;
+  // Read the set back and check it decodes to the same sequences before calling it done.
+  if ControlLogic::from_images(&control::read_images(prefix)?)?.images()? != images {
+    return Err(Error::InvalidImage(String::from("The images did not read back the same.")))
+  }
+  for chip in 0..control::CHIPS {
+    println!("{} (bits {}-{})", control::image_filename(prefix, chip), chip * 8, chip * 8 + 7);
+  }
+  Ok(())
+}
+
+// The control store to check: a definition file, EEPROM images, or the built-in microcode.
+fn control_logic(args: &ArgMatches) -> Result<ControlLogic> {
+  if let Some(prefix) = args.value_of("eeprom") {
+    ControlLogic::from_images(&control::read_images(prefix)?)
+  } else {
+    ControlLogic::from_definition(definition(args.value_of("microcode"))?)
+  }
+}
+
+fn verify(args: &ArgMatches) -> Result<i32> {
+  let report = control_logic(args)?.verify();
+  print!("{}", report);
+  Ok(if report.broken() { 1 } else { 0 })
+}
+
+fn reference(args: &ArgMatches) -> Result<()> {
+  let reference = control_logic(args)?.reference();
+  let text = if args.is_present("csv") { reference.csv() } else { reference.markdown() };
+  match args.value_of("file") {
+    None => print!("{}", text),
+    Some(filename) => fs::write(filename, text).map_err(|error| Error::File(String::from(filename), error))?,
+  }
+  Ok(())
+}
+
+fn write_definition(filename: Option<&str>) -> Result<()> {
+  let text = Definition::built_in().to_string();
+  match filename {
+    None => print!("{}", text),
+    Some(filename) => fs::write(filename, text).map_err(|error| Error::File(String::from(filename), error))?,
+  }
+  Ok(())
+}
+
+fn init() -> Result<i32> {
+  let args = App::new("microcode")
+    .setting(AppSettings::SubcommandRequiredElseHelp)
+    .subcommand(SubCommand::with_name("export")
+      .about("Writes the control store as EEPROM images, <prefix>.0.bin to <prefix>.7.bin.")
+      .arg(Arg::with_name("prefix")
+        .required(true))
+      .arg(Arg::with_name("microcode")
+        .long("microcode")
+        .takes_value(true)))
+    .subcommand(SubCommand::with_name("verify")
+      .about("Checks every opcode's micro-steps and lists each as legal, illegal or broken; exits 1 if any are broken.")
+      .arg(Arg::with_name("microcode")
+        .long("microcode")
+        .takes_value(true))
+      .arg(Arg::with_name("eeprom")
+        .long("eeprom")
+        .takes_value(true)
+        .conflicts_with("microcode")))
+    .subcommand(SubCommand::with_name("reference")
+      .about("Writes the opcode reference as Markdown, or CSV with --csv, to a file or stdout.")
+      .arg(Arg::with_name("file"))
+      .arg(Arg::with_name("csv")
+        .long("csv"))
+      .arg(Arg::with_name("microcode")
+        .long("microcode")
+        .takes_value(true))
+      .arg(Arg::with_name("eeprom")
+        .long("eeprom")
+        .takes_value(true)
+        .conflicts_with("microcode")))
+    .subcommand(SubCommand::with_name("definition")
+      .about("Writes the built-in microcode as a definition file, or to stdout.")
+      .arg(Arg::with_name("file")))
+    .get_matches();
+
+  match args.subcommand() {
+    ("export", Some(args)) => export(args.value_of("prefix").unwrap(), definition(args.value_of("microcode"))?).map(|_| 0),
+    ("verify", Some(args)) => verify(args),
+    ("reference", Some(args)) => reference(args).map(|_| 0),
+    ("definition", Some(args)) => write_definition(args.value_of("file")).map(|_| 0),
+    _ => Err(Error::InvalidCommand(String::from(args.usage()))),
+  }
+}
+
+fn main() {
+  match init() {
+    Err(error) => {
+      eprintln!("Error:\n\t{}", error);
+      std::process::exit(2)
+    },
+    Ok(code) => std::process::exit(code),
+  }
+}
@@ -0,0 +1,120 @@
+use std::fmt;
+use std::str::FromStr;
+use std::thread;
+use std::time::{
+  Duration,
+  Instant,
+};
+use crate::error::Error;
+
+
+// Sleeping for less than this is mostly scheduler overhead, so let the debt build up first.
+const MIN_SLEEP: Duration = Duration::from_millis(1);
+// Falling further behind than this (debugger, window drag, slow host) resyncs instead of bursting to catch up.
+const MAX_DRIFT: Duration = Duration::from_millis(250);
+
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Pacing {
+  Unthrottled,
+  RealTime,
+  Multiplier(f64),
+}
+
+impl FromStr for Pacing {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Pacing, Error> {
+    match s {
+      "unthrottled" | "max" => Ok(Pacing::Unthrottled),
+      "realtime" => Ok(Pacing::RealTime),
+      value => {
+        match value.trim_end_matches('x').parse::<f64>() {
+          Ok(multiplier) if multiplier.is_finite() && multiplier > 0.0 => Ok(Pacing::Multiplier(multiplier)),
+          _ => Err(Error::InvalidPacing(String::from(s))),
+        }
+      },
+    }
+  }
+}
+
+impl fmt::Display for Pacing {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Pacing::Unthrottled => write!(f, "unthrottled"),
+      Pacing::RealTime => write!(f, "realtime"),
+      Pacing::Multiplier(multiplier) => write!(f, "{}x", multiplier),
+    }
+  }
+}
+
+
+#[derive(Debug)]
+pub struct Clock {
+  hz: f64,
+  pacing: Pacing,
+  start: Instant,
+  cycles: u64,
+}
+
+impl Clock {
+  pub fn new(hz: f64, pacing: Pacing) -> Clock {
+    Clock {
+      hz,
+      pacing,
+      start: Instant::now(),
+      cycles: 0,
+    }
+  }
+
+  pub fn hz(&self) -> f64 {
+    self.hz
+  }
+
+  pub fn pacing(&self) -> Pacing {
+    self.pacing
+  }
+
+  pub fn set_pacing(&mut self, pacing: Pacing) {
+    self.pacing = pacing;
+    self.resync();
+  }
+
+  // Effective cycles per second, or None when running as fast as possible.
+  pub fn rate(&self) -> Option<f64> {
+    match self.pacing {
+      Pacing::Unthrottled => None,
+      Pacing::RealTime => Some(self.hz),
+      Pacing::Multiplier(multiplier) => Some(self.hz * multiplier),
+    }
+  }
+
+  pub fn resync(&mut self) {
+    self.start = Instant::now();
+    self.cycles = 0;
+  }
+
+  // Account for `cycles` emulated cycles, sleeping until wall-clock time catches up with them.
+  pub fn tick(&mut self, cycles: u64) {
+    let rate = match self.rate() {
+      None => return,
+      Some(rate) => rate,
+    };
+
+    self.cycles += cycles;
+    // Rates too small to time (or not positive, from a library caller) aren't paced.
+    let target = match Duration::try_from_secs_f64((self.cycles as f64) / rate) {
+      Ok(target) => target,
+      Err(_) => return,
+    };
+    let elapsed = self.start.elapsed();
+    if target > elapsed {
+      let ahead = target - elapsed;
+      if ahead >= MIN_SLEEP {
+        thread::sleep(ahead);
+      }
+    } else if (elapsed - target) > MAX_DRIFT {
+      self.resync();
+    }
+  }
+}
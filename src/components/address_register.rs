@@ -5,6 +5,11 @@ use crate::control::{
   Address,
 };
 use crate::error::Result;
+use crate::snapshot::{
+  Snapshot,
+  Writer,
+  Reader,
+};
 use super::BusComponent;
 
 
@@ -21,6 +26,14 @@ impl AddressRegister {
       value: 0x0000,
     }
   }
+
+  pub fn get(&self) -> u16 {
+    self.value
+  }
+
+  pub fn set(&mut self, value: u16) {
+    self.value = value;
+  }
 }
 
 impl fmt::Display for AddressRegister {
@@ -63,3 +76,16 @@ impl BusComponent for AddressRegister {
     }
   }
 }
+
+impl Snapshot for AddressRegister {
+  fn save(&self, w: &mut Writer) {
+    w.control(&self.control);
+    w.u16(self.value);
+  }
+
+  fn restore(&mut self, r: &mut Reader) -> Result<()> {
+    self.control = r.control()?;
+    self.value = r.u16()?;
+    Ok(())
+  }
+}
@@ -5,6 +5,11 @@ use crate::control::{
   AluMode,
 };
 use crate::error::Result;
+use crate::snapshot::{
+  Snapshot,
+  Writer,
+  Reader,
+};
 use super::flags::Flag;
 use super::BusComponent;
 
@@ -23,6 +28,14 @@ impl Alu {
     }
   }
 
+  pub fn get(&self, t: usize) -> u16 {
+    self.t[t]
+  }
+
+  pub fn set(&mut self, t: usize, value: u16) {
+    self.t[t] = value;
+  }
+
   fn flags(&self, zero: bool, sign: bool, carry: bool, overflow: bool) -> [bool; 8] {
     let mut flags = [false; 8];
     flags[Flag::Zero as usize] = zero;
@@ -131,3 +144,16 @@ impl BusComponent for Alu {
     }
   }
 }
+
+impl Snapshot for Alu {
+  fn save(&self, w: &mut Writer) {
+    w.control(&self.control);
+    w.words(&self.t);
+  }
+
+  fn restore(&mut self, r: &mut Reader) -> Result<()> {
+    self.control = r.control()?;
+    r.words(&mut self.t)?;
+    Ok(())
+  }
+}
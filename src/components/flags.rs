@@ -5,6 +5,11 @@ use crate::control::{
   Condition,
 };
 use crate::error::Result;
+use crate::snapshot::{
+  Snapshot,
+  Writer,
+  Reader,
+};
 use super::BusComponent;
 
 
@@ -42,7 +47,7 @@ impl Flags {
 
   pub fn test(&self, c: Control) -> bool {
     if let Some(interrupt) = c.branch.interrupt {
-      return interrupt <= 7 && self.flags[Flag::InterruptEnable as usize] && !self.flags[(interrupt as usize) + 8]
+      self.interruptible(interrupt)
     } else {
       c.branch.negate ^ match c.branch.condition {
         Condition::Always => true,
@@ -56,6 +61,11 @@ impl Flags {
     }
   }
 
+  /// True if hardware interrupt `interrupt` can be taken: InterruptEnable set and its mask flag clear.
+  pub fn interruptible(&self, interrupt: u16) -> bool {
+    interrupt <= 7 && self.flags[Flag::InterruptEnable as usize] && !self.flags[(interrupt as usize) + 8]
+  }
+
   pub fn set(&mut self, flag: Flag, value: bool) {
     self.flags[flag as usize] = value;
   }
@@ -68,13 +78,23 @@ impl Flags {
     }
   }
 
-  fn value(&self) -> u16 {
+  pub fn get(&self, flag: Flag) -> bool {
+    self.flags[flag as usize]
+  }
+
+  pub fn value(&self) -> u16 {
     let mut value = 0;
     for i in 0..16 {
       value |= (self.flags[i] as u16) << i;
     }
     value
   }
+
+  pub fn set_value(&mut self, value: u16) {
+    for i in 0..16 {
+      self.flags[i] = ((value >> i) & 1) != 0;
+    }
+  }
 }
 
 impl fmt::Display for Flags {
@@ -124,3 +144,17 @@ impl BusComponent for Flags {
     }
   }
 }
+
+impl Snapshot for Flags {
+  fn save(&self, w: &mut Writer) {
+    w.control(&self.control);
+    w.u16(self.value());
+  }
+
+  fn restore(&mut self, r: &mut Reader) -> Result<()> {
+    self.control = r.control()?;
+    let value = r.u16()?;
+    self.set_value(value);
+    Ok(())
+  }
+}
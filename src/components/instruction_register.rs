@@ -5,6 +5,11 @@ use crate::control::{
   IMode,
 };
 use crate::error::Result;
+use crate::snapshot::{
+  Snapshot,
+  Writer,
+  Reader,
+};
 use super::BusComponent;
 
 
@@ -77,3 +82,16 @@ impl BusComponent for InstructionRegister {
     }
   }
 }
+
+impl Snapshot for InstructionRegister {
+  fn save(&self, w: &mut Writer) {
+    w.control(&self.control);
+    w.u16(self.value);
+  }
+
+  fn restore(&mut self, r: &mut Reader) -> Result<()> {
+    self.control = r.control()?;
+    self.value = r.u16()?;
+    Ok(())
+  }
+}
@@ -2,6 +2,11 @@
 use std::fmt;
 use crate::control::Control;
 use crate::error::Result;
+use crate::snapshot::{
+  Snapshot,
+  Writer,
+  Reader,
+};
 use super::BusComponent;
 
 
@@ -19,6 +24,14 @@ impl LinkRegister {
     }
   }
 
+  pub fn get(&self) -> u16 {
+    self.value
+  }
+
+  pub fn set(&mut self, value: u16) {
+    self.value = value;
+  }
+
   pub fn link(&mut self, value: u16) {
     if self.control.link {
       self.value = value;
@@ -74,3 +87,16 @@ impl BusComponent for LinkRegister {
     }
   }
 }
+
+impl Snapshot for LinkRegister {
+  fn save(&self, w: &mut Writer) {
+    w.control(&self.control);
+    w.u16(self.value);
+  }
+
+  fn restore(&mut self, r: &mut Reader) -> Result<()> {
+    self.control = r.control()?;
+    self.value = r.u16()?;
+    Ok(())
+  }
+}
@@ -5,6 +5,11 @@ use crate::control::{
   Address,
 };
 use crate::error::Result;
+use crate::snapshot::{
+  Snapshot,
+  Writer,
+  Reader,
+};
 use super::BusComponent;
 
 
@@ -25,6 +30,14 @@ impl ProgramCounter {
   pub fn link(&self) -> u16 {
     self.value
   }
+
+  pub fn get(&self) -> u16 {
+    self.value
+  }
+
+  pub fn set(&mut self, value: u16) {
+    self.value = value;
+  }
 }
 
 impl fmt::Display for ProgramCounter {
@@ -86,3 +99,16 @@ impl BusComponent for ProgramCounter {
     }
   }
 }
+
+impl Snapshot for ProgramCounter {
+  fn save(&self, w: &mut Writer) {
+    w.control(&self.control);
+    w.u16(self.value);
+  }
+
+  fn restore(&mut self, r: &mut Reader) -> Result<()> {
+    self.control = r.control()?;
+    self.value = r.u16()?;
+    Ok(())
+  }
+}
@@ -5,6 +5,11 @@ use crate::control::{
   Register,
 };
 use crate::error::Result;
+use crate::snapshot::{
+  Snapshot,
+  Writer,
+  Reader,
+};
 use super::BusComponent;
 
 
@@ -22,6 +27,14 @@ impl RegisterFile {
     }
   }
 
+  pub fn get(&self, r: usize) -> u16 {
+    self.values[r]
+  }
+
+  pub fn set(&mut self, r: usize, value: u16) {
+    self.values[r] = value;
+  }
+
   fn fmt_r(&self, f: &mut fmt::Formatter, r: Register, name: &'static str) -> fmt::Result {
     let load = self.control.register.load;
     let out = self.control.register.out;
@@ -84,3 +97,16 @@ impl BusComponent for RegisterFile {
     }
   }
 }
+
+impl Snapshot for RegisterFile {
+  fn save(&self, w: &mut Writer) {
+    w.control(&self.control);
+    w.words(&self.values);
+  }
+
+  fn restore(&mut self, r: &mut Reader) -> Result<()> {
+    self.control = r.control()?;
+    r.words(&mut self.values)?;
+    Ok(())
+  }
+}
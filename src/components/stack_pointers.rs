@@ -5,6 +5,11 @@ use crate::control::{
   Address,
 };
 use crate::error::Result;
+use crate::snapshot::{
+  Snapshot,
+  Writer,
+  Reader,
+};
 use super::BusComponent;
 
 
@@ -22,6 +27,14 @@ impl StackPointers {
     }
   }
 
+  pub fn get(&self, s: usize) -> u16 {
+    self.values[s]
+  }
+
+  pub fn set(&mut self, s: usize, value: u16) {
+    self.values[s] = value;
+  }
+
   fn fmt_s(&self, f: &mut fmt::Formatter, s: usize) -> fmt::Result {
     match self.control.address {
       Address::StackZero if s == 0 => write!(f, "[S0]")?,
@@ -103,3 +116,16 @@ impl BusComponent for StackPointers {
     }
   }
 }
+
+impl Snapshot for StackPointers {
+  fn save(&self, w: &mut Writer) {
+    w.control(&self.control);
+    w.words(&self.values);
+  }
+
+  fn restore(&mut self, r: &mut Reader) -> Result<()> {
+    self.control = r.control()?;
+    r.words(&mut self.values)?;
+    Ok(())
+  }
+}
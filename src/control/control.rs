@@ -1,3 +1,8 @@
+use crate::error::{
+  Result,
+  Error,
+};
+
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Address {
@@ -128,6 +133,12 @@ pub struct Control {
   pub halt: bool,
 }
 
+impl Default for Control {
+  fn default() -> Control {
+    Control::new()
+  }
+}
+
 impl Control {
   pub fn new() -> Control {
     Control {
@@ -178,4 +189,162 @@ impl Control {
     }
     s
   }
+
+  // True if any component takes its value from the data bus this cycle.
+  pub fn loads(&self) -> bool {
+    self.register.load != Register::None || self.alu.t.iter().any(|t| t.load) || self.flags.load ||
+      self.pc.load || self.lr.load || self.s.iter().any(|s| s.load) || self.a.load || self.i.load ||
+      self.memory.load
+  }
+}
+
+// Packed control word, least significant bit first:
+//  0- 1 address (A, PC, S0, S1)       2- 5 register.load (0-7, 8=None)  6- 9 register.out
+// 10-12 alu.mode (Add And Or Xor Shift)
+// 13 alu.t0.load  14 alu.t1.load  15 alu.out  16 alu.set_flags  17 alu.t0_zero  18 alu.t1_invert
+// 19 alu.carry_invert  20 alu.direction  21 alu.extend  22 flags.load  23 flags.out
+// 24 pc.load  25 pc.out  26 pc.increment  27 lr.load  28 lr.out  29 lr.increment
+// 30-33 s0 load/out/count/direction  34-37 s1 load/out/count/direction  38 a.load
+// 39 i.load  40-42 i.mode (None SignedByte UnsignedByte Bitmask Interrupt Startup)
+// 43 memory.load  44 memory.out  45 branch.negate
+// 46-48 branch.condition (Always Zero Sign Carry CarryNotZero Overflow OverflowNotZero)
+// 49 branch.interrupt is set  50-52 branch.interrupt  53 link  54 stack_sequence  55 halt
+pub const CONTROL_BITS: u32 = 56;
+
+fn field(bits: u64, offset: u32, width: u32) -> u64 {
+  (bits >> offset) & ((1 << width) - 1)
+}
+
+fn flag(bits: u64, offset: u32) -> bool {
+  field(bits, offset, 1) != 0
+}
+
+fn register(bits: u64, offset: u32) -> Result<Register> {
+  match field(bits, offset, 4) {
+    0 => Ok(Register::Zero), 1 => Ok(Register::One), 2 => Ok(Register::Two), 3 => Ok(Register::Three),
+    4 => Ok(Register::Four), 5 => Ok(Register::Five), 6 => Ok(Register::Six), 7 => Ok(Register::Seven),
+    8 => Ok(Register::None),
+    _ => Err(Error::InvalidControlWord(bits)),
+  }
+}
+
+impl Control {
+  pub fn bits(&self) -> u64 {
+    let stack = |s: &StackRegister| {
+      (s.load as u64) | ((s.out as u64) << 1) | ((s.count as u64) << 2) | ((s.direction as u64) << 3)
+    };
+    (self.address as u64) |
+      ((self.register.load as u64) << 2) |
+      ((self.register.out as u64) << 6) |
+      ((self.alu.mode as u64) << 10) |
+      ((self.alu.t[0].load as u64) << 13) |
+      ((self.alu.t[1].load as u64) << 14) |
+      ((self.alu.out as u64) << 15) |
+      ((self.alu.set_flags as u64) << 16) |
+      ((self.alu.t0_zero as u64) << 17) |
+      ((self.alu.t1_invert as u64) << 18) |
+      ((self.alu.carry_invert as u64) << 19) |
+      ((self.alu.direction as u64) << 20) |
+      ((self.alu.extend as u64) << 21) |
+      ((self.flags.load as u64) << 22) |
+      ((self.flags.out as u64) << 23) |
+      ((self.pc.load as u64) << 24) |
+      ((self.pc.out as u64) << 25) |
+      ((self.pc.increment as u64) << 26) |
+      ((self.lr.load as u64) << 27) |
+      ((self.lr.out as u64) << 28) |
+      ((self.lr.increment as u64) << 29) |
+      (stack(&self.s[0]) << 30) |
+      (stack(&self.s[1]) << 34) |
+      ((self.a.load as u64) << 38) |
+      ((self.i.load as u64) << 39) |
+      ((self.i.mode as u64) << 40) |
+      ((self.memory.load as u64) << 43) |
+      ((self.memory.out as u64) << 44) |
+      ((self.branch.negate as u64) << 45) |
+      ((self.branch.condition as u64) << 46) |
+      ((self.branch.interrupt.is_some() as u64) << 49) |
+      ((self.branch.interrupt.unwrap_or(0) as u64 & 0x7) << 50) |
+      ((self.link as u64) << 53) |
+      ((self.stack_sequence as u64) << 54) |
+      ((self.halt as u64) << 55)
+  }
+
+  pub fn from_bits(bits: u64) -> Result<Control> {
+    let invalid = || Error::InvalidControlWord(bits);
+    if field(bits, CONTROL_BITS, 64 - CONTROL_BITS) != 0 {
+      return Err(invalid())
+    }
+    let stack = |offset| StackRegister {
+      load: flag(bits, offset),
+      out: flag(bits, offset + 1),
+      count: flag(bits, offset + 2),
+      direction: flag(bits, offset + 3),
+    };
+    Ok(Control {
+      address: match field(bits, 0, 2) {
+        0 => Address::A,
+        1 => Address::ProgramCounter,
+        2 => Address::StackZero,
+        _ => Address::StackOne,
+      },
+      register: RegisterFile { load: register(bits, 2)?, out: register(bits, 6)? },
+      alu: Alu {
+        mode: match field(bits, 10, 3) {
+          0 => AluMode::Add,
+          1 => AluMode::And,
+          2 => AluMode::Or,
+          3 => AluMode::Xor,
+          4 => AluMode::Shift,
+          _ => return Err(invalid()),
+        },
+        t: [
+          LoadRegister { load: flag(bits, 13) },
+          LoadRegister { load: flag(bits, 14) },
+        ],
+        out: flag(bits, 15),
+        set_flags: flag(bits, 16),
+        t0_zero: flag(bits, 17),
+        t1_invert: flag(bits, 18),
+        carry_invert: flag(bits, 19),
+        direction: flag(bits, 20),
+        extend: flag(bits, 21),
+      },
+      flags: Bidirectional { load: flag(bits, 22), out: flag(bits, 23) },
+      pc: ProgramRegister { load: flag(bits, 24), out: flag(bits, 25), increment: flag(bits, 26) },
+      lr: ProgramRegister { load: flag(bits, 27), out: flag(bits, 28), increment: flag(bits, 29) },
+      s: [stack(30), stack(34)],
+      a: LoadRegister { load: flag(bits, 38) },
+      i: InstructionRegister {
+        load: flag(bits, 39),
+        mode: match field(bits, 40, 3) {
+          0 => IMode::None,
+          1 => IMode::SignedByte,
+          2 => IMode::UnsignedByte,
+          3 => IMode::Bitmask,
+          4 => IMode::Interrupt,
+          5 => IMode::Startup,
+          _ => return Err(invalid()),
+        },
+      },
+      memory: Bidirectional { load: flag(bits, 43), out: flag(bits, 44) },
+      branch: Branch {
+        negate: flag(bits, 45),
+        condition: match field(bits, 46, 3) {
+          0 => Condition::Always,
+          1 => Condition::Zero,
+          2 => Condition::Sign,
+          3 => Condition::Carry,
+          4 => Condition::CarryNotZero,
+          5 => Condition::Overflow,
+          6 => Condition::OverflowNotZero,
+          _ => return Err(invalid()),
+        },
+        interrupt: if flag(bits, 49) { Some(field(bits, 50, 3) as u16) } else { None },
+      },
+      link: flag(bits, 53),
+      stack_sequence: flag(bits, 54),
+      halt: flag(bits, 55),
+    })
+  }
 }
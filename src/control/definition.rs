@@ -0,0 +1,189 @@
+use std::fmt;
+use std::fs;
+use crate::error::{
+  Result,
+  Error,
+};
+use super::microcode::{self, Microcode, MicrocodeArray};
+use super::instructions::{Instruction, Instructions};
+
+
+const GROUPS: usize = 23;
+
+/// Microcode steps and the instruction table that sequences them, as loaded from a definition file.
+///
+/// The file is plain text, one step or opcode group per line, `#` to end of line is a comment:
+///
+/// ```text
+/// [microcode]
+/// 0 address=ProgramCounter data=Memory,I pc_increment
+/// 1 data=RegisterOne,RegisterZero
+/// ...
+/// [instructions]
+/// fetch 0
+/// init 45 43
+/// 0 argument None LD r,r: 1 | LD r,(r): 2 3 | LD r,word: 4 | LD r,(word): 5 3 | LD r,(r+r): 6 7 8 3
+/// 1 normal None LD r,b: 9
+/// 11 stack PUT/POP: 29
+/// ...
+/// ```
+///
+/// Steps are numbered from 0 in order. Opcodes map to groups 0-22 through the built-in decode table,
+/// so every group needs a line. `microcode definition` writes out the built-in tables in this format.
+pub struct Definition {
+  microcode: MicrocodeArray,
+  fetch: usize,
+  init: Vec<usize>,
+  instructions: [Instruction; GROUPS],
+}
+
+fn invalid(line: usize, message: String) -> Error {
+  Error::InvalidDefinition(Some(line), message)
+}
+
+fn index(line: usize, text: &str) -> Result<usize> {
+  text.parse::<usize>().map_err(|_| invalid(line, format!("Expected a number, found '{}'.", text)))
+}
+
+impl Definition {
+  pub fn built_in() -> Definition {
+    Definition {
+      microcode: microcode::array(),
+      fetch: 0,
+      init: vec![45, 43],
+      instructions: Instructions::array(),
+    }
+  }
+
+  pub fn load(filename: &str) -> Result<Definition> {
+    match fs::read_to_string(filename) {
+      Err(error) => Err(Error::File(String::from(filename), error)),
+      Ok(text) => Definition::parse(&text),
+    }
+  }
+
+  pub fn parse(text: &str) -> Result<Definition> {
+    let mut section = None;
+    let mut microcode = Vec::new();
+    let mut fetch = None;
+    let mut init = None;
+    let mut groups: Vec<Option<(usize, Instruction)>> = (0..GROUPS).map(|_| None).collect();
+
+    for (n, line) in text.lines().enumerate() {
+      let n = n + 1;
+      let line = line.split('#').next().unwrap_or("").trim();
+      if line.is_empty() {
+        continue
+      }
+      if line.starts_with('[') {
+        section = match line {
+          "[microcode]" => Some(true),
+          "[instructions]" => Some(false),
+          _ => return Err(invalid(n, format!("Unknown section '{}', expected [microcode] or [instructions].", line))),
+        };
+        continue
+      }
+      let (first, rest) = line.split_at(line.find(char::is_whitespace).unwrap_or(line.len()));
+      match section {
+        None => return Err(invalid(n, String::from("Expected [microcode] or [instructions] first."))),
+        Some(true) => {
+          if index(n, first)? != microcode.len() {
+            return Err(invalid(n, format!("Expected microcode step {}, found {}.", microcode.len(), first)))
+          }
+          microcode.push((n, Microcode::parse(rest).map_err(|message| invalid(n, message))?));
+        },
+        Some(false) => match first {
+          "fetch" => fetch = Some((n, index(n, rest.trim())?)),
+          "init" => init = Some((n, rest.split_whitespace().map(|i| index(n, i)).collect::<Result<Vec<usize>>>()?)),
+          _ => {
+            let group = index(n, first)?;
+            if group >= GROUPS {
+              return Err(invalid(n, format!("Group {} is out of range, opcodes decode to groups 0-{}.", group, GROUPS - 1)))
+            }
+            if let Some((line, _)) = groups[group] {
+              return Err(invalid(n, format!("Group {} is already defined on line {}.", group, line)))
+            }
+            groups[group] = Some((n, Instruction::parse(rest).map_err(|message| invalid(n, message))?));
+          },
+        },
+      }
+    }
+
+    let missing = |what: &str| Error::InvalidDefinition(None, format!("No {} defined.", what));
+    let (fetch_line, fetch) = fetch.ok_or_else(|| missing("'fetch' step"))?;
+    let (init_line, init) = init.ok_or_else(|| missing("'init' sequence"))?;
+    if microcode.is_empty() {
+      return Err(missing("[microcode] steps"))
+    }
+    let check = |line: usize, name: &str, steps: &[usize]| match steps.iter().find(|i| **i >= microcode.len()) {
+      Some(i) => Err(invalid(line, format!("{} uses microcode step {}, but only 0-{} are defined.", name, i, microcode.len() - 1))),
+      None => Ok(()),
+    };
+    check(fetch_line, "FETCH", &[fetch])?;
+    check(init_line, "INIT", &init)?;
+    let mut instructions = Vec::new();
+    for (group, instruction) in groups.into_iter().enumerate() {
+      let (line, instruction) = instruction.ok_or_else(|| missing(&format!("instruction for group {}", group)))?;
+      for (name, steps) in instruction.sequences() {
+        check(line, name, steps)?;
+      }
+      instructions.push(instruction);
+    }
+
+    let mut instructions = instructions.into_iter();
+    let mut next = || instructions.next().unwrap();
+    Ok(Definition {
+      microcode: microcode.into_iter().map(|(_, m)| m).collect(),
+      fetch,
+      init,
+      instructions: [
+        next(), next(), next(), next(), next(), next(), next(), next(), next(), next(), next(), next(),
+        next(), next(), next(), next(), next(), next(), next(), next(), next(), next(), next(),
+      ],
+    })
+  }
+
+  pub fn into_parts(self) -> (MicrocodeArray, usize, Vec<usize>, [Instruction; GROUPS]) {
+    (self.microcode, self.fetch, self.init, self.instructions)
+  }
+}
+
+impl fmt::Display for Definition {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    // Each step is commented with the instructions that use it.
+    let mut users = vec![Vec::new(); self.microcode.len()];
+    users[self.fetch].push("FETCH");
+    for i in self.init.iter() {
+      users[*i].push("INIT");
+    }
+    for instruction in self.instructions.iter() {
+      for (name, steps) in instruction.sequences() {
+        for i in steps {
+          if !users[*i].contains(&name) {
+            users[*i].push(name);
+          }
+        }
+      }
+    }
+
+    writeln!(f, "# Microcode definition, load with --microcode <file>.")?;
+    writeln!(f)?;
+    writeln!(f, "[microcode]")?;
+    writeln!(f, "# <step> [address=<select>] [data=<select>[:<direction>],<select>[:<direction>]] [alu=<mode>]")?;
+    writeln!(f, "#        [s_count=<direction>] [set_flags] [pc_increment] [halt]")?;
+    for (i, m) in self.microcode.iter().enumerate() {
+      writeln!(f, "{:<3}{:<72}# {}", i, m.to_string(), users[i].join(" / "))?;
+    }
+    writeln!(f)?;
+    writeln!(f, "[instructions]")?;
+    writeln!(f, "# <group> normal <branch> <name>: <step>...")?;
+    writeln!(f, "# <group> argument <branch> <name>: <step>... | (one per argument mode, 5 in all)")?;
+    writeln!(f, "# <group> stack <name>: <step>")?;
+    writeln!(f, "fetch {}", self.fetch)?;
+    writeln!(f, "init {}", self.init.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(" "))?;
+    for (group, instruction) in self.instructions.iter().enumerate() {
+      writeln!(f, "{:<3}{}", group, instruction)?;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,166 @@
+use super::instructions::{
+  argument_mode,
+  group,
+};
+
+
+// Disassembly follows the emulator's decode (DECODE_TABLE and the microcode), not the assembler,
+// so it shows what the hardware will actually do with a word.
+
+const REGISTERS: [&str; 8] = ["A", "B", "C", "D", "E", "X", "Y", "Z"];
+const EXTRA: [&str; 4] = ["S0", "S1", "PC", "LR"];
+const BINARY: [&str; 8] = ["ADD", "AND", "CMP", "SUB", "CPN", "SBN", "OR", "XOR"];
+const UNARY: [&str; 8] = ["NEG", "NOT", "???", "???", "SL", "???", "LSR", "ASR"];
+
+fn r(op: u16, offset: u16) -> &'static str {
+  REGISTERS[((op >> offset) & 0x0007) as usize]
+}
+
+fn x(op: u16) -> &'static str {
+  EXTRA[((op >> 3) & 0x0003) as usize]
+}
+
+fn signed(op: u16) -> i16 {
+  (((op >> 3) & 0x00FF) as i8) as i16
+}
+
+fn unsigned(op: u16) -> u16 {
+  (op >> 3) & 0x00FF
+}
+
+fn bit(op: u16) -> u16 {
+  (op >> 3) & 0x000F
+}
+
+fn word(word: Option<u16>) -> String {
+  match word {
+    Some(word) => format!("0x{:04X}", word),
+    None => String::from("????"),
+  }
+}
+
+fn condition(op: u16) -> &'static str {
+  let negate = (op & 0x0800) != 0;
+  match (op & 0x0007, negate) {
+    (0, false) => "",
+    (0, true)  => "!",
+    (2, false) => "Z.",
+    (2, true)  => "Z!",
+    (3, false) => "N.",
+    (3, true)  => "N!",
+    (4, false) => "C.",
+    (4, true)  => "C!",
+    (5, false) => ">",
+    (5, true)  => "<=",
+    (6, false) => "V.",
+    (6, true)  => "V!",
+    (7, false) => "Gt",
+    (7, true)  => "Le",
+    _ => "?.",
+  }
+}
+
+// Addressing mode of the `a` argument groups, and whether it takes a trailing word.
+fn argument(op: u16, next: Option<u16>) -> (String, u16) {
+  match argument_mode(op) {
+    0 => (String::from(r(op, 3)), 1),
+    1 => (format!("({})", r(op, 3)), 1),
+    2 => (word(next), 2),
+    3 => (format!("({})", word(next)), 2),
+    _ => (format!("({}+{})", r(op, 3), r(op, 6)), 1),
+  }
+}
+
+fn load(load: bool, register: &str, argument: &str) -> String {
+  if load {
+    format!("LD   {},{}", register, argument)
+  } else {
+    format!("LD   {},{}", argument, register)
+  }
+}
+
+fn jump(op: u16, link: u16, argument: &str) -> String {
+  let name = if (op & link) != 0 { "JML" } else { "JMP" };
+  format!("{}{} {}", condition(op), name, argument)
+}
+
+/// Disassembles the instruction word `op` found at `address`.
+///
+/// `next` is the word after it, used by instructions with a word argument.
+/// Returns the text and the number of words the instruction occupies.
+pub fn disassemble(address: u16, op: u16, next: Option<u16>) -> (String, u16) {
+  match group(op) {
+    0 => {
+      let (a, len) = argument(op, next);
+      if argument_mode(op) == 0 {
+        (format!("LD   {},{}", r(op, 0), a), len)
+      } else {
+        (load((op & 0x0400) != 0, r(op, 0), &a), len)
+      }
+    },
+    1 => (format!("LD   {},{}", r(op, 0), signed(op)), 1),
+    2 => (load((op & 0x0800) != 0, r(op, 0), &format!("(0x{:02X})", unsigned(op))), 1),
+
+    3 => {
+      let (a, len) = argument(op, next);
+      (format!("{:4} {},{}", BINARY[((op >> 10) & 0x0007) as usize], r(op, 0), a), len)
+    },
+    4 => (format!("{:4} {},{}", BINARY[((op >> 10) & 0x0006) as usize], r(op, 0), signed(op)), 1),
+    5 => (format!("{:4} {},(0x{:02X})", BINARY[((op >> 10) & 0x0006) as usize], r(op, 0), unsigned(op)), 1),
+
+    6 => {
+      let (a, len) = argument(op, next);
+      (jump(op, 0x0400, &a), len)
+    },
+    7 => {
+      let target = address.wrapping_add(1).wrapping_add(signed(op) as u16);
+      (jump(op, 0x1000, &format!("{:+} ; 0x{:04X}", signed(op), target)), 1)
+    },
+    8 => (jump(op, 0x1000, &format!("(0x{:02X})", unsigned(op))), 1),
+
+    9 => {
+      let name = if (op & 0x0400) != 0 { "RTL" } else { "RET" };
+      (format!("{}{}", condition(op), name), 1)
+    },
+    10 => {
+      let name = if (op & 0x0400) != 0 { "RTL" } else { "RET" };
+      (format!("{}{}{}", condition(op), name, (op >> 9) & 1), 1)
+    },
+
+    11 => {
+      let name = if (op & 0x0400) != 0 { "POP" } else { "PUT" };
+      let stack = if (op & 0x0200) != 0 { "D" } else { "" };
+      let mut registers: Vec<&str> = (0..8)
+        .filter(|i| (op & (1 << i)) != 0)
+        .map(|i| REGISTERS[i])
+        .collect();
+      if (op & 0x0100) != 0 {
+        registers.push("F");
+      }
+      if (op & 0x0800) != 0 {
+        registers.push(if (op & 0x0400) != 0 { "PC" } else { "LR" });
+      }
+      (format!("{}{} [{}]", name, stack, registers.join(",")), 1)
+    },
+
+    12 => (format!("SET  F,{},{}", bit(op), (op >> 7) & 1), 1),
+    13 => (format!("SET  {},{},{}", r(op, 0), bit(op), (op >> 7) & 1), 1),
+    14 => (format!("TEST {},{}", r(op, 0), bit(op)), 1),
+    15 => (format!("{:4} {}", UNARY[((op >> 3) & 0x0007) as usize], r(op, 0)), 1),
+
+    16 => (load((op & 0x0400) != 0, x(op), r(op, 0)), 1),
+    17 => (load((op & 0x0400) != 0, x(op), &format!("({})", r(op, 0))), 1),
+    18 => (load((op & 0x0400) != 0, x(op), &word(next)), 2),
+    19 => (load((op & 0x0400) != 0, x(op), &format!("({})", word(next))), 2),
+    20 => (load((op & 0x0400) != 0, x(op), &format!("({}+{})", r(op, 0), r(op, 6))), 1),
+
+    21 => {
+      let name = if (op & 0x0080) != 0 { "BRK" } else { "INT" };
+      (format!("{}  {}", name, (op >> 3) & 0x0007), 1)
+    },
+    _ => {
+      let name = if (op & 0x0080) != 0 { "HLT" } else { "NOP" };
+      (String::from(name), 1)
+    },
+  }
+}
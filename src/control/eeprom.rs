@@ -0,0 +1,139 @@
+use std::fs;
+use std::io::prelude::*;
+use std::fs::File;
+use crate::error::{
+  Result,
+  Error,
+};
+use super::control::Control;
+use super::instructions::Instructions;
+
+
+// The control store as burned: one 64-bit word per (opcode, step), at address `op << 4 | step`.
+// The decode tables are folded into the addressing, so every opcode has its own 16 words:
+//   steps 0x0-0xB  the instruction's micro-steps, in order; the first missing step means FETCH next
+//   steps 0xC-0xE  the INIT sequence, entered at 0xC on reset (I is cleared, so only opcode 0 is used)
+//   step  0xF      the FETCH word, the same for every opcode
+// Word layout, least significant bit first:
+//    0-55 the packed control word, as `Control::bits`
+//   56-61 microcode index of the step, only used by traces and the debugger
+//      62 opcode decodes, set at step 0 only; tells an opcode with no micro-steps (e.g. PUT/POP of
+//         no registers) from one that doesn't decode
+//      63 step present
+// Chip `n` holds bits `8n` to `8n + 7` of every word, so a full set is 8 chips of 1 MiB (20 address lines).
+pub const CHIPS: usize = 8;
+pub const CHIP_SIZE: usize = 1 << 20;
+
+const STEPS: usize = 0xC;
+const INIT_STEP: usize = 0xC;
+const FETCH_STEP: usize = 0xF;
+const INDEX: u32 = 56;
+const DECODES: u64 = 1 << 62;
+const PRESENT: u64 = 1 << 63;
+
+fn address(op: u16, step: usize) -> usize {
+  ((op as usize) << 4) | step
+}
+
+// Fails for microcode indexes that don't fit bits 56-61, rather than spilling into DECODES and PRESENT.
+fn word(name: &str, index: usize, c: &Control) -> Result<u64> {
+  if index > 0x3F {
+    return Err(Error::InvalidImage(format!("{} uses microcode step {}, but an image only holds indexes 0-63.", name, index)))
+  }
+  Ok(PRESENT | ((index as u64) << INDEX) | c.bits())
+}
+
+fn read(images: &[Vec<u8>], address: usize) -> u64 {
+  (0..CHIPS).fold(0u64, |word, chip| word | ((images[chip][address] as u64) << (8 * chip)))
+}
+
+fn step(images: &[Vec<u8>], address: usize) -> Result<Option<(usize, Control)>> {
+  let word = read(images, address);
+  if word & PRESENT == 0 {
+    Ok(None)
+  } else {
+    let control = Control::from_bits(word & ((1 << INDEX) - 1))?;
+    Ok(Some((((word >> INDEX) & 0x3F) as usize, control)))
+  }
+}
+
+fn steps(images: &[Vec<u8>], op: u16, first: usize, count: usize) -> Result<Vec<(usize, Control)>> {
+  let mut out = Vec::new();
+  for i in first..(first + count) {
+    match step(images, address(op, i))? {
+      Some(step) => out.push(step),
+      None => break,
+    }
+  }
+  Ok(out)
+}
+
+pub fn images(instructions: &Instructions) -> Result<Vec<Vec<u8>>> {
+  let mut words = vec![0u64; CHIP_SIZE];
+  let init = instructions.init();
+  if init.steps().len() > FETCH_STEP - INIT_STEP {
+    return Err(Error::InvalidImage(format!("INIT has {} micro-steps, more than the {} an image holds.",
+      init.steps().len(), FETCH_STEP - INIT_STEP)))
+  }
+  let init = init.steps().iter()
+    .map(|(index, c)| word(init.name(), *index, c))
+    .collect::<Result<Vec<u64>>>()?;
+  let fetch = word("FETCH", 0, &instructions.fetch())?;
+  for op in 0..=0xFFFF {
+    if let Some(instruction) = instructions.decoded(op) {
+      if instruction.steps().len() > STEPS {
+        return Err(Error::InvalidImage(format!("0x{:04X} ({}) has {} micro-steps, more than the {} an image holds.",
+          op, instruction.name(), instruction.steps().len(), STEPS)))
+      }
+      for (i, (index, c)) in instruction.steps().iter().enumerate() {
+        words[address(op, i)] = word(instruction.name(), *index, c)?;
+      }
+      words[address(op, 0)] |= DECODES;
+    }
+    words[address(op, INIT_STEP)..address(op, INIT_STEP + init.len())].copy_from_slice(&init);
+    words[address(op, FETCH_STEP)] = fetch;
+  }
+  Ok((0..CHIPS).map(|chip| words.iter().map(|word| (word >> (8 * chip)) as u8).collect()).collect())
+}
+
+pub fn instructions(images: &[Vec<u8>]) -> Result<Instructions> {
+  if images.len() != CHIPS {
+    return Err(Error::InvalidImage(format!("Expected {} images, found {}.", CHIPS, images.len())))
+  }
+  if let Some((chip, image)) = images.iter().enumerate().find(|(_, image)| image.len() != CHIP_SIZE) {
+    return Err(Error::InvalidImage(format!("Image {} is {} bytes, expected {}.", chip, image.len(), CHIP_SIZE)))
+  }
+  let fetch = match step(images, address(0x0000, FETCH_STEP))? {
+    Some((_, c)) => c,
+    None => return Err(Error::InvalidImage(String::from("No FETCH word at step 0xF."))),
+  };
+  let init = steps(images, 0x0000, INIT_STEP, FETCH_STEP - INIT_STEP)?;
+  let decoded = (0..=0xFFFF)
+    .map(|op| if read(images, address(op, 0)) & DECODES == 0 { Ok(None) } else { steps(images, op, 0, STEPS).map(Some) })
+    .collect::<Result<Vec<_>>>()?;
+  Ok(Instructions::from_steps(fetch, init, decoded))
+}
+
+/// File holding chip `chip` of the set written to `prefix`.
+pub fn image_filename(prefix: &str, chip: usize) -> String {
+  format!("{}.{}.bin", prefix, chip)
+}
+
+/// Writes `images` to `<prefix>.0.bin` through `<prefix>.7.bin`.
+pub fn write_images(prefix: &str, images: &[Vec<u8>]) -> Result<()> {
+  for (chip, image) in images.iter().enumerate() {
+    let filename = image_filename(prefix, chip);
+    if let Err(error) = File::create(&filename).and_then(|mut f| f.write_all(image)) {
+      return Err(Error::File(filename, error))
+    }
+  }
+  Ok(())
+}
+
+/// Reads back a set written by `write_images`.
+pub fn read_images(prefix: &str) -> Result<Vec<Vec<u8>>> {
+  (0..CHIPS).map(|chip| {
+    let filename = image_filename(prefix, chip);
+    fs::read(&filename).map_err(|error| Error::File(filename, error))
+  }).collect()
+}
@@ -1,33 +1,76 @@
 
 use std::fmt;
+use std::str::FromStr;
+use std::rc::Rc;
+use std::sync::Mutex;
 
-use super::microcode::MicrocodeArray;
+use super::microcode::Microcode;
 use super::control::{Control, Register};
 use crate::error::{Error, Result};
 
 
+// Names from definition files, each leaked once however many times it's parsed.
+static NAMES: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+fn intern(name: &str) -> &'static str {
+  let mut names = NAMES.lock().unwrap();
+  match names.iter().find(|interned| **interned == name) {
+    Some(interned) => interned,
+    None => {
+      let interned = &*Box::leak(String::from(name).into_boxed_str());
+      names.push(interned);
+      interned
+    },
+  }
+}
+
+// Steps are shared with the decode cache, so handing out an `Iter` doesn't allocate.
 #[derive(Clone)]
 pub struct Iter {
   name: &'static str,
   op: u16,
   last_index: Option<usize>,
-  iter: std::iter::Peekable<std::vec::IntoIter<(usize, Control)>>,
+  steps: Rc<[(usize, Control)]>,
+  next: usize,
 }
 impl Iter {
   fn new(name: &'static str, op: u16, vec: Vec<(usize, Control)>) -> Iter {
-    Iter { name, op, last_index: None, iter: vec.into_iter().peekable() }
+    Iter { name, op, last_index: None, steps: vec.into(), next: 0 }
   }
 
   pub fn peek(&mut self) -> Option<&(usize, Control)> {
-    self.iter.peek()
+    self.steps.get(self.next)
+  }
+
+  pub fn name(&self) -> &'static str {
+    self.name
+  }
+
+  pub fn last_index(&self) -> Option<usize> {
+    self.last_index
+  }
+
+  pub fn op(&self) -> u16 {
+    self.op
+  }
+
+  // Every micro-step, with its microcode index.
+  pub fn steps(&self) -> &[(usize, Control)] {
+    &self.steps
+  }
+
+  // Micro-steps not yet handed out by `next`.
+  pub fn remaining(&self) -> usize {
+    self.steps.len() - self.next
   }
 }
 impl Iterator for Iter {
   type Item = Control;
 
   fn next(&mut self) -> Option<Control> {
-    match self.iter.next() {
-      Some((index, c)) => {
+    match self.steps.get(self.next) {
+      Some(&(index, c)) => {
+        self.next += 1;
         self.last_index = Some(index);
         Some(c)
       },
@@ -48,14 +91,33 @@ impl fmt::Debug for Iter {
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Branch {
   None,
   Near,
   Far,
   Interrupt,
 }
+
+const BRANCHES: [(&str, Branch); 4] = [
+  ("None", Branch::None),
+  ("Near", Branch::Near),
+  ("Far", Branch::Far),
+  ("Interrupt", Branch::Interrupt),
+];
+
 impl Branch {
+  fn parse(text: &str) -> std::result::Result<Branch, String> {
+    match BRANCHES.iter().find(|(name, _)| *name == text) {
+      Some((_, branch)) => Ok(*branch),
+      None => Err(format!("Unknown Branch '{}', expected one of None, Near, Far, Interrupt.", text)),
+    }
+  }
+
+  fn name(&self) -> &'static str {
+    BRANCHES.iter().find(|(_, branch)| branch == self).map(|(name, _)| *name).unwrap_or("?")
+  }
+
   fn mask(&self) -> Option<(bool, u16)> {
     match self {
       Branch::None => None,
@@ -84,13 +146,98 @@ impl Instruction {
     Instruction::A(Argument::new(branch, microcode))
   }
 
-  pub fn decode(&self, microcode: &MicrocodeArray, op: u16) -> Result<Iter> {
+  pub fn decode(&self, microcode: &[Microcode], op: u16) -> Result<Iter> {
     match self {
       Instruction::Stack(v)  => v.decode(microcode, op),
       Instruction::Normal(v) => v.decode(microcode, op),
       Instruction::A(v)      => v.decode(microcode, op),
     }
   }
+
+  pub fn name(&self, op: u16) -> &'static str {
+    match self {
+      Instruction::Stack(v)  => v.name,
+      Instruction::Normal(v) => v.name,
+      Instruction::A(v)      => v.microcode[argument_mode(op)].0,
+    }
+  }
+
+  // Every name this instruction decodes to, with its microcode indices; a stack instruction
+  // repeats its one step per register.
+  pub fn sequences(&self) -> Vec<(&'static str, &[usize])> {
+    match self {
+      Instruction::Stack(v)  => vec![(v.name, std::slice::from_ref(&v.base))],
+      Instruction::Normal(v) => vec![(v.name, &v.microcode[..])],
+      Instruction::A(v)      => v.microcode.iter().map(|(name, microcode)| (*name, &microcode[..])).collect(),
+    }
+  }
+
+  // One opcode group as written in a definition file, e.g. `normal None LD r,b: 9`.
+  // Names are interned, so they live as long as the program like the built-in ones.
+  pub fn parse(text: &str) -> std::result::Result<Instruction, String> {
+    let text = text.trim();
+    let (kind, rest) = text.split_at(text.find(char::is_whitespace).unwrap_or(text.len()));
+    let rest = rest.trim_start();
+    let (branch, rest) = match kind {
+      "stack" => (Branch::None, rest),
+      "normal" | "argument" => {
+        let (branch, rest) = rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len()));
+        (Branch::parse(branch)?, rest)
+      },
+      _ => return Err(format!("Unknown instruction kind '{}', expected normal, argument or stack.", kind)),
+    };
+    let mut sequences = Vec::new();
+    for sequence in rest.split('|') {
+      let mut parts = sequence.splitn(2, ':');
+      let name = parts.next().unwrap_or("").trim();
+      let steps = match parts.next() {
+        Some(steps) => steps.split_whitespace()
+          .map(|index| index.parse::<usize>().map_err(|_| format!("Expected a microcode index, found '{}'.", index)))
+          .collect::<std::result::Result<Vec<usize>, String>>()?,
+        None => return Err(format!("Expected <name>: <microcode index>..., found '{}'.", sequence.trim())),
+      };
+      if name.is_empty() || steps.is_empty() {
+        return Err(format!("Expected <name>: <microcode index>..., found '{}'.", sequence.trim()))
+      }
+      sequences.push((intern(name), steps));
+    }
+
+    match (kind, sequences.len()) {
+      ("stack", 1) if sequences[0].1.len() == 1 => Ok(Instruction::stack(sequences[0].0, sequences[0].1[0])),
+      ("stack", _) => Err(String::from("A stack instruction has one name and one microcode index, repeated per register.")),
+      ("normal", 1) => {
+        let (name, steps) = sequences.remove(0);
+        Ok(Instruction::normal(name, branch, steps))
+      },
+      ("normal", n) => Err(format!("A normal instruction has one sequence, found {}.", n)),
+      (_, 5) => {
+        let mut modes = sequences.into_iter();
+        let mut next = || modes.next().unwrap();
+        Ok(Instruction::a(branch, [next(), next(), next(), next(), next()]))
+      },
+      (_, n) => Err(format!("An argument instruction has a sequence for each of its 5 modes, found {}.", n)),
+    }
+  }
+}
+
+impl fmt::Display for Instruction {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let (kind, branch) = match self {
+      Instruction::Stack(_)  => ("stack", None),
+      Instruction::Normal(v) => ("normal", Some(v.branch)),
+      Instruction::A(v)      => ("argument", Some(v.branch)),
+    };
+    let sequences = self.sequences().iter()
+      .map(|(name, steps)| {
+        let steps = steps.iter().map(|index| index.to_string()).collect::<Vec<_>>();
+        format!("{}: {}", name, steps.join(" "))
+      })
+      .collect::<Vec<_>>();
+    match branch {
+      Some(branch) => write!(f, "{} {} {}", kind, branch.name(), sequences.join(" | ")),
+      None => write!(f, "{} {}", kind, sequences.join(" | ")),
+    }
+  }
 }
 
 
@@ -104,7 +251,7 @@ impl Stack {
     Stack { name, base }
   }
 
-  pub fn decode(&self, microcode: &MicrocodeArray, op: u16) -> Result<Iter> {
+  pub fn decode(&self, microcode: &[Microcode], op: u16) -> Result<Iter> {
     let mut out = Vec::new();
 
     let direction = (op & 0x0400) != 0;
@@ -148,7 +295,7 @@ impl Normal {
     Normal { name, branch, microcode }
   }
 
-  pub fn decode(&self, microcode: &MicrocodeArray, op: u16) -> Result<Iter> {
+  pub fn decode(&self, microcode: &[Microcode], op: u16) -> Result<Iter> {
     let branch = self.branch.mask();
     let vec = self.microcode.iter()
       .map(|index| Ok((*index, microcode[*index].decode(op, branch)?)))
@@ -163,6 +310,10 @@ const ARGUMENT_DECODE_TABLE: [usize; 16] = [
   4, 4, 4, 4, 4, 4, 4, 4,
 ];
 
+pub fn argument_mode(op: u16) -> usize {
+  ARGUMENT_DECODE_TABLE[((op as usize) & 0x03C0) >> 6]
+}
+
 #[derive(Debug)]
 pub struct Argument {
   branch: Branch,
@@ -173,8 +324,8 @@ impl Argument {
     Argument { branch, microcode }
   }
 
-  pub fn decode(&self, microcode: &MicrocodeArray, op: u16) -> Result<Iter> {
-    let mode = ARGUMENT_DECODE_TABLE[((op as usize) & 0x03C0) >> 6];
+  pub fn decode(&self, microcode: &[Microcode], op: u16) -> Result<Iter> {
+    let mode = argument_mode(op);
     let branch = self.branch.mask();
     let vec = self.microcode[mode].1.iter()
       .map(|index| Ok((*index, microcode[*index].decode(op, branch)?)))
@@ -219,27 +370,91 @@ const DECODE_TABLE: [usize; 512] = [
    7,  7,  7,  7,  7,  7,  7,  7,  7,  7,  7,  7,  7,  7,  7,  7,
 ];
 
+pub fn group(op: u16) -> usize {
+  DECODE_TABLE[(op as usize) >> 7]
+}
+
+/// Broad instruction families, for filtering traces and listings.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Class {
+  Load,
+  Alu,
+  Bit,
+  Branch,
+  Stack,
+  Interrupt,
+  Control,
+}
+
+impl Class {
+  pub fn of(op: u16) -> Class {
+    match group(op) {
+      0 | 1 | 2 | 16 ..= 20 => Class::Load,
+      3 | 4 | 5 | 15 => Class::Alu,
+      12 ..= 14 => Class::Bit,
+      6 ..= 10 => Class::Branch,
+      11 => Class::Stack,
+      21 => Class::Interrupt,
+      _ => Class::Control,
+    }
+  }
+}
+
+impl FromStr for Class {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Class> {
+    match s {
+      "load" => Ok(Class::Load),
+      "alu" => Ok(Class::Alu),
+      "bit" => Ok(Class::Bit),
+      "branch" => Ok(Class::Branch),
+      "stack" => Ok(Class::Stack),
+      "interrupt" => Ok(Class::Interrupt),
+      "control" => Ok(Class::Control),
+      _ => Err(Error::InvalidClass(String::from(s))),
+    }
+  }
+}
+
+
+pub const INIT: &str = "INIT";
 
 pub struct Instructions {
   fetch: Control,
   init: Iter,
   instructions: [Instruction; 23],
+  decoded: Vec<Option<Iter>>, // Indexed by opcode, None where decode fails.
+  from_microcode: bool,
 }
 
 impl Instructions {
-  pub fn new(microcode: &MicrocodeArray) -> Result<Instructions> {
+  // Every opcode decoded ahead of time from `instructions`, one per group of `DECODE_TABLE`.
+  pub fn new(microcode: &[Microcode], fetch: usize, init: &[usize], instructions: [Instruction; 23]) -> Result<Instructions> {
+    let decoded = (0..=0xFFFF)
+      .map(|op| instructions[group(op)].decode(microcode, op).ok())
+      .collect();
     Ok(Instructions {
-      fetch: microcode[0].decode(0x0000, None)?, // Opcode doesn't matter for fetch.
-      init: Instructions::init_iter(microcode)?,
-      instructions: Instructions::array(),
+      fetch: microcode[fetch].decode(0x0000, None)?, // Opcode doesn't matter for fetch.
+      init: Instruction::normal(INIT, Branch::None, init.to_vec()).decode(microcode, 0x0000)?,
+      instructions,
+      decoded,
+      from_microcode: true,
     })
   }
 
-  fn init_iter(microcode: &MicrocodeArray) -> Result<Iter> {
-    Instruction::normal("INIT", Branch::None, vec![45, 43]).decode(microcode, 0x0000)
+  // Sequences that didn't come from `microcode`, e.g. read back from EEPROM images.
+  // Opcodes without one fail with `InvalidOpcode`; only the names come from the built-in table.
+  pub fn from_steps(fetch: Control, init: Vec<(usize, Control)>, steps: Vec<Option<Vec<(usize, Control)>>>) -> Instructions {
+    let instructions = Instructions::array();
+    let decoded = steps.into_iter().zip(0..=0xFFFF)
+      .map(|(steps, op)| steps.map(|steps| Iter::new(instructions[group(op)].name(op), op, steps)))
+      .collect();
+    Instructions { fetch, init: Iter::new(INIT, 0x0000, init), instructions, decoded, from_microcode: false }
   }
 
-  fn array() -> [Instruction; 23] {
+  // The built-in instruction table; FETCH is microcode step 0 and INIT is `[45, 43]`.
+  pub fn array() -> [Instruction; 23] {
     [
       Instruction::a(Branch::None, [ // 0 LD r,a
         ("LD r,r",      vec![1]),
@@ -301,12 +516,30 @@ impl Instructions {
     self.init.clone()
   }
 
-  pub fn interrupt(&self, microcode: &MicrocodeArray, interrupt: u16) -> Result<(u16, Iter)> {
+  pub fn interrupt(&self, microcode: &[Microcode], interrupt: u16) -> Result<(u16, Iter)> {
     let op = 0x0400 | (interrupt << 3);
-    Ok((op, self.instructions[21].decode(&microcode, op)?))
+    Ok((op, self.decode(microcode, op)?))
+  }
+
+  // Opcodes that failed to decode are decoded again, for the error.
+  pub fn decode(&self, microcode: &[Microcode], op: u16) -> Result<Iter> {
+    match &self.decoded[op as usize] {
+      Some(instruction) => Ok(instruction.clone()),
+      None if self.from_microcode => self.instructions[group(op)].decode(microcode, op),
+      None => Err(Error::InvalidOpcode(op)),
+    }
+  }
+
+  // Decodes `op` from `microcode` again, without the table built in `new`.
+  pub fn decode_uncached(&self, microcode: &[Microcode], op: u16) -> Result<Iter> {
+    self.instructions[group(op)].decode(microcode, op)
+  }
+
+  pub fn decoded(&self, op: u16) -> Option<&Iter> {
+    self.decoded[op as usize].as_ref()
   }
 
-  pub fn decode(&self, microcode: &MicrocodeArray, op: u16) -> Result<Iter> {
-    self.instructions[DECODE_TABLE[(op as usize) >> 7]].decode(&microcode, op)
+  pub fn name(&self, op: u16) -> &'static str {
+    self.instructions[group(op)].name(op)
   }
 }
@@ -1,4 +1,5 @@
 
+use std::fmt;
 use crate::error::{Error, Result};
 use super::control::{self, Control};
 
@@ -383,9 +384,140 @@ impl Microcode {
 }
 
 
-pub type MicrocodeArray = [Microcode; 46];
+// Names used by microcode definition files.
+const ADDRESS_SELECTS: [(&str, AddressSelect); 3] = [
+  ("A", AddressSelect::A),
+  ("ProgramCounter", AddressSelect::ProgramCounter),
+  ("S", AddressSelect::S),
+];
+
+const DATA_SELECTS: [(&str, DataSelect); 19] = [
+  ("None", DataSelect::None),
+  ("RegisterZero", DataSelect::RegisterZero),
+  ("X", DataSelect::X),
+  ("ProgramCounter", DataSelect::ProgramCounter),
+  ("LinkRegister", DataSelect::LinkRegister),
+  ("F", DataSelect::F),
+  ("Memory", DataSelect::Memory),
+  ("RegisterOne", DataSelect::RegisterOne),
+  ("RegisterTwo", DataSelect::RegisterTwo),
+  ("Alu", DataSelect::Alu),
+  ("SignedByte", DataSelect::SignedByte),
+  ("UnsignedByte", DataSelect::UnsignedByte),
+  ("Bitmask", DataSelect::Bitmask),
+  ("Interrupt", DataSelect::Interrupt),
+  ("Startup", DataSelect::Startup),
+  ("T0", DataSelect::T(false)),
+  ("T1", DataSelect::T(true)),
+  ("A", DataSelect::A),
+  ("I", DataSelect::I),
+];
+
+const DIRECTIONS: [(&str, Direction); 5] = [
+  ("Const", Direction::Const),
+  ("Near", Direction::Near),
+  ("Far", Direction::Far),
+  ("Pop", Direction::Pop),
+  ("Put", Direction::Put),
+];
+
+const ALU_MODES: [(&str, AluMode); 7] = [
+  ("None", AluMode::None),
+  ("Unary", AluMode::Unary),
+  ("Short", AluMode::Short),
+  ("Binary", AluMode::Binary),
+  ("Add", AluMode::Add),
+  ("Test", AluMode::Test),
+  ("Set", AluMode::Set),
+];
+
+fn name<T: PartialEq>(table: &[(&'static str, T)], value: &T) -> &'static str {
+  table.iter().find(|(_, v)| v == value).map(|(name, _)| *name).unwrap_or("?")
+}
+
+fn lookup<T: Copy>(table: &[(&'static str, T)], kind: &str, value: &str) -> std::result::Result<T, String> {
+  match table.iter().find(|(name, _)| *name == value) {
+    Some((_, v)) => Ok(*v),
+    None => {
+      let names = table.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+      Err(format!("Unknown {} '{}', expected one of {}.", kind, value, names.join(", ")))
+    },
+  }
+}
+
+fn data(value: &str) -> std::result::Result<(DataSelect, Direction), String> {
+  let mut parts = value.splitn(2, ':');
+  let select = lookup(&DATA_SELECTS, "DataSelect", parts.next().unwrap_or(""))?;
+  let direction = match parts.next() {
+    Some(direction) => lookup(&DIRECTIONS, "Direction", direction)?,
+    None => Direction::Const,
+  };
+  Ok((select, direction))
+}
+
+impl Microcode {
+  // One step as written in a definition file: `key=value` fields and flags, defaults left out.
+  pub fn parse(text: &str) -> std::result::Result<Microcode, String> {
+    let mut m = Microcode::new();
+    for field in text.split_whitespace() {
+      let mut parts = field.splitn(2, '=');
+      match (parts.next().unwrap_or(""), parts.next()) {
+        ("address", Some(value)) => m.address = lookup(&ADDRESS_SELECTS, "AddressSelect", value)?,
+        ("data", Some(value)) => {
+          let selects = value.split(',').collect::<Vec<_>>();
+          if selects.len() != 2 {
+            return Err(format!("Expected data=<select>[:<direction>],<select>[:<direction>], found '{}'.", field))
+          }
+          m.data = [data(selects[0])?, data(selects[1])?];
+        },
+        ("alu", Some(value)) => m.alu_mode = lookup(&ALU_MODES, "AluMode", value)?,
+        ("s_count", Some(value)) => m.s_count = Some(lookup(&DIRECTIONS, "Direction", value)?),
+        ("set_flags", None) => m.set_flags = true,
+        ("pc_increment", None) => m.pc_increment = true,
+        ("halt", None) => m.halt = true,
+        _ => return Err(format!("Unknown field '{}', expected address=, data=, alu=, s_count=, set_flags, pc_increment or halt.", field)),
+      }
+    }
+    Ok(m)
+  }
+}
+
+impl fmt::Display for Microcode {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let data = |(select, direction): (DataSelect, Direction)| match direction {
+      Direction::Const => String::from(name(&DATA_SELECTS, &select)),
+      _ => format!("{}:{}", name(&DATA_SELECTS, &select), name(&DIRECTIONS, &direction)),
+    };
+    let mut fields = Vec::new();
+    if self.address != AddressSelect::A {
+      fields.push(format!("address={}", name(&ADDRESS_SELECTS, &self.address)));
+    }
+    if self.data[0].0 != DataSelect::None || self.data[1].0 != DataSelect::None || self.data[0].1 != Direction::Const || self.data[1].1 != Direction::Const {
+      fields.push(format!("data={},{}", data(self.data[0]), data(self.data[1])));
+    }
+    if self.alu_mode != AluMode::None {
+      fields.push(format!("alu={}", name(&ALU_MODES, &self.alu_mode)));
+    }
+    if let Some(direction) = self.s_count {
+      fields.push(format!("s_count={}", name(&DIRECTIONS, &direction)));
+    }
+    if self.set_flags {
+      fields.push(String::from("set_flags"));
+    }
+    if self.pc_increment {
+      fields.push(String::from("pc_increment"));
+    }
+    if self.halt {
+      fields.push(String::from("halt"));
+    }
+    write!(f, "{}", fields.join(" "))
+  }
+}
+
+
+pub type MicrocodeArray = Vec<Microcode>;
 pub fn array() -> MicrocodeArray {
-  [
+  vec![
     { // 0 FETCH
       let mut m = Microcode::new();
       m.address = AddressSelect::ProgramCounter;
@@ -2,18 +2,54 @@
 mod microcode;
 mod instructions;
 mod control;
+mod disasm;
+mod eeprom;
+mod definition;
+mod verify;
+mod reference;
 
 use std::fmt;
 use crate::error::{Error, Result};
-use super::components::Flags;
-use super::components::InstructionRegister;
+use crate::snapshot::{
+  Snapshot,
+  Writer,
+  Reader,
+};
+use super::components::{
+  self,
+  Flags,
+};
 use self::microcode::MicrocodeArray;
 use self::instructions::{
+  INIT,
   Instructions,
   Iter,
 };
 
 pub use self::control::*;
+pub use self::disasm::disassemble;
+pub use self::definition::Definition;
+pub use self::verify::{
+  Report,
+  Status,
+  Verdict,
+};
+pub use self::reference::{
+  Reference,
+  Entry,
+};
+pub use self::eeprom::{
+  CHIPS,
+  CHIP_SIZE,
+  image_filename,
+  write_images,
+  read_images,
+};
+pub use self::instructions::{
+  Class,
+  group,
+  argument_mode,
+};
 
 
 #[derive(Debug)]
@@ -29,34 +65,126 @@ pub struct ControlLogic {
   previous: Control,
   state: State,
   interrupt: Option<u16>,
+  interrupted: bool,
   cycle: usize,
   fetch: usize,
+  built_in: bool,
 }
 
 impl ControlLogic {
   pub fn new() -> Result<ControlLogic> {
-    let microcode = self::microcode::array();
-    Ok(ControlLogic {
+    let mut logic = ControlLogic::from_definition(Definition::built_in())?;
+    logic.built_in = true;
+    Ok(logic)
+  }
+
+  // Runs the microcode and instruction table from a definition file instead of the built-in ones.
+  pub fn from_definition(definition: Definition) -> Result<ControlLogic> {
+    let (microcode, fetch, init, instructions) = definition.into_parts();
+    let instructions = Instructions::new(&microcode, fetch, &init, instructions)?;
+    Ok(ControlLogic::with_instructions(microcode, instructions))
+  }
+
+  // Runs from EEPROM images rather than the built-in microcode; see `eeprom` for the layout.
+  pub fn from_images(images: &[Vec<u8>]) -> Result<ControlLogic> {
+    let instructions = self::eeprom::instructions(images)?;
+    Ok(ControlLogic::with_instructions(self::microcode::array(), instructions))
+  }
+
+  fn with_instructions(microcode: MicrocodeArray, instructions: Instructions) -> ControlLogic {
+    ControlLogic {
       microcode,
-      instructions: Instructions::new(&microcode)?,
+      instructions,
       previous: Control::new(),
       state: State::Init,
       interrupt: None,
+      interrupted: false,
       cycle: 0,
       fetch: 0,
-    })
+      built_in: false,
+    }
   }
 
-  pub fn interrupt(&mut self, interrupt: u16) -> Result<()> {
-    if interrupt > 7 {
-      Err(Error::InvalidInterrupt(interrupt))
-    } else {
-      self.interrupt = Some(interrupt);
-      Ok(())
+  // True for the built-in microcode and instruction table, the only ones the fast engine implements.
+  pub fn built_in(&self) -> bool {
+    self.built_in
+  }
+
+  // The control store packed into `CHIPS` EEPROM images of `CHIP_SIZE` bytes each.
+  pub fn images(&self) -> Result<Vec<Vec<u8>>> {
+    self::eeprom::images(&self.instructions)
+  }
+
+  // Checks FETCH, INIT and the micro-steps of every opcode for bus conflicts and decode errors.
+  pub fn verify(&self) -> Report {
+    Report::new(&self.microcode, &self.instructions)
+  }
+
+  // Every assembler form, its encoding and what these micro-steps do with it.
+  pub fn reference(&self) -> Reference {
+    Reference::new(&self.microcode, &self.instructions)
+  }
+
+  // Number of micro-steps `op` runs, from the table of opcodes decoded ahead of time.
+  pub fn steps(&self, op: u16) -> Result<usize> {
+    self.instructions.decode(&self.microcode, op).map(|instruction| instruction.remaining())
+  }
+
+  // The same, decoded from the microcode on every call; only there to measure what the table saves.
+  pub fn steps_uncached(&self, op: u16) -> Result<usize> {
+    self.instructions.decode_uncached(&self.microcode, op).map(|instruction| instruction.remaining())
+  }
+
+  // Drives the interrupt request line: the line the interrupt controller would have taken next, if any.
+  pub fn request(&mut self, interrupt: Option<u16>) {
+    self.interrupt = interrupt;
+  }
+
+  pub fn cycles(&self) -> usize {
+    self.cycle
+  }
+
+  pub fn fetches(&self) -> usize {
+    self.fetch
+  }
+
+  // Name and microcode index of the step most recently decoded.
+  pub fn current(&self) -> (&'static str, Option<usize>) {
+    match &self.state {
+      State::Init => ("INIT", None),
+      State::Fetch => ("FETCH", Some(0)),
+      State::Run(instruction) => (instruction.name(), instruction.last_index()),
     }
   }
 
-  pub fn decode(&mut self, op: u16, flags: &Flags, ir: &mut InstructionRegister) -> Result<Control> {
+  // Name of the instruction `op` decodes to, as the `Iter` for it would report.
+  pub fn name(&self, op: u16) -> &'static str {
+    self.instructions.name(op)
+  }
+
+  // True between a FETCH and the decode of the instruction it loaded.
+  pub fn fetched(&self) -> bool {
+    matches!(self.state, State::Fetch)
+  }
+
+  // Accounts for `steps` micro-steps run outside the microcode, then a FETCH, as if decoded here.
+  // Returns the FETCH control word, without any increments left over from those steps.
+  pub fn skip(&mut self, steps: usize) -> Control {
+    self.state = State::Fetch;
+    self.interrupted = false;
+    self.previous = self.instructions.fetch();
+    self.cycle += steps + 1;
+    self.fetch += 1;
+    self.previous.previous(Control::new())
+  }
+
+  // True if the last decode replaced a FETCH with a hardware interrupt sequence.
+  pub fn interrupted(&self) -> bool {
+    self.interrupted
+  }
+
+  pub fn decode(&mut self, op: u16, flags: &Flags, ir: &mut components::InstructionRegister) -> Result<Control> {
+    self.interrupted = false;
     match &mut self.state {
       State::Fetch => {
         let instruction = self.instructions.decode(&self.microcode, op)?;
@@ -70,6 +198,7 @@ impl ControlLogic {
           if let Some(i) = self.interrupt {
             let (op, instruction) = self.instructions.interrupt(&self.microcode, i)?;
             self.interrupt = None;
+            self.interrupted = true;
             ir.set(op); // TODO make this closer to how it'd actually function?
             self.state = State::Run(instruction);
           }
@@ -99,6 +228,59 @@ impl ControlLogic {
   }
 }
 
+// The microcode and decode tables are rebuilt by `new`; only the sequencing state is saved.
+// An instruction in progress is saved as its opcode and the number of micro-steps left,
+// then re-decoded and advanced on restore.
+impl Snapshot for ControlLogic {
+  fn save(&self, w: &mut Writer) {
+    match &self.state {
+      State::Init => w.u16(0),
+      State::Fetch => w.u16(1),
+      State::Run(instruction) => {
+        w.u16(if instruction.name() == INIT { 2 } else { 3 });
+        w.u16(instruction.op());
+        w.u64(instruction.remaining() as u64);
+      },
+    }
+    w.control(&self.previous);
+    w.option(self.interrupt);
+    w.bool(self.interrupted);
+    w.u64(self.cycle as u64);
+    w.u64(self.fetch as u64);
+  }
+
+  fn restore(&mut self, r: &mut Reader) -> Result<()> {
+    self.state = match r.u16()? {
+      0 => State::Init,
+      1 => State::Fetch,
+      tag @ 2 ..= 3 => {
+        let op = r.u16()?;
+        let remaining = r.u64()? as usize;
+        let mut instruction = if tag == 2 {
+          self.instructions.init()
+        } else {
+          self.instructions.decode(&self.microcode, op)?
+        };
+        if remaining > instruction.remaining() {
+          return Err(Error::InvalidSnapshot(format!("{} micro-steps left in {}, which only has {}.",
+            remaining, instruction.name(), instruction.remaining())))
+        }
+        while instruction.remaining() > remaining {
+          instruction.next();
+        }
+        State::Run(instruction)
+      },
+      tag => return Err(Error::InvalidSnapshot(format!("Unknown control logic state {}.", tag))),
+    };
+    self.previous = r.control()?;
+    self.interrupt = r.option()?;
+    self.interrupted = r.bool()?;
+    self.cycle = r.u64()? as usize;
+    self.fetch = r.u64()? as usize;
+    Ok(())
+  }
+}
+
 impl fmt::Debug for ControlLogic {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     write!(f, "ControlLogic(fetch:={}, cycle:={})", self.fetch, self.cycle)
@@ -0,0 +1,405 @@
+use std::collections::HashSet;
+use super::control::Control;
+use super::microcode::Microcode;
+use super::instructions::{
+  Instructions,
+  group,
+};
+use super::disasm::disassemble;
+
+
+const REGISTERS: &[&str] = &["A", "B", "C", "D", "E", "X", "Y", "Z"];
+const BYTES: &[&str] = &["0", "1", "2", "4", "8", "16", "32", "64", "-1"];
+const ADDRESSES: &[&str] = &["0x00", "0x01", "0x02", "0x04", "0x08", "0x10", "0x20", "0x40", "0x80"];
+const BITS: &[&str] = &["0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15"];
+const CONDITIONS: &[&str] = &["", "!", "Z.", "Z!", "N.", "N!", "C.", "C!", ">", "<=", "V.", "V!", "Gt", "Le"];
+const INTERRUPTS: &[&str] = &["0", "1", "2", "3", "4", "5", "6", "7"];
+
+// Placeholders used in `FORMS`: the letter the bits they change show as, and the values tried.
+// The first value is the one used everywhere else in the form.
+const PLACEHOLDERS: [(&str, char, &[&str]); 19] = [
+  ("r",     'r', REGISTERS),
+  ("s",     's', REGISTERS),
+  ("i",     'i', REGISTERS),
+  ("op",    'o', &["ADD", "AND", "CMP", "SUB", "CPN", "SBN", "OR", "XOR"]),
+  ("short", 'o', &["ADD", "CMP", "CPN"]),
+  ("byte",  'b', BYTES),
+  ("addr",  'a', ADDRESSES),
+  ("bit",   'k', BITS),
+  ("value", 'v', &["0", "1"]),
+  ("cond",  'c', CONDITIONS),
+  ("jump",  'l', &["JMP", "JML"]),
+  ("ret",   'l', &["RET", "RTL"]),
+  ("stack", 't', &["0", "1"]),
+  ("sd",    't', &["", "D"]),
+  ("extra", 'x', &["S0", "S1", "PC", "LR"]),
+  ("unary", 'u', &["NOT", "NEG", "SL", "ASR", "LSR"]),
+  ("int",   'q', INTERRUPTS),
+  ("push",  'g', &["A", "B", "C", "D", "E", "X", "Y", "Z", "F", "LR", "A,B,C,D,E,X,Y,Z,F,LR"]),
+  ("pop",   'g', &["A", "B", "C", "D", "E", "X", "Y", "Z", "F", "PC", "A,B,C,D,E,X,Y,Z,F,PC"]),
+];
+
+// Everything the assembler accepts, as (syntax, template).
+const FORMS: [(&str, &str); 50] = [
+  ("LD r,s",              "LD {r},{s}"),
+  ("LD r,(s)",            "LD {r},({s})"),
+  ("LD (s),r",            "LD ({s}),{r}"),
+  ("LD r,word",           "LD {r},0x1234"),
+  ("LD r,(word)",         "LD {r},(0x1234)"),
+  ("LD (word),r",         "LD (0x1234),{r}"),
+  ("LD r,(s+i)",          "LD {r},({s}+{i})"),
+  ("LD (s+i),r",          "LD ({s}+{i}),{r}"),
+  ("LD r,b",              "LD {r},{byte}"),
+  ("LD r,(a)",            "LD {r},({addr})"),
+  ("LD (a),r",            "LD ({addr}),{r}"),
+
+  ("LD x,s",              "LD {extra},{s}"),
+  ("LD s,x",              "LD {s},{extra}"),
+  ("LD x,(s)",            "LD {extra},({s})"),
+  ("LD (s),x",            "LD ({s}),{extra}"),
+  ("LD x,word",           "LD {extra},0x1234"),
+  ("LD x,(word)",         "LD {extra},(0x1234)"),
+  ("LD (word),x",         "LD (0x1234),{extra}"),
+  ("LD x,(s+i)",          "LD {extra},({s}+{i})"),
+  ("LD (s+i),x",          "LD ({s}+{i}),{extra}"),
+
+  ("op r,s",              "{op} {r},{s}"),
+  ("op r,(s)",            "{op} {r},({s})"),
+  ("op r,word",           "{op} {r},0x1234"),
+  ("op r,(word)",         "{op} {r},(0x1234)"),
+  ("op r,(s+i)",          "{op} {r},({s}+{i})"),
+  ("op r,b",              "{short} {r},{byte}"),
+  ("op r,(a)",            "{short} {r},({addr})"),
+  ("u r",                 "{unary} {r}"),
+  ("TEST r,k",            "TEST {r},{bit}"),
+  ("SET r,k,v",           "SET {r},{bit},{value}"),
+  ("SET F,k,v",           "SET F,{bit},{value}"),
+
+  ("cJMP/cJML s",         "{cond}{jump} {s}"),
+  ("cJMP/cJML (s)",       "{cond}{jump} ({s})"),
+  ("cJMP/cJML word",      "{cond}{jump} 0x1234"),
+  ("cJMP/cJML (word)",    "{cond}{jump} (0x1234)"),
+  ("cJMP/cJML (s+i)",     "{cond}{jump} ({s}+{i})"),
+  ("cJMP/cJML b",         "{cond}{jump} {byte}"),
+  ("cJMP/cJML (a)",       "{cond}{jump} ({addr})"),
+  ("cRET/cRTL",           "{cond}{ret}"),
+  ("cJMP/cJML LR",        "{cond}{jump} LR"),
+  ("cRETt/cRTLt",         "{cond}{ret}{stack}"),
+  ("cPOPt PC",            "{cond}POP{stack} PC"),
+
+  ("PUTt [g]",            "PUT{sd} [{push}]"),
+  ("POPt [g]",            "POP{sd} [{pop}]"),
+
+  ("INT q",               "INT {int}"),
+  ("BRK q",               "BRK {int}"),
+  ("NOP",                 "NOP"),
+  ("HLT",                 "HLT"),
+  ("INC r",               "INC {r}"),
+  ("DEC r",               "DEC {r}"),
+];
+
+fn placeholder(key: &str) -> (char, &'static [&'static str]) {
+  let (_, letter, values) = PLACEHOLDERS.iter().find(|(name, _, _)| *name == key).unwrap();
+  (*letter, values)
+}
+
+fn keys(template: &str) -> Vec<&str> {
+  template.split('{').skip(1).map(|part| part.split('}').next().unwrap()).collect()
+}
+
+// Fills in `template`, with `choice` for one placeholder and the first value for the rest.
+fn expand(template: &str, choice: Option<(&str, &str)>) -> String {
+  let mut out = String::new();
+  let mut rest = template;
+  while let Some(start) = rest.find('{') {
+    let end = start + rest[start..].find('}').unwrap();
+    let key = &rest[(start + 1)..end];
+    out.push_str(&rest[..start]);
+    out.push_str(match choice {
+      Some((k, value)) if k == key => value,
+      _ => placeholder(key).1[0],
+    });
+    rest = &rest[(end + 1)..];
+  }
+  out.push_str(rest);
+  out
+}
+
+fn assemble(text: &str) -> Result<Vec<u16>, String> {
+  match assembler::from_string(text) {
+    // Drops the "[UNKNOWN]: <line>:" prefix, and nom's dump of the input for parse errors.
+    Err(error) => {
+      let text = error.to_string();
+      let message = text.splitn(3, ": ").last().unwrap_or("");
+      Err(String::from(if message.starts_with("Parser(") { "parse error" } else { message }))
+    },
+    Ok(words) if words.is_empty() => Err(String::from("no words")),
+    Ok(words) => Ok(words),
+  }
+}
+
+fn normalize(text: &str) -> String {
+  text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn sets_flags(steps: &[(usize, Control)]) -> bool {
+  steps.iter().any(|(_, c)| c.alu.set_flags || c.flags.load)
+}
+
+fn bits(encoding: &[char; 16]) -> String {
+  encoding.chunks(4).map(|nibble| nibble.iter().collect::<String>()).collect::<Vec<_>>().join(" ")
+}
+
+fn range(min: usize, max: usize) -> String {
+  if min == max { min.to_string() } else { format!("{}-{}", min, max) }
+}
+
+/// One row of the reference: an assembler form, or an instruction the assembler never produces.
+pub struct Entry {
+  pub syntax: &'static str,
+  pub example: String,
+  /// Most significant bit first, `0`/`1` fixed, letters for fields and `?` for bits that vary.
+  pub encoding: String,
+  pub group: Option<usize>,
+  pub name: &'static str,
+  pub words: usize,
+  pub steps: Option<(usize, usize)>,
+  pub flags: &'static str,
+  pub notes: Vec<String>,
+}
+
+impl Entry {
+  fn failed(syntax: &'static str, example: String, note: String) -> Entry {
+    Entry {
+      syntax, example, encoding: String::new(), group: None, name: "", words: 0, steps: None, flags: "", notes: vec![note],
+    }
+  }
+
+  pub fn steps(&self) -> String {
+    self.steps.map_or(String::new(), |(min, max)| range(min, max))
+  }
+
+  /// Clock cycles, counting FETCH.
+  pub fn cycles(&self) -> String {
+    self.steps.map_or(String::new(), |(min, max)| range(min + 1, max + 1))
+  }
+}
+
+// Running totals over the opcodes an entry covers.
+struct Summary {
+  ones: u16,
+  zeros: u16,
+  count: usize,
+  steps: Option<(usize, usize)>,
+  flags: (bool, bool),
+}
+
+impl Summary {
+  fn new() -> Summary {
+    Summary { ones: 0xFFFF, zeros: 0x0000, count: 0, steps: None, flags: (false, false) }
+  }
+
+  fn add(&mut self, op: u16, steps: &[(usize, Control)]) {
+    self.ones &= op;
+    self.zeros |= op;
+    self.count += 1;
+    let n = steps.len();
+    self.steps = Some(self.steps.map_or((n, n), |(min, max)| (min.min(n), max.max(n))));
+    if sets_flags(steps) { self.flags.0 = true } else { self.flags.1 = true }
+  }
+
+  fn flags(&self) -> &'static str {
+    match self.flags {
+      (true, true) => "some",
+      (true, false) => "yes",
+      (false, true) => "no",
+      (false, false) => "",
+    }
+  }
+}
+
+fn form(microcode: &[Microcode], instructions: &Instructions, syntax: &'static str, template: &str, ops: &mut Vec<u16>) -> Entry {
+  let example = expand(template, None);
+  let words = match assemble(&example) {
+    Ok(words) => words,
+    Err(error) => return Entry::failed(syntax, example.clone(), format!("`{}` does not assemble: {}", example, error)),
+  };
+  let op = words[0];
+  let mut encoding = [' '; 16];
+  for (bit, c) in encoding.iter_mut().enumerate() {
+    *c = if (op & (0x8000 >> bit)) != 0 { '1' } else { '0' };
+  }
+
+  // Each value of each placeholder, one at a time, marks the bits it changes.
+  let mut notes = Vec::new();
+  let mut forms = vec![(example.clone(), words.clone())];
+  for key in keys(template) {
+    let (letter, values) = placeholder(key);
+    for value in values.iter().skip(1) {
+      let text = expand(template, Some((key, value)));
+      match assemble(&text) {
+        Err(error) => notes.push(format!("`{}` does not assemble: {}", text, error)),
+        Ok(other) if group(other[0]) != group(op) || other.len() != words.len() =>
+          notes.push(format!("`{}` assembles to 0x{:04X}, {}", text, other[0], instructions.name(other[0]))),
+        Ok(other) => {
+          for (bit, c) in encoding.iter_mut().enumerate() {
+            if ((op ^ other[0]) & (0x8000 >> bit)) != 0 && (*c == '0' || *c == '1') {
+              *c = letter;
+            }
+          }
+          forms.push((text, other));
+        },
+      }
+    }
+  }
+
+  let mut summary = Summary::new();
+  let mut mismatched = Vec::new();
+  for (text, words) in forms.iter() {
+    ops.push(words[0]);
+    match instructions.decode(microcode, words[0]) {
+      Ok(instruction) => summary.add(words[0], instruction.steps()),
+      Err(error) => notes.push(format!("`{}` (0x{:04X}) does not decode: {}", text, words[0], error)),
+    }
+    let (decoded, length) = disassemble(0x0000, words[0], words.get(1).cloned());
+    if length as usize != words.len() {
+      notes.push(format!("`{}` is {} words, the decode reads {}", text, words.len(), length));
+    }
+    if normalize(&decoded).to_uppercase() != normalize(text).to_uppercase() {
+      mismatched.push(format!("`{}` runs as `{}`", text, normalize(&decoded)));
+    }
+  }
+  if mismatched.len() > 3 {
+    let more = mismatched.len() - 3;
+    mismatched.truncate(3);
+    mismatched.push(format!("{} more", more));
+  }
+  notes.extend(mismatched);
+
+  Entry {
+    syntax,
+    example,
+    encoding: bits(&encoding),
+    group: Some(group(op)),
+    name: instructions.name(op),
+    words: words.len(),
+    steps: summary.steps,
+    flags: summary.flags(),
+    notes,
+  }
+}
+
+// Instructions that decode but that no assembler form produced, one entry per name.
+fn unused(microcode: &[Microcode], instructions: &Instructions, used: &HashSet<u16>) -> Vec<Entry> {
+  let covered: HashSet<(usize, &str)> = used.iter().map(|op| (group(*op), instructions.name(*op))).collect();
+  let mut out: Vec<((usize, &str), Summary)> = Vec::new();
+  for op in 0..=0xFFFF {
+    let key = (group(op), instructions.name(op));
+    if covered.contains(&key) {
+      continue
+    }
+    if let Ok(instruction) = instructions.decode(microcode, op) {
+      let i = match out.iter().position(|(k, _)| *k == key) {
+        Some(i) => i,
+        None => {
+          out.push((key, Summary::new()));
+          out.len() - 1
+        },
+      };
+      out[i].1.add(op, instruction.steps());
+    }
+  }
+  out.into_iter().map(|((group, name), summary)| {
+    let mut encoding = ['?'; 16];
+    for (bit, c) in encoding.iter_mut().enumerate() {
+      let mask = 0x8000 >> bit;
+      if (summary.ones & mask) != 0 { *c = '1' } else if (summary.zeros & mask) == 0 { *c = '0' }
+    }
+    Entry {
+      syntax: "",
+      example: String::new(),
+      encoding: bits(&encoding),
+      group: Some(group),
+      name,
+      words: disassemble(0x0000, summary.ones, None).1 as usize,
+      steps: summary.steps,
+      flags: summary.flags(),
+      notes: vec![format!("{} opcodes, none produced by the assembler", summary.count)],
+    }
+  }).collect()
+}
+
+fn markdown_cell(text: &str) -> String {
+  text.replace('|', "\\|")
+}
+
+fn csv_cell(text: &str) -> String {
+  if text.contains(&[',', '"', '\n'][..]) {
+    format!("\"{}\"", text.replace('"', "\"\""))
+  } else {
+    String::from(text)
+  }
+}
+
+/// Every assembler form with its encoding and what the control store does with it, as Markdown or CSV.
+pub struct Reference {
+  entries: Vec<Entry>,
+}
+
+impl Reference {
+  pub(super) fn new(microcode: &[Microcode], instructions: &Instructions) -> Reference {
+    let mut used = Vec::new();
+    let mut entries: Vec<Entry> = FORMS.iter()
+      .map(|(syntax, template)| form(microcode, instructions, syntax, template, &mut used))
+      .collect();
+    entries.extend(unused(microcode, instructions, &used.into_iter().collect()));
+    Reference { entries }
+  }
+
+  pub fn entries(&self) -> &[Entry] {
+    &self.entries
+  }
+
+  pub fn markdown(&self) -> String {
+    let mut out = String::new();
+    out.push_str("# Opcode reference\n\n");
+    out.push_str("Generated by `microcode reference` from the assembler's encodings, `DECODE_TABLE` and the instruction definitions.\n\n");
+    out.push_str("Encodings are most significant bit first. `0` and `1` are fixed bits, `?` bits vary, and letters are fields:\n");
+    out.push_str("`r` register, `s` source or base register, `i` index register, `o` ALU operation, `u` unary operation,\n");
+    out.push_str("`b` signed byte, `a` byte address, `k` bit number, `v` bit value, `c` condition, `l` link, `t` stack,\n");
+    out.push_str("`x` extra register (S0, S1, PC, LR), `g` register list, `q` interrupt number.\n");
+    out.push_str("Cycles count FETCH. Notes list forms that assemble to something else or that run differently than written.\n\n");
+    out.push_str("| Syntax | Encoding | Example | Group | Instruction | Words | Micro-steps | Cycles | Flags | Notes |\n");
+    out.push_str("|---|---|---|---|---|---|---|---|---|---|\n");
+    for entry in self.entries.iter() {
+      let code = |text: &str| if text.is_empty() { String::new() } else { format!("`{}`", markdown_cell(text)) };
+      out.push_str(&format!("| {} | {} | {} | {} | {} | {} | {} | {} | {} | {} |\n",
+        code(entry.syntax), code(&entry.encoding), code(&entry.example),
+        entry.group.map_or(String::new(), |group| group.to_string()), markdown_cell(entry.name),
+        entry.words, entry.steps(), entry.cycles(), entry.flags,
+        markdown_cell(&entry.notes.join("; "))));
+    }
+    out
+  }
+
+  pub fn csv(&self) -> String {
+    let mut out = String::from("syntax,encoding,example,group,instruction,words,micro_steps,cycles,flags,notes\n");
+    for entry in self.entries.iter() {
+      let row = [
+        String::from(entry.syntax),
+        entry.encoding.clone(),
+        entry.example.clone(),
+        entry.group.map_or(String::new(), |group| group.to_string()),
+        String::from(entry.name),
+        entry.words.to_string(),
+        entry.steps(),
+        entry.cycles(),
+        String::from(entry.flags),
+        entry.notes.join("; "),
+      ];
+      out.push_str(&row.iter().map(|cell| csv_cell(cell)).collect::<Vec<_>>().join(","));
+      out.push('\n');
+    }
+    out
+  }
+}
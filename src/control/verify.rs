@@ -0,0 +1,160 @@
+use std::fmt;
+use crate::error::Error;
+use super::control::{
+  Control,
+  Address,
+  Register,
+  IMode,
+};
+use super::microcode::Microcode;
+use super::instructions::Instructions;
+
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Status {
+  /// Decodes, and every micro-step is well formed.
+  Legal,
+  /// The opcode's fields don't decode, e.g. an unused condition or ALU operation.
+  Illegal,
+  /// Decodes, but would fail or misbehave at runtime.
+  Broken,
+}
+
+impl fmt::Display for Status {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.pad(match self {
+      Status::Legal => "legal",
+      Status::Illegal => "illegal",
+      Status::Broken => "broken",
+    })
+  }
+}
+
+#[derive(Debug)]
+pub struct Verdict {
+  pub op: u16,
+  pub name: &'static str,
+  pub status: Status,
+  pub problems: Vec<String>,
+}
+
+// The same checks the bus makes every half-cycle in `Cpu`, made on the decoded control words instead.
+fn drivers(c: &Control) -> Vec<&'static str> {
+  let mut out = Vec::new();
+  if c.register.out != Register::None { out.push("RegisterFile"); }
+  if c.alu.out { out.push("Alu"); }
+  if c.flags.out { out.push("FlagsRegister"); }
+  if c.pc.out { out.push("ProgramCounter"); }
+  if c.lr.out { out.push("LinkRegister"); }
+  if c.s[0].out { out.push("StackPointers(S0)"); }
+  if c.s[1].out { out.push("StackPointers(S1)"); }
+  if c.memory.out { out.push("Memory"); }
+  if c.i.mode != IMode::None { out.push("InstructionRegister"); }
+  out
+}
+
+fn check(steps: &[(usize, Control)]) -> Vec<String> {
+  let mut problems = Vec::new();
+  let mut a = false;
+  for (n, (index, c)) in steps.iter().enumerate() {
+    let drivers = drivers(c);
+    if drivers.len() > 1 {
+      problems.push(format!("step {} ({}): {} drive the data bus at once", n, index, drivers.join(", ")));
+    } else if drivers.is_empty() && c.loads() && !c.alu.set_flags {
+      problems.push(format!("step {} ({}): loads from the data bus, but nothing drives it", n, index));
+    }
+    if (c.memory.load || c.memory.out) && c.address == Address::A && !a {
+      problems.push(format!("step {} ({}): addresses memory through A before any step loads it", n, index));
+    }
+    a |= c.a.load;
+  }
+  problems
+}
+
+// Decode errors from the opcode's own fields make it illegal; any other is a fault in the microcode.
+fn reason(error: &Error) -> (Status, String) {
+  match error {
+    Error::InvalidExtraRegister(_, value) => (Status::Illegal, format!("0b{:02b} is not a valid extra register", value)),
+    Error::InvalidRegister(_, offset, value) => (Status::Illegal, format!("0b{:03b} at bit {} is not a valid register", value, offset)),
+    Error::InvalidBinaryOp(_, value) => (Status::Illegal, format!("0b{:03b} is not a valid binary ALU operation", value)),
+    Error::InvalidUnaryOp(_, value) => (Status::Illegal, format!("0b{:03b} is not a valid unary ALU operation", value)),
+    Error::InvalidCondition(_, value) => (Status::Illegal, format!("0b{:03b} is not a valid condition", value)),
+    Error::InvalidOpcode(_) => (Status::Illegal, String::from("no micro-steps in the control store")),
+    Error::InvalidRead(_, message) | Error::InvalidWrite(_, message) | Error::Impossible(_, message) =>
+      (Status::Broken, String::from(*message)),
+    error => (Status::Broken, error.to_string()),
+  }
+}
+
+/// Result of checking FETCH, INIT and every opcode's micro-steps, without running anything.
+pub struct Report {
+  fetch: Vec<String>,
+  init: Vec<String>,
+  opcodes: Vec<Verdict>,
+}
+
+impl Report {
+  pub(super) fn new(microcode: &[Microcode], instructions: &Instructions) -> Report {
+    let fetch = check(&[(0, instructions.fetch())]);
+    let init = check(instructions.init().steps());
+    let opcodes = (0..=0xFFFF).map(|op| {
+      let name = instructions.name(op);
+      match instructions.decode(microcode, op) {
+        Ok(instruction) => {
+          let problems = check(instruction.steps());
+          let status = if problems.is_empty() { Status::Legal } else { Status::Broken };
+          Verdict { op, name, status, problems }
+        },
+        Err(error) => {
+          let (status, problem) = reason(&error);
+          Verdict { op, name, status, problems: vec![problem] }
+        },
+      }
+    }).collect();
+    Report { fetch, init, opcodes }
+  }
+
+  /// Every opcode, in order.
+  pub fn opcodes(&self) -> &[Verdict] {
+    &self.opcodes
+  }
+
+  pub fn count(&self, status: Status) -> usize {
+    self.opcodes.iter().filter(|verdict| verdict.status == status).count()
+  }
+
+  /// True if FETCH, INIT or any opcode is broken.
+  pub fn broken(&self) -> bool {
+    !self.fetch.is_empty() || !self.init.is_empty() || self.count(Status::Broken) > 0
+  }
+}
+
+// Runs of consecutive opcodes with the same verdict are listed as one range.
+impl fmt::Display for Report {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, "{} legal, {} illegal, {} broken opcodes.",
+      self.count(Status::Legal), self.count(Status::Illegal), self.count(Status::Broken))?;
+    for (name, problems) in [("FETCH", &self.fetch), ("INIT", &self.init)].iter() {
+      let status = if problems.is_empty() { Status::Legal } else { Status::Broken };
+      writeln!(f, "{:<13} {:<7} {}{}", "", status, name,
+        problems.iter().map(|problem| format!("; {}", problem)).collect::<String>())?;
+    }
+
+    let mut start = 0;
+    for i in 1..=self.opcodes.len() {
+      let (first, verdict) = (&self.opcodes[start], self.opcodes.get(i));
+      if verdict.is_some_and(|v| v.name == first.name && v.status == first.status && v.problems == first.problems) {
+        continue
+      }
+      let range = if i - 1 == start {
+        format!("0x{:04X}", first.op)
+      } else {
+        format!("0x{:04X}-0x{:04X}", first.op, self.opcodes[i - 1].op)
+      };
+      writeln!(f, "{:<13} {:<7} {}{}", range, first.status, first.name,
+        first.problems.iter().map(|problem| format!("; {}", problem)).collect::<String>())?;
+      start = i;
+    }
+    Ok(())
+  }
+}
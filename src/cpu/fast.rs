@@ -0,0 +1,490 @@
+use crate::error::Result;
+use crate::control::{
+  group,
+  argument_mode,
+};
+use super::Cpu;
+
+
+// Micro-steps in each argument mode (see `argument_mode`) of LD r,a / JMl a, and of OP r,a.
+const LD_STEPS: [usize; 5] = [1, 2, 1, 2, 4];
+const OP_STEPS: [usize; 5] = [3, 4, 3, 4, 6];
+
+const INTERRUPT_ENABLE: u16 = 0x8000;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Mode {
+  Add,
+  And,
+  Or,
+  Xor,
+}
+
+// Z, S, C and V as the low bits of F.
+fn flags(value: u16, carry: bool, overflow: bool) -> u16 {
+  ((value == 0) as u16) | ((value >> 15) << 1) | ((carry as u16) << 2) | ((overflow as u16) << 3)
+}
+
+// The ALU's result and flags for `t0 mode t1`, with any T0 zeroing or T1 inversion already applied.
+fn binary(mode: Mode, t0: u16, t1: u16, carry_invert: bool) -> (u16, u16) {
+  let (t0, t1) = (t0 as i32, t1 as i32);
+  let value = match mode {
+    Mode::Add => t0 + t1 + carry_invert as i32,
+    Mode::And => t0 & t1,
+    Mode::Or  => t0 | t1,
+    Mode::Xor => t0 ^ t1,
+  };
+  let carry = carry_invert ^ (value > 0xFFFF);
+  let overflow = carry_invert ^ (value > 0x7FFF);
+  (value as u16, flags(value as u16, carry, overflow))
+}
+
+fn signed_byte(op: u16) -> u16 {
+  (((op >> 3) & 0x00FF) as i8) as u16
+}
+
+fn unsigned_byte(op: u16) -> u16 {
+  (op >> 3) & 0x00FF
+}
+
+fn bitmask(op: u16) -> u16 {
+  1 << ((op >> 3) & 0x000F)
+}
+
+fn register(op: u16, offset: u16) -> usize {
+  ((op >> offset) & 0x0007) as usize
+}
+
+// Opcodes the fast engine runs itself. HLT and BRK stop partway through, and the rest fail to
+// decode, so those are left to the microcode to get exactly the same state (or error).
+pub fn interpretable(op: u16) -> bool {
+  match group(op) {
+    6 ..= 10 => (op & 0x0007) != 1,
+    15 => !matches!((op >> 3) & 0x0007, 2 | 3 | 5),
+    21 => (op & 0x0080) == 0,
+    22 => op == 0x0000,
+    _ => true,
+  }
+}
+
+impl Cpu {
+  // Runs the instruction in I and any hardware interrupts after it, then fetches the next one,
+  // leaving the machine exactly as the microcode would at that FETCH.
+  pub(super) fn execute(&mut self) -> Result<()> {
+    let (op, pc) = (self.i.get(), self.instruction_pc);
+    let mut total = 0;
+    let mut steps = 0;
+    let passed = match self.interpret(op, pc)? {
+      Some(n) => { steps = n; true },
+      None => false,
+    };
+
+    // An interrupt is only taken once an instruction runs to its last step; a failed condition goes straight to FETCH.
+    // The controller only requests lines that are unmasked, so every one taken runs its sequence.
+    if passed {
+      while let Some(i) = self.memory.pic()?.request(&self.flags) {
+        self.memory.pic()?.acknowledge(i);
+        let op = 0x0400 | (i << 3);
+        self.half_cycles += 2 * steps as u64;
+        total += steps;
+        self.i.set(op);
+        self.instructions += 1;
+        self.instruction_pc = self.pc.get();
+        self.clock_devices()?;
+        self.record()?;
+        self.int(op)?;
+        steps = 3;
+      }
+    }
+    total += steps;
+
+    self.half_cycles += 2 * steps as u64 + 1;
+    let address = self.pc.get();
+    let op = self.memory.read(address)?;
+    self.i.set(op);
+    self.instructions += 1;
+    self.instruction_pc = address;
+    self.address = address;
+    self.data = Some(op);
+    self.memory.set_address(address);
+    self.clock_devices()?;
+    self.record()?;
+    self.half_cycles += 1;
+
+    let c = self.control.skip(total);
+    self.set_control(c);
+    self.boundary = true;
+    Ok(())
+  }
+
+  fn condition(&self, op: u16) -> bool {
+    let f = self.flags.value();
+    let (zero, sign, carry, overflow) = (f & 0x1 != 0, f & 0x2 != 0, f & 0x4 != 0, f & 0x8 != 0);
+    ((op & 0x0800) != 0) ^ match op & 0x0007 {
+      0 => true,
+      2 => zero,
+      3 => sign,
+      4 => carry,
+      5 => carry && !zero,
+      6 => overflow,
+      7 => overflow && !zero,
+      _ => false,
+    }
+  }
+
+  fn interruptible(&self, interrupt: u16) -> bool {
+    let f = self.flags.value();
+    (f & INTERRUPT_ENABLE) != 0 && (f & (0x0100 << interrupt)) == 0
+  }
+
+  fn set_alu_flags(&mut self, flags: u16) {
+    self.flags.set_value((self.flags.value() & 0xFF00) | flags);
+  }
+
+  fn link(&mut self, op: u16, mask: u16, value: u16) {
+    if (op & mask) != 0 {
+      self.lr.set(value);
+    }
+  }
+
+  // S0, S1, PC or LR, as picked by bits 3-4 of the LD x family.
+  fn extra(&self, op: u16) -> u16 {
+    match (op >> 3) & 0x0003 {
+      0 => self.s.get(0),
+      1 => self.s.get(1),
+      2 => self.pc.get(),
+      _ => self.lr.get(),
+    }
+  }
+
+  fn set_extra(&mut self, op: u16, value: u16) {
+    match (op >> 3) & 0x0003 {
+      0 => self.s.set(0, value),
+      1 => self.s.set(1, value),
+      2 => self.pc.set(value),
+      _ => self.lr.set(value),
+    }
+  }
+
+  // Address of the operand for argument modes (r), (word) and (r+r), loading A (and T0/T1 for (r+r)) on the way.
+  fn operand(&mut self, op: u16, mode: usize, word: u16) -> Result<u16> {
+    let address = match mode {
+      1 => self.r.get(register(op, 3)),
+      3 => {
+        self.pc.set(word.wrapping_add(1));
+        self.memory.read(word)?
+      },
+      _ => {
+        let (t0, t1) = (self.r.get(register(op, 3)), self.r.get(register(op, 6)));
+        self.alu.set(0, t0);
+        self.alu.set(1, t1);
+        t0.wrapping_add(t1)
+      },
+    };
+    self.a.set(address);
+    Ok(address)
+  }
+
+  // Binary op `n` (ADD AND CMP SUB CPN SBN OR XOR) between register `r` and `arg`, as OP r,a/b/(u) run it.
+  fn operate(&mut self, n: u16, r: usize, arg: u16) {
+    let (t0, t1) = match n {
+      4 | 5 => (arg, self.r.get(r)),
+      _ => (self.r.get(r), arg),
+    };
+    self.alu.set(0, t0);
+    self.alu.set(1, t1);
+    let (value, flags) = match n {
+      0 => binary(Mode::Add, t0, t1, false),
+      1 => binary(Mode::And, t0, t1, false),
+      2 ..= 5 => binary(Mode::Add, t0, !t1, true),
+      6 => binary(Mode::Or, t0, t1, false),
+      _ => binary(Mode::Xor, t0, t1, false),
+    };
+    self.set_alu_flags(flags);
+    if n != 2 && n != 4 {
+      self.r.set(r, value);
+    }
+  }
+
+  // INT's micro-steps: push the return address (PC), then jump through vector 0xFFF8+i.
+  fn int(&mut self, op: u16) -> Result<()> {
+    let s = ((op & 0x0200) != 0) as usize;
+    let sp = self.s.get(s);
+    let pc = self.pc.get();
+    self.memory.write(sp, pc)?;
+    self.s.set(s, sp.wrapping_sub(1));
+    let vector = 0xFFF8 | ((op >> 3) & 0x0007);
+    self.a.set(vector);
+    let target = self.memory.read(vector)?;
+    self.pc.set(target);
+    Ok(())
+  }
+
+  // Runs `op`, fetched from `pc`, up to the FETCH of whatever follows, and returns how many
+  // micro-steps that took, or `None` if its condition failed on the first one.
+  fn interpret(&mut self, op: u16, pc: u16) -> Result<Option<usize>> {
+    let word = pc.wrapping_add(1);
+    let r = register(op, 0);
+    let load = (op & 0x0400) != 0;
+    self.pc.set(word);
+
+    let steps = match group(op) {
+      0 => { // LD r,a
+        let mode = argument_mode(op);
+        match mode {
+          0 => {
+            let value = self.r.get(register(op, 3));
+            self.r.set(r, value);
+          },
+          2 => {
+            self.pc.set(word.wrapping_add(1));
+            if load {
+              let value = self.memory.read(word)?;
+              self.r.set(r, value);
+            } else {
+              self.memory.write(word, self.r.get(r))?;
+            }
+          },
+          _ => {
+            let address = self.operand(op, mode, word)?;
+            if load {
+              let value = self.memory.read(address)?;
+              self.r.set(r, value);
+            } else {
+              self.memory.write(address, self.r.get(r))?;
+            }
+          },
+        }
+        LD_STEPS[mode]
+      },
+      1 => { // LD r,b
+        self.r.set(r, signed_byte(op));
+        1
+      },
+      2 => { // LD r,(u)
+        let address = unsigned_byte(op);
+        self.a.set(address);
+        if (op & 0x0800) != 0 {
+          let value = self.memory.read(address)?;
+          self.r.set(r, value);
+        } else {
+          self.memory.write(address, self.r.get(r))?;
+        }
+        2
+      },
+
+      3 => { // OP r,a
+        let mode = argument_mode(op);
+        let arg = match mode {
+          0 => self.r.get(register(op, 3)),
+          2 => {
+            self.pc.set(word.wrapping_add(1));
+            self.memory.read(word)?
+          },
+          _ => {
+            let address = self.operand(op, mode, word)?;
+            self.memory.read(address)?
+          },
+        };
+        self.operate((op >> 10) & 0x0007, r, arg);
+        OP_STEPS[mode]
+      },
+      4 => { // OP r,b
+        self.operate((op >> 10) & 0x0006, r, signed_byte(op));
+        3
+      },
+      5 => { // OP r,(u)
+        let address = unsigned_byte(op);
+        self.a.set(address);
+        let arg = self.memory.read(address)?;
+        self.operate((op >> 10) & 0x0006, r, arg);
+        4
+      },
+
+      6 => { // JMl a
+        if !self.condition(op) {
+          return Ok(None)
+        }
+        let mode = argument_mode(op);
+        let target = match mode {
+          0 => self.r.get(register(op, 3)),
+          2 => self.memory.read(word)?,
+          _ => {
+            let address = self.operand(op, mode, word)?;
+            self.memory.read(address)?
+          },
+        };
+        // The argument word's PC increment carries into the link.
+        let link = if mode == 2 || mode == 3 { word.wrapping_add(1) } else { word };
+        self.link(op, 0x0400, link);
+        self.pc.set(target);
+        LD_STEPS[mode]
+      },
+      7 => { // JMl b
+        if !self.condition(op) {
+          return Ok(None)
+        }
+        let offset = signed_byte(op);
+        self.alu.set(0, word);
+        self.alu.set(1, offset);
+        self.link(op, 0x1000, word);
+        self.pc.set(word.wrapping_add(offset));
+        3
+      },
+      8 => { // JMl (u)
+        if !self.condition(op) {
+          return Ok(None)
+        }
+        let address = unsigned_byte(op);
+        self.a.set(address);
+        let target = self.memory.read(address)?;
+        self.link(op, 0x1000, word);
+        self.pc.set(target);
+        2
+      },
+
+      9 => { // RET
+        if !self.condition(op) {
+          return Ok(None)
+        }
+        self.link(op, 0x0400, word);
+        let target = self.lr.get();
+        self.pc.set(target);
+        1
+      },
+      10 => { // RETs
+        if !self.condition(op) {
+          return Ok(None)
+        }
+        let s = ((op & 0x0200) != 0) as usize;
+        let sp = self.s.get(s);
+        self.link(op, 0x0400, word);
+        let target = if (op & 0x0800) == 0 {
+          self.s.set(s, sp.wrapping_add(1));
+          self.memory.read(sp.wrapping_add(1))?
+        } else {
+          self.s.set(s, sp.wrapping_sub(1));
+          self.memory.read(sp)?
+        };
+        self.pc.set(target);
+        1
+      },
+
+      11 => { // PUT/POP
+        let s = ((op & 0x0200) != 0) as usize;
+        let registers = ((op & 0x0800) >> 2) | (op & 0x01FF);
+        let mut sp = self.s.get(s);
+        if load {
+          for bit in (0..10).rev().filter(|bit| (registers & (1 << bit)) != 0) {
+            sp = sp.wrapping_add(1);
+            let value = self.memory.read(sp)?;
+            match bit {
+              8 => self.flags.set_value(value),
+              9 => self.pc.set(value),
+              r => self.r.set(r, value),
+            }
+          }
+        } else {
+          for bit in (0..10).filter(|bit| (registers & (1 << bit)) != 0) {
+            let value = match bit {
+              8 => self.flags.value(),
+              9 => self.lr.get(),
+              r => self.r.get(r),
+            };
+            self.memory.write(sp, value)?;
+            sp = sp.wrapping_sub(1);
+          }
+        }
+        self.s.set(s, sp);
+        registers.count_ones() as usize
+      },
+
+      12 => { // SET F
+        let (f, mask) = (self.flags.value(), bitmask(op));
+        self.alu.set(0, f);
+        self.alu.set(1, mask);
+        self.flags.set_value(if (op & 0x0080) != 0 { f | mask } else { f & !mask });
+        3
+      },
+      13 => { // SET r
+        let (value, mask) = (self.r.get(r), bitmask(op));
+        self.alu.set(0, value);
+        self.alu.set(1, mask);
+        self.r.set(r, if (op & 0x0080) != 0 { value | mask } else { value & !mask });
+        3
+      },
+      14 => { // TEST r
+        let (value, mask) = (self.r.get(r), bitmask(op));
+        self.alu.set(0, value);
+        self.alu.set(1, mask);
+        self.set_alu_flags(binary(Mode::And, value, mask, false).1);
+        2
+      },
+
+      15 => { // UOP r
+        // Only T0 is loaded, so the unary ops work on whatever T1 still holds, and the ALU
+        // takes the shift direction before sign extension: SL shifts right, LSR and ASR left.
+        self.alu.set(0, self.r.get(r));
+        let t1 = self.alu.get(1);
+        let (value, flags) = match (op >> 3) & 0x0007 {
+          0 => binary(Mode::Add, 0, !t1, true), // NEG
+          1 => binary(Mode::Add, 0, !t1, false), // NOT
+          4 => { let v = t1 >> 1; (v, flags(v, (t1 & 0x0001) != 0, false)) }, // SL
+          _ => { let v = t1 << 1; (v, flags(v, (t1 & 0x8000) != 0, false)) }, // LSR, ASR
+        };
+        self.r.set(r, value);
+        self.set_alu_flags(flags);
+        2
+      },
+
+      16 => { // LD x,r
+        if load {
+          self.r.set(r, self.extra(op));
+        } else {
+          self.set_extra(op, self.r.get(r));
+        }
+        1
+      },
+      17 ..= 20 => { // LD x,(r) / LD x,word / LD x,(word) / LD x,(r+r)
+        let (address, steps) = match group(op) {
+          17 => (self.r.get(r), 2),
+          18 => (word, 1),
+          19 => (self.memory.read(word)?, 2),
+          _ => {
+            let (t0, t1) = (self.r.get(r), self.r.get(register(op, 6)));
+            self.alu.set(0, t0);
+            self.alu.set(1, t1);
+            (t0.wrapping_add(t1), 4)
+          },
+        };
+        if group(op) != 18 {
+          self.a.set(address);
+        }
+        // LD x,word stores PC before its increment, which a load into PC cancels.
+        if group(op) == 18 && !load {
+          self.memory.write(address, self.extra(op))?;
+        }
+        if group(op) == 18 || group(op) == 19 {
+          self.pc.set(word.wrapping_add(1));
+        }
+        if load {
+          let value = self.memory.read(address)?;
+          self.set_extra(op, value);
+        } else if group(op) != 18 {
+          self.memory.write(address, self.extra(op))?;
+        }
+        steps
+      },
+
+      21 => { // INT
+        if !self.interruptible((op >> 3) & 0x0007) {
+          return Ok(None)
+        }
+        self.int(op)?;
+        3
+      },
+      _ => 1, // NOP
+    };
+    Ok(Some(steps))
+  }
+}
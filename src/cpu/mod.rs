@@ -0,0 +1,1178 @@
+
+use std::fmt;
+use std::str::FromStr;
+use sdl2::keyboard::{
+  Keycode,
+  Mod,
+};
+use crate::error::{
+  Result,
+  Error,
+};
+use super::components::{
+  BusComponent,
+  AddressRegister,
+  Alu,
+  Flags,
+  InstructionRegister,
+  LinkRegister,
+  ProgramCounter,
+  RegisterFile,
+  StackPointers,
+};
+use super::memory::Memory;
+use super::io::{
+  Screen,
+  Keyboard,
+  Pic,
+  Timer,
+  Rtc,
+  TimeSource,
+  Uart,
+  Sd,
+};
+use super::control::{
+  ControlLogic,
+  Control,
+};
+use super::clock::{
+  Clock,
+  Pacing,
+};
+#[cfg(unix)]
+use super::serial::Serial;
+use super::snapshot::{
+  self,
+  Snapshot,
+  Writer,
+  Reader,
+};
+use super::debug::{
+  History,
+  Input,
+  Recorder,
+  Replay,
+  Script,
+  Trace,
+  Vcd,
+  Breakpoint,
+  Breakpoints,
+  Watchpoint,
+  Watchpoints,
+  WatchHit,
+};
+
+mod fast;
+
+
+/// Architectural registers visible to programs (and debuggers).
+///
+/// `R0`-`R7` are the general purpose registers, named `A B C D E X Y Z` by the assembler.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Reg {
+  R0, R1, R2, R3, R4, R5, R6, R7,
+  S0, S1,
+  PC,
+  LR,
+  F,
+  A,
+  I,
+}
+
+impl Reg {
+  pub const ALL: [Reg; 15] = [
+    Reg::R0, Reg::R1, Reg::R2, Reg::R3, Reg::R4, Reg::R5, Reg::R6, Reg::R7,
+    Reg::S0, Reg::S1, Reg::PC, Reg::LR, Reg::F, Reg::A, Reg::I,
+  ];
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Reg::R0 => "R0", Reg::R1 => "R1", Reg::R2 => "R2", Reg::R3 => "R3",
+      Reg::R4 => "R4", Reg::R5 => "R5", Reg::R6 => "R6", Reg::R7 => "R7",
+      Reg::S0 => "S0", Reg::S1 => "S1",
+      Reg::PC => "PC",
+      Reg::LR => "LR",
+      Reg::F  => "F",
+      Reg::A  => "A",
+      Reg::I  => "I",
+    }
+  }
+}
+
+/// Which half of the clock cycle a `Step` ended on.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Phase {
+  /// Control word decoded and address bus driven; the data transfer is still pending.
+  Decode,
+  /// Data bus transferred and loaded; the next step starts a fresh cycle.
+  Transfer,
+}
+
+/// What a `Cpu::step_*` call did.
+#[derive(Debug)]
+pub struct Step {
+  pub half_cycles: u64,
+  pub instructions: u64,
+  pub phase: Phase,
+  /// Instruction (and microcode index) the last half-cycle belonged to.
+  pub name: &'static str,
+  pub microcode: Option<usize>,
+  /// Bus state of the last half-cycle. `data` is `None` until the transfer half has run.
+  pub control: Control,
+  pub address: u16,
+  pub data: Option<u16>,
+  /// Address and opcode of the instruction now in `I`.
+  pub pc: u16,
+  pub op: u16,
+  pub halted: bool,
+  /// Breakpoint or watchpoint tripped during the step.
+  pub stop: Option<Stop>,
+}
+
+impl fmt::Display for Step {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{} half-cycle(s), {} instruction(s): ", self.half_cycles, self.instructions)?;
+    match self.microcode {
+      Some(index) => write!(f, "{} [{}]", self.name, index)?,
+      None => write!(f, "{}", self.name)?,
+    }
+    write!(f, " ADDR=0x{:04X}", self.address)?;
+    match (self.phase, self.data) {
+      (Phase::Decode, _) => write!(f, " DATA=..")?,
+      (Phase::Transfer, Some(data)) => write!(f, " DATA=0x{:04X}", data)?,
+      (Phase::Transfer, None) => write!(f, " DATA=--")?,
+    }
+    write!(f, " | 0x{:04X}: 0x{:04X}", self.pc, self.op)?;
+    if self.halted {
+      write!(f, " HALTED")?;
+    }
+    if let Some(stop) = &self.stop {
+      write!(f, " [{}]", stop)?;
+    }
+    Ok(())
+  }
+}
+
+/// Why `Cpu::run` returned.
+#[derive(Debug)]
+pub enum Stop {
+  /// Breakpoint `id` matched the instruction at `pc`, which has been fetched but not executed.
+  Breakpoint { id: usize, pc: u16, op: u16 },
+  /// A bus access matched a watchpoint during the instruction at `pc`; the access has completed.
+  Watchpoint { hit: WatchHit, pc: u16 },
+  /// A HLT executed, or the machine is paused.
+  Halted,
+  /// The cycle budget ran out.
+  Budget,
+  Error(Error),
+}
+
+impl Stop {
+  /// Moves `Stop::Error` into the `Err` side, for callers that treat every other stop alike.
+  pub fn result(self) -> Result<Stop> {
+    match self {
+      Stop::Error(error) => Err(error),
+      stop => Ok(stop),
+    }
+  }
+}
+
+impl fmt::Display for Stop {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Stop::Breakpoint { id, pc, op } => write!(f, "Breakpoint {} at 0x{:04X} (0x{:04X})", id, pc, op),
+      Stop::Watchpoint { hit, pc } => write!(f, "{} at 0x{:04X}", hit, pc),
+      Stop::Halted => write!(f, "Halted"),
+      Stop::Budget => write!(f, "Cycle budget exhausted"),
+      Stop::Error(error) => write!(f, "{}", error),
+    }
+  }
+}
+
+/// How `step_instruction` and `run` execute instructions.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Engine {
+  /// Every micro-step on the bus, as the hardware runs it.
+  Microcode,
+  /// Whole instructions at once, with the same results and cycle counts as the microcode.
+  /// Only available with the built-in microcode, not a definition file or EEPROM images.
+  ///
+  /// INIT, HLT, BRK and opcodes that fail to decode still run on the microcode. VCD dumps are not
+  /// sampled, inputs wait for the next instruction, and breakpoints are not checked on interrupt
+  /// sequences. Single half-cycle and cycle steps always use the microcode.
+  Fast,
+}
+
+impl FromStr for Engine {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Engine> {
+    match s {
+      "microcode" => Ok(Engine::Microcode),
+      "fast" => Ok(Engine::Fast),
+      _ => Err(Error::InvalidEngine(String::from(s))),
+    }
+  }
+}
+
+/// The whole machine: control logic, every bus component, and the memory map.
+#[derive(Debug)]
+pub struct Cpu {
+  clock: Clock,
+  engine: Engine,
+  halt: bool,
+  c: Control,
+  phase: Phase,
+  half_cycles: u64,
+  instructions: u64,
+  instruction_pc: u16,
+  address: u16,
+  data: Option<u16>,
+  boundary: bool,
+  breakpoints: Breakpoints,
+  trace: Option<Trace>,
+  vcd: Option<Vcd>,
+  history: Option<History>,
+  input: bool,
+  recorder: Option<Recorder>,
+  replay: Option<Replay>,
+  script: Option<Script>,
+  #[cfg(unix)]
+  serial: Option<Serial>,
+
+  control: ControlLogic,
+
+  a: AddressRegister,
+  alu: Alu,
+  flags: Flags,
+  i: InstructionRegister,
+  lr: LinkRegister,
+  memory: Memory,
+  pc: ProgramCounter,
+  r: RegisterFile,
+  s: StackPointers,
+}
+
+impl Cpu {
+  /// Builds a machine with `rom` mapped at `0xE000`, clocked at `hz` in real time.
+  ///
+  /// Nothing runs until the first step; the first instruction is the INIT sequence,
+  /// which jumps through the reset vector at `0xFFFF`.
+  pub fn new(hz: f64, rom: Vec<u16>) -> Result<Cpu> {
+    Ok(Cpu::with_control(hz, rom, ControlLogic::new()?))
+  }
+
+  /// Builds a machine as [`Cpu::new`] does, sequenced by `control`, e.g. [`ControlLogic::from_images`].
+  pub fn with_control(hz: f64, rom: Vec<u16>, control: ControlLogic) -> Cpu {
+    Cpu {
+      clock: Clock::new(hz, Pacing::RealTime),
+      engine: Engine::Microcode,
+      halt: false,
+      c: Control::new(),
+      phase: Phase::Transfer,
+      half_cycles: 0,
+      instructions: 0,
+      instruction_pc: 0x0000,
+      address: 0x0000,
+      data: None,
+      boundary: false,
+      breakpoints: Breakpoints::new(),
+      trace: None,
+      vcd: None,
+      history: None,
+      input: false,
+      recorder: None,
+      replay: None,
+      script: None,
+      #[cfg(unix)]
+      serial: None,
+
+      control,
+
+      a: AddressRegister::new(),
+      alu: Alu::new(),
+      flags: Flags::new(),
+      i: InstructionRegister::new(),
+      lr: LinkRegister::new(),
+      memory: Memory::new(rom),
+      pc: ProgramCounter::new(),
+      r: RegisterFile::new(),
+      s: StackPointers::new(),
+    }
+  }
+
+  fn components(&self) -> Vec<&dyn BusComponent> {
+    vec![
+      &self.a, &self.alu, &self.flags, &self.i,
+      &self.lr, &self.memory, &self.pc, &self.r, &self.s,
+    ]
+  }
+
+  fn components_mut(&mut self) -> Vec<&mut dyn BusComponent> {
+    vec![
+      &mut self.a, &mut self.alu, &mut self.flags, &mut self.i,
+      &mut self.lr, &mut self.memory, &mut self.pc, &mut self.r, &mut self.s,
+    ]
+  }
+
+  fn set_control(&mut self, c: Control) {
+    for i in self.components_mut() {
+      i.set_control(c);
+    }
+    self.c = c;
+  }
+
+  fn load(&mut self, value: Option<u16>) -> Result<()> {
+    if let Some(value) = value {
+      for i in self.components_mut() {
+        i.load(value)?;
+      }
+    }
+    Ok(())
+  }
+
+  fn data(&mut self) -> Result<Option<u16>> {
+    let mut out = None;
+    for component in self.components() {
+      match (out, component.data()?) {
+        (Some(_), Some(_)) => return Err(Error::DataBusConflict(self.i.get(), component.name())),
+        (None, data) => out = data,
+        (_, None) => (),
+      }
+    }
+    match out {
+      Some(value) => Ok(Some(value)),
+      None if !self.c.loads() => Ok(None), // NOP, HLT
+      None if self.c.alu.set_flags => Ok(None), // CMP, CPN, TEST
+      None => Err(Error::DataBusUnused(self.i.get())),
+    }
+  }
+
+  fn address(&self) -> Result<u16> {
+    let mut out = None;
+    for component in self.components() {
+      match (out, component.address()?) {
+        (Some(_), Some(_)) => return Err(Error::AddressBusConflict(self.i.get(), component.name())),
+        (None, address) => out = address,
+        (_, None) => (),
+      }
+    }
+    match out {
+      None => Err(Error::AddressBusUnused(self.i.get())),
+      Some(value) => Ok(value),
+    }
+  }
+
+  fn half_cycle(&mut self) -> Result<()> {
+    let c = self.control.decode(self.i.get(), &self.flags, &mut self.i)?;
+    self.set_control(c);
+    self.address = self.address()?;
+    self.memory.set_address(self.address);
+    self.lr.link(self.pc.link());
+    Ok(())
+  }
+
+  fn cycle(&mut self) -> Result<()> {
+    let data = self.data()?;
+    self.load(data)?;
+    self.flags.set_alu(self.alu.get_flags());
+    self.data = data;
+    Ok(())
+  }
+
+  // Runs whichever half of the cycle is next. Only FETCH loads I, so that marks an instruction boundary,
+  // as does a hardware interrupt replacing the FETCH with an INT sequence.
+  fn tick_half(&mut self) -> Result<()> {
+    self.checkpoint();
+    self.inject()?;
+    self.boundary = false;
+    match self.phase {
+      Phase::Transfer => {
+        let request = self.memory.pic()?.request(&self.flags);
+        self.control.request(request);
+        self.half_cycle()?;
+        self.data = None;
+        self.phase = Phase::Decode;
+        if self.control.interrupted() {
+          self.memory.pic()?.acknowledge((self.i.get() >> 3) & 0x0007);
+          self.boundary = true;
+          self.instructions += 1;
+          self.instruction_pc = self.pc.get();
+          self.clock_devices()?;
+          self.record()?;
+        }
+      },
+      Phase::Decode => {
+        self.cycle()?;
+        self.phase = Phase::Transfer;
+        if self.c.i.load {
+          self.boundary = true;
+          self.instructions += 1;
+          self.instruction_pc = self.address;
+          self.clock_devices()?;
+          self.record()?;
+        }
+        if self.c.halt {
+          self.halt = true;
+        }
+      },
+    }
+    self.half_cycles += 1;
+    if let Some(vcd) = &mut self.vcd {
+      let decode = self.phase == Phase::Decode;
+      vcd.sample(&self.c, decode, self.address, self.data, self.control.cycles(), self.control.fetches())?;
+    }
+    Ok(())
+  }
+
+  // Apply replayed and scripted inputs due by now.
+  fn inject(&mut self) -> Result<()> {
+    let half_cycles = self.half_cycles;
+    while let Some(input) = self.replay.as_mut().and_then(|replay| replay.next(half_cycles)) {
+      self.apply(input)?;
+    }
+    if let Some(input) = self.script.as_mut().and_then(|script| script.next(half_cycles)) {
+      self.apply(input)?;
+    }
+    Ok(())
+  }
+
+  fn apply(&mut self, input: Input) -> Result<()> {
+    if let Some(recorder) = &mut self.recorder {
+      recorder.record(self.half_cycles, input)?;
+    }
+    match input {
+      Input::Key(key, keymod) => {
+        if self.keyboard()?.pressed(key, keymod) {
+          self.interrupt(Keyboard::INTERRUPT)?;
+        }
+      },
+      Input::Char(key) => {
+        self.keyboard()?.push(key);
+        self.interrupt(Keyboard::INTERRUPT)?;
+      },
+      Input::Serial(byte) => self.uart()?.receive(byte),
+    }
+    Ok(())
+  }
+
+  // Snapshot for rewinding when one is due, or when something outside the machine changed it since the last one.
+  // Taken before the inputs due this half-cycle, so restoring it moves the replay back to them.
+  fn checkpoint(&mut self) {
+    let due = match &self.history {
+      Some(history) => self.input || history.due(self.half_cycles),
+      None => false,
+    };
+    self.input = false;
+    if due {
+      let state = self.save_state();
+      if let Some(history) = &mut self.history {
+        history.record(self.half_cycles, self.instructions, state);
+      }
+    }
+  }
+
+  // Restores `state` and re-runs it until `done`, without tracing, dumping or checkpointing the replay.
+  fn replay(&mut self, state: Vec<u8>, done: impl Fn(&Cpu) -> bool) -> Result<()> {
+    let history = self.history.take();
+    let (trace, vcd) = (self.trace.take(), self.vcd.take());
+    let result = self.load_state(&state).and_then(|()| {
+      while !done(self) {
+        self.tick_half()?;
+      }
+      Ok(())
+    });
+    self.memory.take_hit();
+    self.history = history;
+    self.trace = trace;
+    self.vcd = vcd;
+    self.input = false;
+    if let Some(history) = &mut self.history {
+      history.truncate(self.half_cycles);
+    }
+    result
+  }
+
+  // Finishes pending DMA and brings the devices that count clock cycles up to date, raising the
+  // interrupts they are due. Only done at instruction boundaries, where both engines agree on the cycle count.
+  fn clock_devices(&mut self) -> Result<()> {
+    self.memory.dma()?;
+    let cycles = self.cycles();
+    if let Some(interrupt) = self.memory.timer()?.advance(cycles) {
+      self.memory.pic()?.raise(interrupt)?;
+    }
+    let hz = self.hz();
+    self.memory.rtc()?.advance(cycles, hz);
+    if let Some(interrupt) = self.memory.uart()?.advance(cycles) {
+      self.memory.pic()?.raise(interrupt)?;
+    }
+    Ok(())
+  }
+
+  // Log the instruction now in I, before it runs.
+  fn record(&mut self) -> Result<()> {
+    if let Some(trace) = &mut self.trace {
+      let op = self.i.get();
+      let registers = [
+        self.r.get(0), self.r.get(1), self.r.get(2), self.r.get(3),
+        self.r.get(4), self.r.get(5), self.r.get(6), self.r.get(7),
+        self.s.get(0), self.s.get(1), self.lr.get(), self.flags.value(),
+      ];
+      trace.record(self.half_cycles / 2, self.instruction_pc, op, self.control.name(op), &registers)?;
+    }
+    Ok(())
+  }
+
+  // Watchpoint hits from the transfer half, then breakpoints if an instruction boundary was crossed.
+  fn trap(&self, boundary: bool) -> Option<Stop> {
+    if let Some(hit) = self.memory.take_hit() {
+      return Some(Stop::Watchpoint { hit, pc: self.instruction_pc })
+    }
+    if boundary {
+      if let Some(id) = self.breakpoints.check(self.instruction_pc, self.i.get()) {
+        return Some(Stop::Breakpoint { id, pc: self.instruction_pc, op: self.i.get() })
+      }
+    }
+    None
+  }
+
+  // Finishes the current cycle, returning any breakpoint or watchpoint it tripped.
+  fn tick(&mut self) -> Result<Option<Stop>> {
+    self.tick_half()?;
+    let mut boundary = self.boundary;
+    if let Phase::Decode = self.phase {
+      self.tick_half()?;
+      boundary |= self.boundary;
+    }
+    Ok(self.trap(boundary))
+  }
+
+  // Runs one instruction on the fast engine, through the FETCH of the next. Mid-instruction, or for
+  // what the fast engine leaves to the microcode, the microcode runs until the next boundary instead.
+  fn step_fast(&mut self) -> Result<Option<Stop>> {
+    self.checkpoint();
+    self.inject()?;
+    if self.phase == Phase::Transfer && self.control.fetched() && fast::interpretable(self.i.get()) {
+      let pc = self.instruction_pc;
+      self.execute()?;
+      if let Some(hit) = self.memory.take_hit() {
+        return Ok(Some(Stop::Watchpoint { hit, pc }))
+      }
+      return Ok(self.trap(true))
+    }
+
+    self.finish_instruction()
+  }
+
+  // Ticks until the next instruction boundary or a HLT, or a breakpoint or watchpoint stops it.
+  fn finish_instruction(&mut self) -> Result<Option<Stop>> {
+    let instructions = self.instructions;
+    let mut stop = None;
+    while self.instructions == instructions && stop.is_none() {
+      stop = self.tick()?;
+      if self.c.halt {
+        break;
+      }
+    }
+    Ok(stop)
+  }
+
+  fn report(&self, half_cycles: u64, instructions: u64) -> Step {
+    let (name, microcode) = self.control.current();
+    Step {
+      half_cycles: self.half_cycles - half_cycles,
+      instructions: self.instructions - instructions,
+      phase: self.phase,
+      name,
+      microcode,
+      control: self.c,
+      address: self.address,
+      data: self.data,
+      pc: self.instruction_pc,
+      op: self.i.get(),
+      halted: self.halt,
+      stop: None,
+    }
+  }
+
+  /// Nominal clock rate given to `new`.
+  pub fn hz(&self) -> f64 {
+    self.clock.hz()
+  }
+
+  /// Paced clock rate, or `None` when unthrottled.
+  pub fn rate(&self) -> Option<f64> {
+    self.clock.rate()
+  }
+
+  pub fn pacing(&self) -> Pacing {
+    self.clock.pacing()
+  }
+
+  pub fn set_pacing(&mut self, pacing: Pacing) {
+    self.clock.set_pacing(pacing);
+  }
+
+  pub fn engine(&self) -> Engine {
+    self.engine
+  }
+
+  /// True if the machine runs the built-in control store, which `Engine::Fast` needs.
+  pub fn fast_available(&self) -> bool {
+    self.control.built_in()
+  }
+
+  pub fn set_engine(&mut self, engine: Engine) -> Result<()> {
+    if engine == Engine::Fast && !self.fast_available() {
+      return Err(Error::EngineUnavailable(String::from("fast")))
+    }
+    self.engine = engine;
+    Ok(())
+  }
+
+  /// Clock cycles completed since power on.
+  pub fn cycles(&self) -> u64 {
+    self.half_cycles / 2
+  }
+
+  /// Instruction boundaries crossed since power on, counting each hardware interrupt sequence as one.
+  pub fn instructions(&self) -> u64 {
+    self.instructions
+  }
+
+  /// Address the instruction currently in `I` was fetched from.
+  pub fn instruction_pc(&self) -> u16 {
+    self.instruction_pc
+  }
+
+  // The step_* functions run immediately, ignoring pacing and the pause latch.
+
+  /// Runs a single half-cycle: either the decode/address half or the data transfer half.
+  pub fn step_half_cycle(&mut self) -> Result<Step> {
+    let (half_cycles, instructions) = (self.half_cycles, self.instructions);
+    self.tick_half()?;
+    let mut step = self.report(half_cycles, instructions);
+    step.stop = self.trap(self.boundary);
+    Ok(step)
+  }
+
+  /// Runs one microcode step, finishing the current one if a half-cycle step left it in progress.
+  pub fn step_cycle(&mut self) -> Result<Step> {
+    let (half_cycles, instructions) = (self.half_cycles, self.instructions);
+    let stop = self.tick()?;
+    let mut step = self.report(half_cycles, instructions);
+    step.stop = stop;
+    Ok(step)
+  }
+
+  /// Runs the rest of the current instruction, through the FETCH of the next one.
+  ///
+  /// A watchpoint hit ends the step early, after the cycle that tripped it.
+  pub fn step_instruction(&mut self) -> Result<Step> {
+    let (half_cycles, instructions) = (self.half_cycles, self.instructions);
+    let stop = match self.engine {
+      Engine::Microcode => self.finish_instruction()?,
+      Engine::Fast => self.step_fast()?,
+    };
+    let mut step = self.report(half_cycles, instructions);
+    step.stop = stop;
+    Ok(step)
+  }
+
+  /// Like `step_instruction`, but a linking JMl runs until the call returns to the following instruction.
+  ///
+  /// The call has returned once execution reaches the link address with both stack pointers
+  /// back where they were, so recursive calls through the same call site are stepped over too.
+  /// Breakpoints and watchpoints inside the call end the step where they hit.
+  pub fn step_over(&mut self) -> Result<Step> {
+    let (half_cycles, instructions) = (self.half_cycles, self.instructions);
+    let mut linked = false;
+    let mut stop = None;
+    while self.instructions == instructions && stop.is_none() {
+      stop = self.tick()?;
+      linked |= self.c.link;
+      if self.c.halt {
+        break;
+      }
+    }
+
+    if linked && !self.c.halt && stop.is_none() {
+      let (address, s) = (self.lr.get(), [self.s.get(0), self.s.get(1)]);
+      loop {
+        let boundary = self.instructions;
+        while self.instructions == boundary && stop.is_none() {
+          stop = self.tick()?;
+          if self.c.halt {
+            break;
+          }
+        }
+        if self.c.halt || stop.is_some() {
+          break;
+        }
+        if self.instruction_pc == address && self.s.get(0) == s[0] && self.s.get(1) == s[1] {
+          break;
+        }
+      }
+    }
+    let mut step = self.report(half_cycles, instructions);
+    step.stop = stop;
+    Ok(step)
+  }
+
+  /// Starts logging every instruction to `trace`, replacing any trace already running.
+  pub fn set_trace(&mut self, trace: Trace) {
+    self.trace = Some(trace);
+  }
+
+  /// Stops tracing, handing back the trace so it can be flushed.
+  pub fn take_trace(&mut self) -> Option<Trace> {
+    self.trace.take()
+  }
+
+  /// Starts dumping control signals and buses every half-cycle to `vcd`.
+  pub fn set_vcd(&mut self, vcd: Vcd) {
+    self.vcd = Some(vcd);
+  }
+
+  pub fn take_vcd(&mut self) -> Option<Vcd> {
+    self.vcd.take()
+  }
+
+  /// Starts keeping snapshots in `history`, so execution can be rewound.
+  pub fn set_history(&mut self, history: History) {
+    self.history = Some(history);
+  }
+
+  pub fn history(&self) -> Option<&History> {
+    self.history.as_ref()
+  }
+
+  /// Goes back `n` half-cycles, by restoring the latest snapshot before then and re-running from it.
+  pub fn rewind_half_cycles(&mut self, n: u64) -> Result<()> {
+    let target = self.half_cycles.saturating_sub(n);
+    let state = match self.history.as_ref().and_then(|h| h.before_half_cycle(target)) {
+      Some(checkpoint) => checkpoint.state.clone(),
+      None => return Err(Error::RewindUnavailable(self.history.as_ref().and_then(|h| h.oldest()))),
+    };
+    self.replay(state, |cpu| cpu.half_cycles >= target)
+  }
+
+  /// Goes back to the start of the cycle `n` cycles ago; `0` restarts the current one.
+  pub fn rewind_cycles(&mut self, n: u64) -> Result<()> {
+    let target = self.cycles().saturating_sub(n) * 2;
+    self.rewind_half_cycles(self.half_cycles - target)
+  }
+
+  /// Goes back to just after the FETCH of the instruction `n` instructions ago.
+  ///
+  /// `0` restarts the current instruction, which after an error is the one that caused it.
+  pub fn rewind_instructions(&mut self, n: u64) -> Result<()> {
+    let target = self.instructions.saturating_sub(n);
+    let state = match self.history.as_ref().and_then(|h| h.before_instruction(target)) {
+      Some(checkpoint) => checkpoint.state.clone(),
+      None => return Err(Error::RewindUnavailable(self.history.as_ref().and_then(|h| h.oldest()))),
+    };
+    self.replay(state, |cpu| cpu.instructions >= target && cpu.phase == Phase::Transfer)
+  }
+
+  /// Queues a key press, raising the keyboard interrupt, and logs it if recording.
+  pub fn press(&mut self, key: Keycode, keymod: Mod) -> Result<()> {
+    self.apply(Input::Key(key, keymod))
+  }
+
+  /// Logs every input from here on to `recorder`, timestamped by half-cycle.
+  pub fn set_recorder(&mut self, recorder: Recorder) {
+    self.recorder = Some(recorder);
+  }
+
+  pub fn take_recorder(&mut self) -> Option<Recorder> {
+    self.recorder.take()
+  }
+
+  /// Injects the inputs in `replay`, each right before the half-cycle it was recorded at.
+  /// Inputs recorded before the current half-cycle are skipped.
+  pub fn set_replay(&mut self, mut replay: Replay) {
+    replay.seek(self.half_cycles);
+    self.replay = Some(replay);
+  }
+
+  /// Types the keys in `script` as the machine runs, replacing any script still in progress.
+  pub fn set_script(&mut self, script: Script) {
+    self.script = Some(script);
+  }
+
+  /// Connects the UART to `serial`. Bytes are exchanged with it once per `run`, and logged if recording.
+  #[cfg(unix)]
+  pub fn set_serial(&mut self, serial: Serial) {
+    self.serial = Some(serial);
+  }
+
+  #[cfg(unix)]
+  pub fn take_serial(&mut self) -> Option<Serial> {
+    self.serial.take()
+  }
+
+  // Passes bytes from the host end of the UART in as inputs.
+  #[cfg(unix)]
+  fn receive(&mut self) -> Result<()> {
+    while let Some(byte) = self.serial.as_mut().and_then(|serial| serial.receive()) {
+      self.apply(Input::Serial(byte))?;
+    }
+    Ok(())
+  }
+
+  // Hands the bytes the UART sent to the host, or drops them with nothing connected.
+  #[cfg(unix)]
+  fn transmit(&mut self) -> Result<()> {
+    let bytes = self.memory.uart()?.transmitted();
+    match &mut self.serial {
+      Some(serial) => serial.send(&bytes),
+      None => Ok(()),
+    }
+  }
+
+  // Without a host end the UART receives nothing and whatever it sends is dropped.
+  #[cfg(not(unix))]
+  fn receive(&mut self) -> Result<()> {
+    Ok(())
+  }
+
+  #[cfg(not(unix))]
+  fn transmit(&mut self) -> Result<()> {
+    self.memory.uart()?.transmitted();
+    Ok(())
+  }
+
+  /// Serializes the whole machine: every component, memory and IO, and the control logic mid-instruction.
+  ///
+  /// Breakpoints, watchpoints, traces and pacing belong to the session, not the machine, and are not included.
+  pub fn save_state(&self) -> Vec<u8> {
+    let mut w = Writer::new();
+    self.save(&mut w);
+    w.into_bytes()
+  }
+
+  /// Replaces the machine state with a snapshot from `save_state`, including the ROM.
+  /// A replay or script in progress carries on from the inputs due at the restored half-cycle.
+  pub fn load_state(&mut self, bytes: &[u8]) -> Result<()> {
+    let mut r = Reader::new(bytes)?;
+    self.restore(&mut r)?;
+    r.finish()?;
+    self.clock.resync();
+    let half_cycles = self.half_cycles;
+    if let Some(replay) = &mut self.replay {
+      replay.seek(half_cycles);
+    }
+    if let Some(script) = &mut self.script {
+      script.seek(half_cycles);
+    }
+    if let Some(history) = &mut self.history {
+      history.clear();
+    }
+    Ok(())
+  }
+
+  pub fn save_state_file(&self, filename: &str) -> Result<()> {
+    let mut w = Writer::new();
+    self.save(&mut w);
+    w.write_file(filename)
+  }
+
+  pub fn load_state_file(&mut self, filename: &str) -> Result<()> {
+    self.load_state(&snapshot::read_file(filename)?)
+  }
+
+  pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> usize {
+    self.breakpoints.add(breakpoint)
+  }
+
+  pub fn remove_breakpoint(&mut self, id: usize) -> Option<Breakpoint> {
+    self.breakpoints.remove(id)
+  }
+
+  pub fn breakpoints(&self) -> &Breakpoints {
+    &self.breakpoints
+  }
+
+  pub fn breakpoints_mut(&mut self) -> &mut Breakpoints {
+    &mut self.breakpoints
+  }
+
+  pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> usize {
+    self.memory.watchpoints_mut().add(watchpoint)
+  }
+
+  pub fn remove_watchpoint(&mut self, id: usize) -> Option<Watchpoint> {
+    self.memory.watchpoints_mut().remove(id)
+  }
+
+  pub fn watchpoints(&self) -> &Watchpoints {
+    self.memory.watchpoints()
+  }
+
+  /// Runs up to `cycles` clock cycles at the configured pacing.
+  ///
+  /// Stops early on HLT, a breakpoint or a watchpoint; time left in the budget is still paced out after a HLT.
+  pub fn run(&mut self, cycles: u32) -> Stop {
+    if let Err(error) = self.receive() {
+      return Stop::Error(error)
+    }
+    let stop = self.run_cycles(cycles);
+    match self.transmit() {
+      Err(error) if !matches!(stop, Stop::Error(_)) => Stop::Error(error),
+      _ => stop,
+    }
+  }
+
+  fn run_cycles(&mut self, cycles: u32) -> Stop {
+    if let Err(error) = self.inject() {
+      return Stop::Error(error)
+    }
+    if self.halt {
+      self.clock.tick(cycles as u64);
+      return Stop::Halted
+    }
+
+    if self.engine == Engine::Fast {
+      return self.run_fast(cycles as u64)
+    }
+
+    for cycle in 0..cycles {
+      let stop = match self.tick() {
+        Err(error) => return Stop::Error(error),
+        Ok(stop) => stop,
+      };
+      self.clock.tick(1);
+
+      if self.halt {
+        self.clock.tick((cycles - cycle - 1) as u64);
+        return Stop::Halted
+      }
+      if let Some(stop) = stop {
+        return stop
+      }
+    }
+    Stop::Budget
+  }
+
+  // `run` on the fast engine, which can only stop between instructions and so may overrun the budget a little.
+  fn run_fast(&mut self, cycles: u64) -> Stop {
+    let start = self.cycles();
+    while self.cycles() - start < cycles {
+      let stop = match self.step_fast() {
+        Err(error) => return Stop::Error(error),
+        Ok(stop) => stop,
+      };
+      if self.halt {
+        self.clock.tick(std::cmp::max(self.cycles() - start, cycles));
+        return Stop::Halted
+      }
+      if let Some(stop) = stop {
+        self.clock.tick(self.cycles() - start);
+        return stop
+      }
+    }
+    self.clock.tick(self.cycles() - start);
+    Stop::Budget
+  }
+
+  /// True once a HLT has executed, or while paused.
+  pub fn halted(&self) -> bool {
+    self.halt
+  }
+
+  /// Toggles the pause/halt latch, which also resumes after a HLT.
+  pub fn pause(&mut self) {
+    self.halt = !self.halt;
+  }
+
+  pub fn register(&self, r: Reg) -> u16 {
+    match r {
+      Reg::R0 => self.r.get(0), Reg::R1 => self.r.get(1), Reg::R2 => self.r.get(2), Reg::R3 => self.r.get(3),
+      Reg::R4 => self.r.get(4), Reg::R5 => self.r.get(5), Reg::R6 => self.r.get(6), Reg::R7 => self.r.get(7),
+      Reg::S0 => self.s.get(0),
+      Reg::S1 => self.s.get(1),
+      Reg::PC => self.pc.get(),
+      Reg::LR => self.lr.get(),
+      Reg::F  => self.flags.value(),
+      Reg::A  => self.a.get(),
+      Reg::I  => self.i.get(),
+    }
+  }
+
+  /// Overwrites a register between cycles. Writing `I` mid-instruction does not re-decode it.
+  pub fn set_register(&mut self, r: Reg, value: u16) {
+    match r {
+      Reg::R0 => self.r.set(0, value), Reg::R1 => self.r.set(1, value),
+      Reg::R2 => self.r.set(2, value), Reg::R3 => self.r.set(3, value),
+      Reg::R4 => self.r.set(4, value), Reg::R5 => self.r.set(5, value),
+      Reg::R6 => self.r.set(6, value), Reg::R7 => self.r.set(7, value),
+      Reg::S0 => self.s.set(0, value),
+      Reg::S1 => self.s.set(1, value),
+      Reg::PC => self.pc.set(value),
+      Reg::LR => self.lr.set(value),
+      Reg::F  => self.flags.set_value(value),
+      Reg::A  => self.a.set(value),
+      Reg::I  => self.i.set(value),
+    }
+    self.input = true;
+  }
+
+  /// The general purpose registers.
+  pub fn registers(&self) -> &RegisterFile {
+    &self.r
+  }
+
+  /// Both stack pointers.
+  pub fn stack_pointers(&self) -> &StackPointers {
+    &self.s
+  }
+
+  /// The flags register, ALU flags and interrupt masks.
+  pub fn flags(&self) -> &Flags {
+    &self.flags
+  }
+
+  /// Continues execution at `address`, as a debugger's "set PC" would.
+  ///
+  /// At an instruction boundary the instruction already fetched into `I` is replaced by the one at
+  /// `address`. Mid-instruction only `PC` changes, so the rest of the current instruction still runs.
+  pub fn jump(&mut self, address: u16) -> Result<()> {
+    self.input = true;
+    let boundary = self.phase == Phase::Transfer && self.control.current().1 == Some(0);
+    if boundary {
+      let op = self.memory.peek(address)?;
+      self.i.set(op);
+      self.instruction_pc = address;
+    }
+    // FETCH's increment lands with the next control word, so PC still points at the fetched instruction.
+    self.pc.set(address);
+    Ok(())
+  }
+
+  /// Reads memory without side effects (the keyboard FIFO is not popped).
+  pub fn peek(&self, address: u16) -> Result<u16> {
+    self.memory.peek(address)
+  }
+
+  /// Writes memory as the bus would; writes to ROM are an error.
+  pub fn poke(&mut self, address: u16, value: u16) -> Result<()> {
+    self.input = true;
+    self.memory.poke(address, value)
+  }
+
+  pub fn memory(&self) -> &Memory {
+    &self.memory
+  }
+
+  pub fn memory_mut(&mut self) -> &mut Memory {
+    self.input = true;
+    &mut self.memory
+  }
+
+  pub fn screen(&self) -> Result<&Screen> {
+    self.memory.screen()
+  }
+
+  pub fn keyboard(&mut self) -> Result<&mut Keyboard> {
+    self.input = true;
+    self.memory.keyboard()
+  }
+
+  /// The interrupt controller, for inspecting pending and in-service lines.
+  pub fn pic(&mut self) -> Result<&mut Pic> {
+    self.input = true;
+    self.memory.pic()
+  }
+
+  pub fn timer(&mut self) -> Result<&mut Timer> {
+    self.input = true;
+    self.memory.timer()
+  }
+
+  pub fn rtc(&mut self) -> Result<&mut Rtc> {
+    self.input = true;
+    self.memory.rtc()
+  }
+
+  pub fn uart(&mut self) -> Result<&mut Uart> {
+    self.input = true;
+    self.memory.uart()
+  }
+
+  pub fn sd(&mut self) -> Result<&mut Sd> {
+    self.input = true;
+    self.memory.sd()
+  }
+
+  /// Sets where the RTC gets the time from. Fixed or virtual time keeps runs reproducible.
+  pub fn set_time_source(&mut self, source: TimeSource) -> Result<()> {
+    self.rtc()?.set_source(source);
+    Ok(())
+  }
+
+  /// Raises hardware interrupt line `interrupt` (0-7). It stays pending until it is unmasked,
+  /// then is taken at an instruction boundary, highest priority (lowest) line first.
+  pub fn interrupt(&mut self, interrupt: u16) -> Result<()> {
+    self.input = true;
+    self.memory.pic()?.raise(interrupt)
+  }
+}
+
+impl Snapshot for Cpu {
+  fn save(&self, w: &mut Writer) {
+    w.bool(self.halt);
+    w.control(&self.c);
+    w.bool(self.phase == Phase::Decode);
+    w.u64(self.half_cycles);
+    w.u64(self.instructions);
+    w.u16(self.instruction_pc);
+    w.u16(self.address);
+    w.option(self.data);
+    w.bool(self.boundary);
+
+    self.control.save(w);
+
+    self.a.save(w);
+    self.alu.save(w);
+    self.flags.save(w);
+    self.i.save(w);
+    self.lr.save(w);
+    self.memory.save(w);
+    self.pc.save(w);
+    self.r.save(w);
+    self.s.save(w);
+  }
+
+  fn restore(&mut self, r: &mut Reader) -> Result<()> {
+    self.halt = r.bool()?;
+    self.c = r.control()?;
+    self.phase = if r.bool()? { Phase::Decode } else { Phase::Transfer };
+    self.half_cycles = r.u64()?;
+    self.instructions = r.u64()?;
+    self.instruction_pc = r.u16()?;
+    self.address = r.u16()?;
+    self.data = r.option()?;
+    self.boundary = r.bool()?;
+
+    self.control.restore(r)?;
+
+    self.a.restore(r)?;
+    self.alu.restore(r)?;
+    self.flags.restore(r)?;
+    self.i.restore(r)?;
+    self.lr.restore(r)?;
+    self.memory.restore(r)?;
+    self.pc.restore(r)?;
+    self.r.restore(r)?;
+    self.s.restore(r)?;
+    Ok(())
+  }
+}
+
+impl fmt::Display for Cpu {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{c}\n{r}\n\n{pc}\n{lr}\n{s}\n{a}\n{mem}\n\n{i}\n{f}\n\n{alu}\n\n",
+      c=self.control, r=self.r, pc=self.pc, lr=self.lr, s=self.s, a=self.a, mem=self.memory, i=self.i, f=self.flags, alu=self.alu)
+  }
+}
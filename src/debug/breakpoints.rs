@@ -0,0 +1,83 @@
+use std::fmt;
+
+
+// Checked at every instruction boundary: after FETCH loads I, or when a hardware interrupt is taken.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Breakpoint {
+  Address(u16),
+  Opcode { value: u16, mask: u16 },
+}
+
+impl Breakpoint {
+  pub fn opcode(value: u16, mask: u16) -> Breakpoint {
+    Breakpoint::Opcode { value: value & mask, mask }
+  }
+
+  pub fn matches(&self, pc: u16, op: u16) -> bool {
+    match *self {
+      Breakpoint::Address(address) => pc == address,
+      Breakpoint::Opcode { value, mask } => (op & mask) == value,
+    }
+  }
+}
+
+impl fmt::Display for Breakpoint {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Breakpoint::Address(address) => write!(f, "PC == 0x{:04X}", address),
+      Breakpoint::Opcode { value, mask } => write!(f, "I & 0x{:04X} == 0x{:04X}", mask, value),
+    }
+  }
+}
+
+
+#[derive(Debug)]
+pub struct Breakpoints {
+  next: usize,
+  list: Vec<(usize, Breakpoint)>,
+}
+
+impl Default for Breakpoints {
+  fn default() -> Breakpoints {
+    Breakpoints::new()
+  }
+}
+
+impl Breakpoints {
+  pub fn new() -> Breakpoints {
+    Breakpoints {
+      next: 1,
+      list: Vec::new(),
+    }
+  }
+
+  pub fn add(&mut self, breakpoint: Breakpoint) -> usize {
+    let id = self.next;
+    self.next += 1;
+    self.list.push((id, breakpoint));
+    id
+  }
+
+  pub fn remove(&mut self, id: usize) -> Option<Breakpoint> {
+    let index = self.list.iter().position(|(i, _)| *i == id)?;
+    Some(self.list.remove(index).1)
+  }
+
+  pub fn clear(&mut self) {
+    self.list.clear();
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.list.is_empty()
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = &(usize, Breakpoint)> {
+    self.list.iter()
+  }
+
+  pub fn check(&self, pc: u16, op: u16) -> Option<usize> {
+    self.list.iter()
+      .find(|(_, breakpoint)| breakpoint.matches(pc, op))
+      .map(|(id, _)| *id)
+  }
+}
@@ -0,0 +1,458 @@
+use std::io::prelude::*;
+use std::io::{
+  BufReader,
+  ErrorKind,
+};
+use std::net::{
+  TcpListener,
+  TcpStream,
+};
+use crate::error::{
+  Result,
+  Error,
+};
+use crate::cpu::{
+  Cpu,
+  Reg,
+  Stop,
+};
+use super::{
+  Breakpoint,
+  Watchpoint,
+  WatchKind,
+};
+
+
+// GDB's register numbers are indices into this; PC reads as the address of the instruction about to run.
+const REGISTERS: [Reg; 13] = [
+  Reg::R0, Reg::R1, Reg::R2, Reg::R3, Reg::R4, Reg::R5, Reg::R6, Reg::R7,
+  Reg::S0, Reg::S1, Reg::PC, Reg::LR, Reg::F,
+];
+
+// `c` runs in chunks this size, checking for a ^C from the client in between.
+const CONTINUE_CYCLES: u32 = 4096;
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.cpu-emulator.core">
+    <reg name="r0" bitsize="16" type="uint16" regnum="0"/>
+    <reg name="r1" bitsize="16" type="uint16"/>
+    <reg name="r2" bitsize="16" type="uint16"/>
+    <reg name="r3" bitsize="16" type="uint16"/>
+    <reg name="r4" bitsize="16" type="uint16"/>
+    <reg name="r5" bitsize="16" type="uint16"/>
+    <reg name="r6" bitsize="16" type="uint16"/>
+    <reg name="r7" bitsize="16" type="uint16"/>
+    <reg name="s0" bitsize="16" type="data_ptr"/>
+    <reg name="s1" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="lr" bitsize="16" type="code_ptr"/>
+    <reg name="f" bitsize="16" type="uint16"/>
+  </feature>
+</target>
+"#;
+
+
+fn hex_u16(s: &str) -> Option<u16> {
+  u16::from_str_radix(s, 16).ok()
+}
+
+fn hex_usize(s: &str) -> Option<usize> {
+  usize::from_str_radix(s, 16).ok()
+}
+
+// Registers and memory words go over the wire little-endian, low byte first.
+fn encode_word(value: u16) -> String {
+  format!("{:02x}{:02x}", value & 0x00FF, value >> 8)
+}
+
+fn decode_bytes(s: &str) -> Option<Vec<u8>> {
+  if !s.len().is_multiple_of(2) {
+    return None
+  }
+  (0..s.len()).step_by(2)
+    .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+    .collect()
+}
+
+fn decode_word(s: &str) -> Option<u16> {
+  match *decode_bytes(s)?.as_slice() {
+    [l, h] => Some(u16::from_le_bytes([l, h])),
+    _ => None,
+  }
+}
+
+// `addr,len` as used by m, M and qXfer.
+fn address_length(s: &str) -> Option<(usize, usize)> {
+  let mut parts = s.splitn(2, ',');
+  let address = hex_usize(parts.next()?)?;
+  let length = hex_usize(parts.next()?)?;
+  Some((address, length))
+}
+
+// Word address and length in bytes of an `m` or `M` packet, if it stays inside the 64 KiW address space.
+fn memory_range(s: &str) -> Option<(usize, usize)> {
+  address_length(s).filter(|(address, length)| *address <= 0xFFFF && *length <= 0x20000)
+}
+
+
+/// GDB remote serial protocol server.
+///
+/// Memory is word addressed on the wire as well: `m e000,4` reads the two words at `0xE000` and
+/// `0xE001`, each sent low byte first, so addresses match PC, breakpoints and watchpoints.
+/// Software and hardware breakpoints (`Z0`/`Z1`) both become `Breakpoint::Address`; `Z2`-`Z4` become
+/// write, read and access watchpoints covering `len / 2` words.
+pub struct GdbStub<'a> {
+  cpu: &'a mut Cpu,
+  // (Z type, address, breakpoint or watchpoint id) for the points GDB inserted.
+  points: Vec<(u8, u16, usize)>,
+  no_ack: bool,
+}
+
+impl<'a> GdbStub<'a> {
+  pub fn new(cpu: &'a mut Cpu) -> GdbStub<'a> {
+    GdbStub {
+      cpu,
+      points: Vec::new(),
+      no_ack: false,
+    }
+  }
+
+  /// Listens on `127.0.0.1:port` and serves one debugger connection until it detaches or kills the target.
+  pub fn serve(&mut self, port: u16) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    eprintln!("Waiting for GDB on {}...", listener.local_addr()?);
+    self.serve_listener(listener)
+  }
+
+  /// Serves one debugger connection accepted from `listener`.
+  pub fn serve_listener(&mut self, listener: TcpListener) -> Result<()> {
+    let (stream, address) = listener.accept()?;
+    eprintln!("GDB connected from {}.", address);
+    self.session(stream)
+  }
+
+  fn session(&mut self, stream: TcpStream) -> Result<()> {
+    stream.set_nodelay(true)?;
+    let mut output = stream.try_clone()?;
+    let mut input = BufReader::new(stream);
+    while let Some(packet) = self.receive(&mut input, &mut output)? {
+      let (reply, done) = self.packet(&packet, &mut input)?;
+      if let Some(reply) = reply {
+        self.send(&mut output, &reply)?;
+      }
+      if done {
+        break;
+      }
+    }
+    for (kind, _, id) in self.points.drain(..) {
+      match kind {
+        0 | 1 => { self.cpu.remove_breakpoint(id); },
+        _ => { self.cpu.remove_watchpoint(id); },
+      }
+    }
+    Ok(())
+  }
+
+  // Next packet body, acking it; None once the client disconnects.
+  fn receive(&mut self, input: &mut BufReader<TcpStream>, output: &mut TcpStream) -> Result<Option<String>> {
+    let mut byte = [0u8; 1];
+    loop {
+      if input.read(&mut byte)? == 0 {
+        return Ok(None)
+      }
+      // Acks, and a ^C while already stopped, need no reply.
+      if byte[0] != b'$' {
+        continue;
+      }
+
+      let mut body = Vec::new();
+      loop {
+        if input.read(&mut byte)? == 0 {
+          return Ok(None)
+        }
+        if byte[0] == b'#' {
+          break;
+        }
+        body.push(byte[0]);
+      }
+      let mut checksum = [0u8; 2];
+      input.read_exact(&mut checksum)?;
+
+      let expected = std::str::from_utf8(&checksum).ok().and_then(|s| u8::from_str_radix(s, 16).ok());
+      let sum = body.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+      if !self.no_ack {
+        if expected != Some(sum) {
+          output.write_all(b"-")?;
+          continue;
+        }
+        output.write_all(b"+")?;
+      }
+      return Ok(Some(String::from_utf8_lossy(&body).into_owned()))
+    }
+  }
+
+  fn send(&self, output: &mut TcpStream, reply: &str) -> Result<()> {
+    let mut body = Vec::with_capacity(reply.len());
+    for &b in reply.as_bytes() {
+      match b {
+        b'$' | b'#' | b'}' | b'*' => body.extend_from_slice(&[b'}', b ^ 0x20]),
+        b => body.push(b),
+      }
+    }
+    let sum = body.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+    output.write_all(b"$")?;
+    output.write_all(&body)?;
+    write!(output, "#{:02x}", sum)?;
+    output.flush()?;
+    Ok(())
+  }
+
+  // Reply to send (None for none at all), and whether the session is over.
+  fn packet(&mut self, packet: &str, input: &mut BufReader<TcpStream>) -> Result<(Option<String>, bool)> {
+    let (command, args) = packet.split_at(std::cmp::min(1, packet.len()));
+    let reply = match command {
+      "?" => format!("S{:02x}", SIGTRAP),
+      "g" => REGISTERS.iter().map(|r| encode_word(self.register(*r))).collect(),
+      "G" => self.write_registers(args)?,
+      "p" => match hex_usize(args).and_then(|n| REGISTERS.get(n)) {
+        Some(r) => encode_word(self.register(*r)),
+        None => String::from("E01"),
+      },
+      "P" => self.write_register(args)?,
+      "m" => self.read_memory(args),
+      "M" => self.write_memory(args),
+      "s" => {
+        let step = self.cpu.step_instruction();
+        let stop = match step {
+          Err(error) => Stop::Error(error),
+          Ok(step) => step.stop.unwrap_or(Stop::Budget),
+        };
+        self.stop_reply(Some(stop))
+      },
+      "c" => {
+        let stop = self.resume(input)?;
+        self.stop_reply(stop)
+      },
+      // Reverse step; stopping at the start of the recorded history is how GDB expects it to fail.
+      "b" if args == "s" => match self.cpu.rewind_instructions(1) {
+        Err(Error::RewindUnavailable(_)) => format!("T{:02x}replaylog:begin;", SIGTRAP),
+        Err(error) => self.stop_reply(Some(Stop::Error(error))),
+        Ok(()) => self.stop_reply(Some(Stop::Budget)),
+      },
+      "Z" | "z" => self.point(command == "Z", args),
+      "H" | "T" => String::from("OK"),
+      "D" => return Ok((Some(String::from("OK")), true)),
+      "k" => return Ok((None, true)),
+      "q" | "Q" => self.query(packet),
+      _ => String::new(),
+    };
+    Ok((Some(reply), false))
+  }
+
+  fn query(&mut self, packet: &str) -> String {
+    if packet.starts_with("qSupported") {
+      String::from("PacketSize=1000;qXfer:features:read+;swbreak+;hwbreak+;QStartNoAckMode+;ReverseStep+")
+    } else if let Some(args) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+      match address_length(args) {
+        Some((offset, length)) if offset < TARGET_XML.len() => {
+          let end = std::cmp::min(offset + length, TARGET_XML.len());
+          let more = if end < TARGET_XML.len() { "m" } else { "l" };
+          format!("{}{}", more, &TARGET_XML[offset..end])
+        },
+        Some(_) => String::from("l"),
+        None => String::from("E00"),
+      }
+    } else if packet == "QStartNoAckMode" {
+      self.no_ack = true;
+      String::from("OK")
+    } else if packet == "qAttached" {
+      String::from("1")
+    } else if packet == "qfThreadInfo" {
+      String::from("m1")
+    } else if packet == "qsThreadInfo" {
+      String::from("l")
+    } else if packet == "qC" {
+      String::from("QC1")
+    } else {
+      String::new()
+    }
+  }
+
+  fn register(&self, r: Reg) -> u16 {
+    match r {
+      Reg::PC => self.cpu.instruction_pc(),
+      r => self.cpu.register(r),
+    }
+  }
+
+  fn set_register(&mut self, r: Reg, value: u16) -> Result<()> {
+    match r {
+      Reg::PC => self.cpu.jump(value)?,
+      r => self.cpu.set_register(r, value),
+    }
+    Ok(())
+  }
+
+  fn write_registers(&mut self, args: &str) -> Result<String> {
+    if args.len() != REGISTERS.len() * 4 {
+      return Ok(String::from("E01"))
+    }
+    let values = (0..REGISTERS.len())
+      .map(|i| decode_word(&args[i * 4..i * 4 + 4]))
+      .collect::<Option<Vec<u16>>>();
+    match values {
+      None => Ok(String::from("E01")),
+      Some(values) => {
+        for (r, value) in REGISTERS.iter().zip(values) {
+          // Only move PC if it actually changed, so an unrelated G doesn't refetch mid-instruction.
+          if *r != Reg::PC || value != self.register(Reg::PC) {
+            self.set_register(*r, value)?;
+          }
+        }
+        Ok(String::from("OK"))
+      },
+    }
+  }
+
+  fn write_register(&mut self, args: &str) -> Result<String> {
+    let mut parts = args.splitn(2, '=');
+    let r = parts.next().and_then(hex_usize).and_then(|n| REGISTERS.get(n)).cloned();
+    let value = parts.next().and_then(decode_word);
+    match (r, value) {
+      (Some(r), Some(value)) => {
+        self.set_register(r, value)?;
+        Ok(String::from("OK"))
+      },
+      _ => Ok(String::from("E01")),
+    }
+  }
+
+  fn read_memory(&self, args: &str) -> String {
+    let (address, length) = match memory_range(args) {
+      Some(range) => range,
+      None => return String::from("E01"),
+    };
+    let mut reply = String::with_capacity(length * 2);
+    for i in 0..length.div_ceil(2) {
+      let word = match self.cpu.peek((address + i) as u16) {
+        Ok(word) => encode_word(word),
+        Err(_) if i > 0 => break,
+        Err(_) => return String::from("E02"),
+      };
+      reply.push_str(&word[..std::cmp::min(4, (length - i * 2) * 2)]);
+    }
+    reply
+  }
+
+  fn write_memory(&mut self, args: &str) -> String {
+    let mut parts = args.splitn(2, ':');
+    let range = parts.next().and_then(memory_range);
+    let bytes = parts.next().and_then(decode_bytes);
+    let (address, bytes) = match (range, bytes) {
+      (Some((address, length)), Some(bytes)) if bytes.len() == length => (address, bytes),
+      _ => return String::from("E01"),
+    };
+    for (i, chunk) in bytes.chunks(2).enumerate() {
+      let address = (address + i) as u16;
+      // An odd trailing byte only replaces the low half of its word.
+      let word = match *chunk {
+        [l, h] => Ok(u16::from_le_bytes([l, h])),
+        [l] => self.cpu.peek(address).map(|word| (word & 0xFF00) | (l as u16)),
+        _ => unreachable!(),
+      };
+      if word.and_then(|word| self.cpu.poke(address, word)).is_err() {
+        return String::from("E02")
+      }
+    }
+    String::from("OK")
+  }
+
+  fn point(&mut self, insert: bool, args: &str) -> String {
+    let mut parts = args.split(',');
+    let kind = parts.next().and_then(|kind| kind.parse::<u8>().ok());
+    let address = parts.next().and_then(hex_u16);
+    let length = parts.next().and_then(hex_u16);
+    let (kind, address, length) = match (kind, address, length) {
+      (Some(kind), Some(address), Some(length)) if kind <= 4 => (kind, address, length),
+      _ => return String::new(),
+    };
+
+    if insert {
+      let id = match kind {
+        0 | 1 => self.cpu.add_breakpoint(Breakpoint::Address(address)),
+        _ => {
+          let kind = match kind {
+            2 => WatchKind::Write,
+            3 => WatchKind::Read,
+            _ => WatchKind::Access,
+          };
+          let words = std::cmp::max(length.div_ceil(2), 1);
+          self.cpu.add_watchpoint(Watchpoint::new(address, address.wrapping_add(words - 1), kind))
+        },
+      };
+      self.points.push((kind, address, id));
+    } else {
+      let index = self.points.iter().position(|&(k, a, _)| k == kind && a == address);
+      if let Some(index) = index {
+        let (_, _, id) = self.points.remove(index);
+        match kind {
+          0 | 1 => { self.cpu.remove_breakpoint(id); },
+          _ => { self.cpu.remove_watchpoint(id); },
+        }
+      }
+    }
+    String::from("OK")
+  }
+
+  // Runs until something stops the CPU or the client sends a ^C (None).
+  fn resume(&mut self, input: &mut BufReader<TcpStream>) -> Result<Option<Stop>> {
+    loop {
+      match self.cpu.run(CONTINUE_CYCLES) {
+        Stop::Budget => (),
+        stop => return Ok(Some(stop)),
+      }
+
+      input.get_ref().set_nonblocking(true)?;
+      let mut byte = [0u8; 1];
+      let interrupted = match input.read(&mut byte) {
+        Ok(n) => n == 0 || byte[0] == 0x03,
+        Err(ref error) if error.kind() == ErrorKind::WouldBlock => false,
+        Err(error) => return Err(Error::Io(error)),
+      };
+      input.get_ref().set_nonblocking(false)?;
+      if interrupted {
+        return Ok(None)
+      }
+    }
+  }
+
+  // `None` is a ^C from the client.
+  fn stop_reply(&self, stop: Option<Stop>) -> String {
+    match stop {
+      None => format!("S{:02x}", SIGINT),
+      Some(Stop::Breakpoint { .. }) => format!("T{:02x}swbreak:;", SIGTRAP),
+      Some(Stop::Watchpoint { hit, .. }) => {
+        let kind = self.points.iter()
+          .find(|&&(k, _, id)| k >= 2 && id == hit.id)
+          .map(|&(k, _, _)| k);
+        let name = match kind {
+          Some(3) => "rwatch",
+          Some(4) => "awatch",
+          _ => "watch",
+        };
+        format!("T{:02x}{}:{:x};", SIGTRAP, name, hit.address)
+      },
+      Some(Stop::Error(error)) => {
+        eprintln!("Error:\n\t{}", error);
+        format!("S{:02x}", SIGILL)
+      },
+      Some(_) => format!("S{:02x}", SIGTRAP),
+    }
+  }
+}
@@ -0,0 +1,81 @@
+use std::collections::VecDeque;
+
+
+/// Machine snapshot taken at a known position, for rewinding.
+#[derive(Debug)]
+pub struct Checkpoint {
+  pub half_cycles: u64,
+  pub instructions: u64,
+  pub state: Vec<u8>,
+}
+
+/// Ring of periodic snapshots that reverse execution replays forward from.
+///
+/// A snapshot is also taken right before the first half-cycle after any input from outside the
+/// machine (interrupts, keys, pokes, register writes), so re-running from a checkpoint never has
+/// to reproduce an input: everything between two checkpoints is deterministic.
+#[derive(Debug)]
+pub struct History {
+  interval: u64,
+  capacity: usize,
+  checkpoints: VecDeque<Checkpoint>,
+}
+
+impl History {
+  /// Snapshots every `interval` cycles, keeping the latest `capacity` of them.
+  pub fn new(interval: u64, capacity: usize) -> History {
+    History {
+      interval: std::cmp::max(interval, 1) * 2,
+      capacity: std::cmp::max(capacity, 1),
+      checkpoints: VecDeque::new(),
+    }
+  }
+
+  pub fn due(&self, half_cycles: u64) -> bool {
+    match self.checkpoints.back() {
+      Some(last) => half_cycles >= last.half_cycles + self.interval,
+      None => true,
+    }
+  }
+
+  pub fn record(&mut self, half_cycles: u64, instructions: u64, state: Vec<u8>) {
+    if let Some(last) = self.checkpoints.back() {
+      if last.half_cycles == half_cycles {
+        self.checkpoints.pop_back();
+      }
+    }
+    if self.checkpoints.len() == self.capacity {
+      self.checkpoints.pop_front();
+    }
+    self.checkpoints.push_back(Checkpoint { half_cycles, instructions, state });
+  }
+
+  /// Latest checkpoint at or before half-cycle `target`.
+  pub fn before_half_cycle(&self, target: u64) -> Option<&Checkpoint> {
+    self.checkpoints.iter().rev().find(|c| c.half_cycles <= target)
+  }
+
+  /// Latest checkpoint from which instruction `target` has yet to be fetched.
+  pub fn before_instruction(&self, target: u64) -> Option<&Checkpoint> {
+    self.checkpoints.iter().rev().find(|c| c.instructions < target || c.half_cycles == 0)
+  }
+
+  /// Forgets checkpoints after half-cycle `half_cycles`, which a rewind makes stale.
+  pub fn truncate(&mut self, half_cycles: u64) {
+    while let Some(last) = self.checkpoints.back() {
+      if last.half_cycles <= half_cycles {
+        break;
+      }
+      self.checkpoints.pop_back();
+    }
+  }
+
+  pub fn clear(&mut self) {
+    self.checkpoints.clear();
+  }
+
+  /// Oldest cycle that can still be rewound to.
+  pub fn oldest(&self) -> Option<u64> {
+    self.checkpoints.front().map(|c| c.half_cycles / 2)
+  }
+}
@@ -0,0 +1,262 @@
+use std::fmt;
+use std::convert::TryFrom;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::fs::File;
+use sdl2::keyboard::{
+  Keycode,
+  Mod,
+};
+use crate::error::{
+  Result,
+  Error,
+};
+
+
+/// Input from the host that changes the machine, as injected between half-cycles.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Input {
+  Key(Keycode, Mod),
+  // Already translated key word, as `Keyboard::push` takes it.
+  Char(u16),
+  // Byte from the host end of the UART.
+  Serial(u8),
+}
+
+impl fmt::Display for Input {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Input::Key(key, keymod) => write!(f, "key {} 0x{:04X}", *key as i32, keymod.bits()),
+      Input::Char(key) => write!(f, "char 0x{:04X}", key),
+      Input::Serial(byte) => write!(f, "serial 0x{:02X}", byte),
+    }
+  }
+}
+
+fn hex(s: &str) -> Option<u16> {
+  u16::from_str_radix(s.trim_start_matches("0x"), 16).ok()
+}
+
+fn parse(line: &str) -> Option<(u64, Input)> {
+  let words: Vec<&str> = line.split_whitespace().collect();
+  let (half_cycle, input) = match words.as_slice() {
+    [half_cycle, "key", key, keymod] => {
+      let key = Keycode::from_i32(key.parse::<i32>().ok()?)?;
+      (half_cycle, Input::Key(key, Mod::from_bits_truncate(hex(keymod)?)))
+    },
+    [half_cycle, "char", key] => (half_cycle, Input::Char(hex(key)?)),
+    [half_cycle, "serial", byte] => (half_cycle, Input::Serial(u8::try_from(hex(byte)?).ok()?)),
+    _ => return None,
+  };
+  Some((half_cycle.parse::<u64>().ok()?, input))
+}
+
+
+/// Log of every input with the half-cycle it arrived at, for `Replay`.
+///
+/// One event per line, `<half-cycle> key <SDL keycode> <SDL keymod>`, `<half-cycle> char <key word>`
+/// or `<half-cycle> serial <byte>`.
+/// `#` starts a comment.
+pub struct Recorder {
+  out: Box<dyn Write>,
+}
+
+impl Recorder {
+  pub fn new(mut out: Box<dyn Write>) -> Result<Recorder> {
+    writeln!(out, "# half-cycle key <keycode> <keymod> | half-cycle char <word> | half-cycle serial <byte>")?;
+    Ok(Recorder { out })
+  }
+
+  pub fn create(filename: &str) -> Result<Recorder> {
+    match File::create(filename) {
+      Err(error) => Err(Error::File(String::from(filename), error)),
+      Ok(file) => Recorder::new(Box::new(BufWriter::new(file))),
+    }
+  }
+
+  pub fn record(&mut self, half_cycles: u64, input: Input) -> Result<()> {
+    writeln!(self.out, "{} {}", half_cycles, input)?;
+    Ok(())
+  }
+
+  pub fn flush(&mut self) -> Result<()> {
+    self.out.flush()?;
+    Ok(())
+  }
+}
+
+impl fmt::Debug for Recorder {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "Recorder")
+  }
+}
+
+
+/// Inputs read back from a `Recorder` log, handed out as the machine reaches each one's half-cycle.
+#[derive(Debug)]
+pub struct Replay {
+  events: Vec<(u64, Input)>,
+  next: usize,
+}
+
+impl Replay {
+  pub fn new(text: &str) -> Result<Replay> {
+    let mut events = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+      let line = line.split('#').next().unwrap_or("").trim();
+      if line.is_empty() {
+        continue;
+      }
+      match parse(line) {
+        Some(event) => events.push(event),
+        None => return Err(Error::InvalidRecording(number + 1, String::from(line))),
+      }
+    }
+    Ok(Replay { events, next: 0 })
+  }
+
+  pub fn load(filename: &str) -> Result<Replay> {
+    let mut text = String::new();
+    match File::open(filename).and_then(|mut f| f.read_to_string(&mut text)) {
+      Err(error) => Err(Error::File(String::from(filename), error)),
+      Ok(_) => Replay::new(&text),
+    }
+  }
+
+  /// Next input due by `half_cycles`, if any.
+  pub fn next(&mut self, half_cycles: u64) -> Option<Input> {
+    match self.events.get(self.next) {
+      Some(&(at, input)) if at <= half_cycles => {
+        self.next += 1;
+        Some(input)
+      },
+      _ => None,
+    }
+  }
+
+  /// Moves to the first input recorded at or after `half_cycles`, for a machine restored to then.
+  pub fn seek(&mut self, half_cycles: u64) {
+    self.next = self.events.iter().position(|&(at, _)| at >= half_cycles).unwrap_or(self.events.len());
+  }
+}
+
+
+// Named keys for `\<...>`, with the codes `Keyboard` gives them.
+const KEYS: &[(&str, u16)] = &[
+  ("Return", 0x000A), ("Tab", 0x0009), ("Space", 0x0020), ("Backspace", 0x0008),
+  ("Escape", 0x001B), ("Delete", 0x007F),
+  ("F1", 0x023A), ("F2", 0x023B), ("F3", 0x023C), ("F4", 0x023D), ("F5", 0x023E), ("F6", 0x023F),
+  ("F7", 0x0240), ("F8", 0x0241), ("F9", 0x0242), ("F10", 0x0243), ("F11", 0x0244), ("F12", 0x0245),
+  ("Home", 0x024A), ("PageUp", 0x024B), ("End", 0x024D), ("PageDown", 0x024E),
+  ("Right", 0x024F), ("Left", 0x0250), ("Down", 0x0251), ("Up", 0x0252),
+];
+
+// `S-`, `C-`, `A-` and `G-` prefixes set the Shift, Control, Alt and Gui bits.
+fn named(name: &str) -> Result<u16> {
+  let mut modifiers = 0x0000;
+  let mut rest = name;
+  while rest.len() > 2 && rest.as_bytes()[1] == b'-' {
+    modifiers |= match rest.as_bytes()[0] {
+      b'S' => 0x8000,
+      b'C' => 0x4000,
+      b'A' => 0x2000,
+      b'G' => 0x1000,
+      _ => return Err(Error::InvalidKey(String::from(name))),
+    };
+    rest = &rest[2..];
+  }
+  let mut chars = rest.chars();
+  let key = match (chars.next(), chars.next()) {
+    (Some(c), None) if c.is_ascii() => c as u16,
+    _ => match KEYS.iter().find(|(n, _)| n.eq_ignore_ascii_case(rest)) {
+      Some(&(_, key)) => key,
+      None => return Err(Error::InvalidKey(String::from(name))),
+    },
+  };
+  Ok(key | modifiers)
+}
+
+fn keys(text: &str) -> Result<Vec<u16>> {
+  let mut keys = Vec::new();
+  let mut chars = text.chars();
+  while let Some(c) = chars.next() {
+    let key = match c {
+      '\r' => continue,
+      '\\' => match chars.next() {
+        Some('n') => 0x000A,
+        Some('t') => 0x0009,
+        Some('b') => 0x0008,
+        Some('e') => 0x001B,
+        Some('\\') => '\\' as u16,
+        Some('<') => {
+          let name: String = chars.by_ref().take_while(|&c| c != '>').collect();
+          named(&name)?
+        },
+        Some(c) => return Err(Error::InvalidKey(format!("\\{}", c))),
+        None => return Err(Error::InvalidKey(String::from("\\"))),
+      },
+      c if c.is_ascii() => c as u16,
+      c => return Err(Error::InvalidKey(c.to_string())),
+    };
+    keys.push(key);
+  }
+  Ok(keys)
+}
+
+
+/// Text typed into the keyboard, one key every `interval` cycles from when the machine first polls it.
+///
+/// Backslash escapes: `\n`, `\t`, `\b` (backspace), `\e` (escape), `\\`, and named keys such as
+/// `\<Up>`, `\<F1>`, `\<PageDown>` or `\<C-c>` (prefixes `S-`, `C-`, `A-`, `G-` add modifiers).
+#[derive(Debug)]
+pub struct Script {
+  keys: Vec<u16>,
+  interval: u64,
+  start: Option<u64>,
+  typed: usize,
+}
+
+impl Script {
+  pub fn new(text: &str, interval: u64) -> Result<Script> {
+    let interval = std::cmp::max(interval, 1) * 2;
+    Ok(Script {
+      keys: keys(text)?,
+      interval,
+      start: None,
+      typed: 0,
+    })
+  }
+
+  /// Reads the whole of `filename`, or of stdin for `-`.
+  pub fn load(filename: &str, interval: u64) -> Result<Script> {
+    let mut text = String::new();
+    let read = if filename == "-" {
+      std::io::stdin().read_to_string(&mut text)
+    } else {
+      File::open(filename).and_then(|mut f| f.read_to_string(&mut text))
+    };
+    match read {
+      Err(error) => Err(Error::File(String::from(filename), error)),
+      Ok(_) => Script::new(&text, interval),
+    }
+  }
+
+  /// Next key due by `half_cycles`, if any. Key `n` is due `n + 1` intervals after the first call.
+  pub fn next(&mut self, half_cycles: u64) -> Option<Input> {
+    let start = *self.start.get_or_insert(half_cycles);
+    let key = self.keys.get(self.typed)?;
+    if half_cycles < start + (self.typed as u64 + 1) * self.interval {
+      return None
+    }
+    self.typed += 1;
+    Some(Input::Char(*key))
+  }
+
+  /// Moves to the first key due at or after `half_cycles`, for a machine restored to then.
+  pub fn seek(&mut self, half_cycles: u64) {
+    if let Some(start) = self.start {
+      let due = half_cycles.saturating_sub(start + 1) / self.interval;
+      self.typed = std::cmp::min(due as usize, self.keys.len());
+    }
+  }
+}
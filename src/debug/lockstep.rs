@@ -0,0 +1,137 @@
+use std::fmt;
+use crate::cpu::{
+  Cpu,
+  Engine,
+  Reg,
+};
+use crate::error::{
+  Result,
+  Error,
+};
+
+
+const REGISTERS: [Reg; 14] = [
+  Reg::R0, Reg::R1, Reg::R2, Reg::R3, Reg::R4, Reg::R5, Reg::R6, Reg::R7,
+  Reg::S0, Reg::S1, Reg::LR, Reg::F, Reg::A, Reg::I,
+];
+
+/// First point where the fast engine disagreed with the microcode.
+#[derive(Debug)]
+pub struct Divergence {
+  /// Instruction count and address/opcode of the instruction that was just run.
+  pub instructions: u64,
+  pub pc: u16,
+  pub op: u16,
+  /// What differed, with the microcode's value first.
+  pub what: String,
+  pub microcode: String,
+  pub fast: String,
+}
+
+impl fmt::Display for Divergence {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "Diverged after instruction {} (0x{:04X}: 0x{:04X}): {} is {} on the microcode, {} on the fast engine.",
+      self.instructions, self.pc, self.op, self.what, self.microcode, self.fast)
+  }
+}
+
+fn outcome(result: &Result<()>) -> String {
+  match result {
+    Ok(()) => String::from("no error"),
+    Err(error) => format!("'{}'", error),
+  }
+}
+
+/// Runs the fast engine beside the microcode engine, from the same state, an instruction at a time.
+///
+/// After every instruction the registers, cycle and instruction counts, halt latch and all of RAM
+/// and IO are compared. Inputs are not supported, as the engines only agree between instructions,
+/// and only the microcode machine traces.
+#[derive(Debug)]
+pub struct Lockstep<'a> {
+  microcode: &'a mut Cpu,
+  fast: Cpu,
+}
+
+impl<'a> Lockstep<'a> {
+  /// Runs `microcode` as the reference, against a copy of it on the fast engine.
+  pub fn new(microcode: &'a mut Cpu) -> Result<Lockstep<'a>> {
+    if !microcode.fast_available() {
+      return Err(Error::EngineUnavailable(String::from("fast")))
+    }
+    let mut fast = Cpu::new(microcode.hz(), Vec::new())?;
+    fast.load_state(&microcode.save_state())?;
+    microcode.set_engine(Engine::Microcode)?;
+    fast.set_engine(Engine::Fast)?;
+    Ok(Lockstep { microcode, fast })
+  }
+
+  pub fn cycles(&self) -> u64 {
+    self.microcode.cycles()
+  }
+
+  pub fn halted(&self) -> bool {
+    self.microcode.halted() && self.fast.halted()
+  }
+
+  /// Runs the next instruction on both engines. Errors both engines raise alike are returned as errors.
+  pub fn step(&mut self) -> Result<Option<Divergence>> {
+    let (pc, op) = (self.fast.instruction_pc(), self.fast.register(Reg::I));
+    let fast = self.fast.step_instruction().map(|_| ());
+    // The fast engine runs an interrupt sequence in the same step as the instruction before it.
+    let microcode = loop {
+      let result = self.microcode.step_instruction().map(|_| ());
+      if result.is_err() || self.microcode.halted() || self.microcode.instructions() >= self.fast.instructions() {
+        break result
+      }
+    };
+
+    let divergence = |what: &str, microcode: String, fast: String| Divergence {
+      instructions: self.microcode.instructions(), pc, op,
+      what: String::from(what), microcode, fast,
+    };
+    if outcome(&microcode) != outcome(&fast) {
+      return Ok(Some(divergence("the error", outcome(&microcode), outcome(&fast))))
+    }
+    fast?;
+
+    if self.microcode.instructions() != self.fast.instructions() {
+      let (a, b) = (self.microcode.instructions(), self.fast.instructions());
+      return Ok(Some(divergence("the instruction count", a.to_string(), b.to_string())))
+    }
+    if self.microcode.cycles() != self.fast.cycles() {
+      let (a, b) = (self.microcode.cycles(), self.fast.cycles());
+      return Ok(Some(divergence("the cycle count", a.to_string(), b.to_string())))
+    }
+    if self.microcode.halted() != self.fast.halted() {
+      let (a, b) = (self.microcode.halted(), self.fast.halted());
+      return Ok(Some(divergence("halted", a.to_string(), b.to_string())))
+    }
+    if self.microcode.instruction_pc() != self.fast.instruction_pc() {
+      let (a, b) = (self.microcode.instruction_pc(), self.fast.instruction_pc());
+      return Ok(Some(divergence("PC", format!("0x{:04X}", a), format!("0x{:04X}", b))))
+    }
+    for r in REGISTERS.iter() {
+      let (a, b) = (self.microcode.register(*r), self.fast.register(*r));
+      if a != b {
+        return Ok(Some(divergence(r.name(), format!("0x{:04X}", a), format!("0x{:04X}", b))))
+      }
+    }
+    if let Some(address) = self.microcode.memory().difference(self.fast.memory()) {
+      let (a, b) = (self.microcode.peek(address), self.fast.peek(address));
+      let word = |value: Result<u16>| value.map(|v| format!("0x{:04X}", v)).unwrap_or_else(|e| e.to_string());
+      return Ok(Some(divergence(&format!("memory at 0x{:04X}", address), word(a), word(b))))
+    }
+    Ok(None)
+  }
+
+  /// Steps until cycle `cycles` is reached, a HLT, or the first divergence.
+  pub fn run(&mut self, cycles: u64) -> Result<Option<Divergence>> {
+    while !self.halted() && self.microcode.cycles() < cycles {
+      if let Some(divergence) = self.step()? {
+        return Ok(Some(divergence))
+      }
+    }
+    Ok(None)
+  }
+}
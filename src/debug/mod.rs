@@ -0,0 +1,74 @@
+mod breakpoints;
+mod watchpoints;
+mod monitor;
+mod gdb;
+mod trace;
+mod vcd;
+mod history;
+mod input;
+mod lockstep;
+
+use crate::error::{
+  Result,
+  Error,
+};
+
+pub use breakpoints::{
+  Breakpoint,
+  Breakpoints,
+};
+pub use watchpoints::{
+  WatchKind,
+  Watchpoint,
+  WatchHit,
+  Watchpoints,
+};
+pub use monitor::Monitor;
+pub use gdb::GdbStub;
+pub use trace::Trace;
+pub use vcd::Vcd;
+pub use input::{
+  Input,
+  Recorder,
+  Replay,
+  Script,
+};
+pub use lockstep::{
+  Lockstep,
+  Divergence,
+};
+pub use history::{
+  History,
+  Checkpoint,
+};
+
+
+/// Parses a word given as `0x`/`$`-prefixed hex, `0b` binary or decimal.
+pub fn parse_word(s: &str) -> Result<u16> {
+  let value = if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+    u16::from_str_radix(hex, 16)?
+  } else if let Some(hex) = s.strip_prefix('$') {
+    u16::from_str_radix(hex, 16)?
+  } else if let Some(binary) = s.strip_prefix("0b") {
+    u16::from_str_radix(binary, 2)?
+  } else {
+    s.parse::<u16>()?
+  };
+  Ok(value)
+}
+
+/// Parses an inclusive `<start>-<end>` address range.
+pub fn parse_range(s: &str) -> Result<(u16, u16)> {
+  let mut parts = s.splitn(2, '-');
+  match (parts.next(), parts.next()) {
+    (Some(start), Some(end)) => {
+      let (start, end) = (parse_word(start)?, parse_word(end)?);
+      if start <= end {
+        Ok((start, end))
+      } else {
+        Err(Error::InvalidRange(String::from(s)))
+      }
+    },
+    _ => Err(Error::InvalidRange(String::from(s))),
+  }
+}
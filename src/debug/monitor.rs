@@ -0,0 +1,352 @@
+use std::io::prelude::*;
+use crate::error::{
+  Result,
+  Error,
+};
+use crate::cpu::{
+  Cpu,
+  Reg,
+  Step,
+  Stop,
+};
+use crate::control::disassemble;
+use super::{
+  parse_word,
+  Breakpoint,
+  Watchpoint,
+  WatchKind,
+};
+
+
+// `continue` runs in chunks this size so a cycle limit can be honoured without overshooting much.
+const CONTINUE_CYCLES: u32 = 4096;
+const DEFAULT_MEM_LEN: u16 = 16;
+const DEFAULT_DISASM_LEN: u16 = 8;
+
+const HELP: &str = "\
+Commands:
+  regs                          registers, stack pointers and flags
+  cpu                           full CPU state (control word, buses, ALU)
+  mem <addr> [len]              dump memory
+  poke <addr> <value>...        write memory
+  set <reg> <value>             write a register (A-Z, R0-R7, S0, S1, PC, LR, F)
+  step [n]                      run n instructions (default 1)
+  next                          step over a linking JMl
+  cycle [n] | half [n]          run n microcode steps or half-cycles
+  back [n]                      rewind n instructions (0: restart the current one)
+  backc [n] | backh [n]         rewind n cycles or half-cycles
+  break [<addr> | op <value> [mask] | del <id>]
+  watch [r|w|a <addr> [end] | del <id>]
+  disasm [addr] [n]             disassemble n instructions (default: from PC)
+  continue [cycles]             run until a breakpoint, watchpoint, HLT or the cycle limit
+  save <file> | load <file>     save or restore a machine snapshot
+  quit";
+
+
+fn parse_reg(s: &str) -> Result<Reg> {
+  let r = match s.to_uppercase().as_str() {
+    "A" | "R0" => Reg::R0, "B" | "R1" => Reg::R1, "C" | "R2" => Reg::R2, "D" | "R3" => Reg::R3,
+    "E" | "R4" => Reg::R4, "X" | "R5" => Reg::R5, "Y" | "R6" => Reg::R6, "Z" | "R7" => Reg::R7,
+    "S0" => Reg::S0,
+    "S1" => Reg::S1,
+    "PC" => Reg::PC,
+    "LR" => Reg::LR,
+    "F"  => Reg::F,
+    _ => return Err(Error::InvalidCommand(format!("Unknown register '{}'.", s))),
+  };
+  Ok(r)
+}
+
+fn arg<'a>(args: &[&'a str], index: usize, what: &str) -> Result<&'a str> {
+  match args.get(index) {
+    Some(arg) => Ok(arg),
+    None => Err(Error::InvalidCommand(format!("Missing {}.", what))),
+  }
+}
+
+fn count(args: &[&str], index: usize) -> Result<u64> {
+  match args.get(index) {
+    Some(n) => Ok(n.parse::<u64>()?),
+    None => Ok(1),
+  }
+}
+
+
+/// Line-oriented debugger driving a `Cpu` from a reader (usually stdin).
+pub struct Monitor<'a> {
+  cpu: &'a mut Cpu,
+}
+
+impl<'a> Monitor<'a> {
+  pub fn new(cpu: &'a mut Cpu) -> Monitor<'a> {
+    Monitor {
+      cpu,
+    }
+  }
+
+  /// Reads commands until `quit` or end of input.
+  ///
+  /// Errors, including emulator errors from stepping, are reported and the session goes on
+  /// so the state that caused them can be inspected.
+  pub fn run<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> Result<()> {
+    let prompt = |output: &mut W, cpu: &Cpu| -> Result<()> {
+      write!(output, "0x{:04X}> ", cpu.instruction_pc())?;
+      output.flush()?;
+      Ok(())
+    };
+
+    self.disasm(&mut output, self.cpu.instruction_pc(), 1)?;
+    prompt(&mut output, self.cpu)?;
+    for line in input.lines() {
+      let line = line?;
+      let args = line.split_whitespace().collect::<Vec<&str>>();
+      if let Some(&command) = args.first() {
+        if command == "quit" || command == "q" {
+          break;
+        }
+        match self.command(&mut output, command, &args[1..]) {
+          Err(Error::Io(error)) => return Err(Error::Io(error)),
+          Err(error) => writeln!(output, "{}", error)?,
+          Ok(()) => (),
+        }
+      }
+      prompt(&mut output, self.cpu)?;
+    }
+    writeln!(output)?;
+    Ok(())
+  }
+
+  fn command<W: Write>(&mut self, output: &mut W, command: &str, args: &[&str]) -> Result<()> {
+    match command {
+      "help" | "h" | "?" => writeln!(output, "{}", HELP)?,
+      "regs" | "r" => self.regs(output)?,
+      "cpu" => write!(output, "{}", self.cpu)?,
+      "mem" | "m" => {
+        let address = parse_word(arg(args, 0, "address")?)?;
+        let len = match args.get(1) {
+          Some(len) => parse_word(len)?,
+          None => DEFAULT_MEM_LEN,
+        };
+        self.mem(output, address, len)?;
+      },
+      "poke" => {
+        let mut address = parse_word(arg(args, 0, "address")?)?;
+        arg(args, 1, "value")?;
+        for value in &args[1..] {
+          self.cpu.poke(address, parse_word(value)?)?;
+          address = address.wrapping_add(1);
+        }
+      },
+      "set" => {
+        let r = parse_reg(arg(args, 0, "register")?)?;
+        let value = parse_word(arg(args, 1, "value")?)?;
+        match r {
+          // Through `jump`, so an instruction already fetched from the old PC isn't run.
+          Reg::PC => self.cpu.jump(value)?,
+          r => self.cpu.set_register(r, value),
+        }
+      },
+      "step" | "s" => {
+        for _ in 0..count(args, 0)? {
+          let step = self.cpu.step_instruction()?;
+          if self.report(output, &step)? {
+            break;
+          }
+        }
+        self.disasm(output, self.cpu.instruction_pc(), 1)?;
+      },
+      "next" | "n" => {
+        let step = self.cpu.step_over()?;
+        self.report(output, &step)?;
+        self.disasm(output, self.cpu.instruction_pc(), 1)?;
+      },
+      "cycle" | "half" => {
+        for _ in 0..count(args, 0)? {
+          let step = if command == "cycle" {
+            self.cpu.step_cycle()?
+          } else {
+            self.cpu.step_half_cycle()?
+          };
+          writeln!(output, "{}", step)?;
+          if step.stop.is_some() || step.halted {
+            break;
+          }
+        }
+      },
+      "back" | "backc" | "backh" => {
+        let n = count(args, 0)?;
+        match command {
+          "back" => self.cpu.rewind_instructions(n)?,
+          "backc" => self.cpu.rewind_cycles(n)?,
+          _ => self.cpu.rewind_half_cycles(n)?,
+        }
+        writeln!(output, "Rewound to cycle {}.", self.cpu.cycles())?;
+        self.disasm(output, self.cpu.instruction_pc(), 1)?;
+      },
+      "break" | "b" => self.breakpoint(output, args)?,
+      "watch" | "w" => self.watchpoint(output, args)?,
+      "disasm" | "d" => {
+        let address = match args.first() {
+          Some(address) => parse_word(address)?,
+          None => self.cpu.instruction_pc(),
+        };
+        let n = match args.get(1) {
+          Some(n) => parse_word(n)?,
+          None => DEFAULT_DISASM_LEN,
+        };
+        self.disasm(output, address, n)?;
+      },
+      "continue" | "c" => {
+        let limit = match args.first() {
+          Some(cycles) => Some(cycles.parse::<u64>()?),
+          None => None,
+        };
+        self.resume(output, limit)?;
+      },
+      "save" => self.cpu.save_state_file(arg(args, 0, "file name")?)?,
+      "load" => {
+        self.cpu.load_state_file(arg(args, 0, "file name")?)?;
+        self.disasm(output, self.cpu.instruction_pc(), 1)?;
+      },
+      _ => return Err(Error::InvalidCommand(format!("Unknown command '{}', try 'help'.", command))),
+    }
+    Ok(())
+  }
+
+  fn regs<W: Write>(&self, output: &mut W) -> Result<()> {
+    writeln!(output, "{}", self.cpu.registers())?;
+    writeln!(output, "{}", self.cpu.stack_pointers())?;
+    writeln!(output, " PC  == 0x{:04X}", self.cpu.register(Reg::PC))?;
+    writeln!(output, " LR  == 0x{:04X}", self.cpu.register(Reg::LR))?;
+    writeln!(output, "{}", self.cpu.flags())?;
+    writeln!(output, "{} cycles, I = 0x{:04X} from 0x{:04X}",
+      self.cpu.cycles(), self.cpu.register(Reg::I), self.cpu.instruction_pc())?;
+    Ok(())
+  }
+
+  fn mem<W: Write>(&self, output: &mut W, address: u16, len: u16) -> Result<()> {
+    for row in (0..len).step_by(8) {
+      let start = address.wrapping_add(row);
+      write!(output, "0x{:04X}:", start)?;
+      for offset in row..std::cmp::min(row + 8, len) {
+        write!(output, " {:04X}", self.cpu.peek(address.wrapping_add(offset))?)?;
+      }
+      writeln!(output)?;
+    }
+    Ok(())
+  }
+
+  fn disasm<W: Write>(&self, output: &mut W, mut address: u16, n: u16) -> Result<()> {
+    for _ in 0..n {
+      let op = self.cpu.peek(address)?;
+      let next = self.cpu.peek(address.wrapping_add(1)).ok();
+      let (text, words) = disassemble(address, op, next);
+      let marker = if address == self.cpu.instruction_pc() { ">" } else { " " };
+      if words == 2 {
+        writeln!(output, "{} 0x{:04X}: {:04X} {:04X}  {}", marker, address, op, next.unwrap_or(0), text)?;
+      } else {
+        writeln!(output, "{} 0x{:04X}: {:04X}       {}", marker, address, op, text)?;
+      }
+      address = address.wrapping_add(words);
+    }
+    Ok(())
+  }
+
+  // Prints why a step ended early, returning true if it did.
+  fn report<W: Write>(&self, output: &mut W, step: &Step) -> Result<bool> {
+    if let Some(stop) = &step.stop {
+      writeln!(output, "{}", stop)?;
+      return Ok(true)
+    }
+    if step.halted || self.cpu.halted() {
+      writeln!(output, "{}", Stop::Halted)?;
+      return Ok(true)
+    }
+    Ok(false)
+  }
+
+  fn breakpoint<W: Write>(&mut self, output: &mut W, args: &[&str]) -> Result<()> {
+    match args.first() {
+      None => {
+        for (id, breakpoint) in self.cpu.breakpoints().iter() {
+          writeln!(output, "  {}: {}", id, breakpoint)?;
+        }
+      },
+      Some(&"del") => {
+        let id = arg(args, 1, "breakpoint id")?.parse::<usize>()?;
+        if self.cpu.remove_breakpoint(id).is_none() {
+          return Err(Error::InvalidCommand(format!("No breakpoint {}.", id)))
+        }
+      },
+      Some(&"op") => {
+        let value = parse_word(arg(args, 1, "opcode")?)?;
+        let mask = match args.get(2) {
+          Some(mask) => parse_word(mask)?,
+          None => 0xFFFF,
+        };
+        let breakpoint = Breakpoint::opcode(value, mask);
+        let id = self.cpu.add_breakpoint(breakpoint);
+        writeln!(output, "Breakpoint {}: {}", id, breakpoint)?;
+      },
+      Some(address) => {
+        let breakpoint = Breakpoint::Address(parse_word(address)?);
+        let id = self.cpu.add_breakpoint(breakpoint);
+        writeln!(output, "Breakpoint {}: {}", id, breakpoint)?;
+      },
+    }
+    Ok(())
+  }
+
+  fn watchpoint<W: Write>(&mut self, output: &mut W, args: &[&str]) -> Result<()> {
+    let kind = match args.first() {
+      None => {
+        for (id, watchpoint) in self.cpu.watchpoints().iter() {
+          writeln!(output, "  {}: {}", id, watchpoint)?;
+        }
+        return Ok(())
+      },
+      Some(&"del") => {
+        let id = arg(args, 1, "watchpoint id")?.parse::<usize>()?;
+        if self.cpu.remove_watchpoint(id).is_none() {
+          return Err(Error::InvalidCommand(format!("No watchpoint {}.", id)))
+        }
+        return Ok(())
+      },
+      Some(&"r") => WatchKind::Read,
+      Some(&"w") => WatchKind::Write,
+      Some(&"a") => WatchKind::Access,
+      Some(kind) => return Err(Error::InvalidCommand(format!("Unknown watch kind '{}', expected r, w or a.", kind))),
+    };
+    let start = parse_word(arg(args, 1, "address")?)?;
+    let end = match args.get(2) {
+      Some(end) => parse_word(end)?,
+      None => start,
+    };
+    let watchpoint = Watchpoint::new(start, end, kind);
+    let id = self.cpu.add_watchpoint(watchpoint);
+    writeln!(output, "Watchpoint {}: {}", id, watchpoint)?;
+    Ok(())
+  }
+
+  fn resume<W: Write>(&mut self, output: &mut W, limit: Option<u64>) -> Result<()> {
+    let start = self.cpu.cycles();
+    let stop = loop {
+      let elapsed = self.cpu.cycles() - start;
+      let budget = match limit {
+        Some(limit) if elapsed >= limit => break Stop::Budget,
+        Some(limit) => std::cmp::min(CONTINUE_CYCLES as u64, limit - elapsed) as u32,
+        None => CONTINUE_CYCLES,
+      };
+      match self.cpu.run(budget).result()? {
+        Stop::Budget => (),
+        stop => break stop,
+      }
+    };
+    match stop {
+      Stop::Budget => writeln!(output, "Ran {} cycles.", self.cpu.cycles() - start)?,
+      stop => writeln!(output, "{}", stop)?,
+    }
+    self.disasm(output, self.cpu.instruction_pc(), 1)?;
+    Ok(())
+  }
+}
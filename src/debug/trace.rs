@@ -0,0 +1,84 @@
+use std::fmt;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::fs::File;
+use crate::error::{
+  Result,
+  Error,
+};
+use crate::control::Class;
+
+
+/// One-line-per-instruction execution log.
+///
+/// Each line is the state at the instruction boundary, before the instruction runs:
+///
+/// ```text
+///      cycle   pc   op  name         R0   R1   R2   R3   R4   R5   R6   R7   S0   S1   LR   F
+/// ```
+///
+/// Lines have a fixed layout so two runs can be compared with `diff`.
+pub struct Trace {
+  out: Box<dyn Write>,
+  range: Option<(u16, u16)>,
+  classes: Vec<Class>,
+}
+
+impl Trace {
+  pub fn new(out: Box<dyn Write>) -> Trace {
+    Trace {
+      out,
+      range: None,
+      classes: Vec::new(),
+    }
+  }
+
+  pub fn create(filename: &str) -> Result<Trace> {
+    match File::create(filename) {
+      Err(error) => Err(Error::File(String::from(filename), error)),
+      Ok(file) => Ok(Trace::new(Box::new(BufWriter::new(file)))),
+    }
+  }
+
+  /// Only log instructions fetched from `start..=end`.
+  pub fn set_range(&mut self, start: u16, end: u16) {
+    self.range = Some((start, end));
+  }
+
+  /// Only log instructions of these classes; empty logs every class.
+  pub fn set_classes(&mut self, classes: Vec<Class>) {
+    self.classes = classes;
+  }
+
+  pub fn matches(&self, pc: u16, op: u16) -> bool {
+    let range = match self.range {
+      Some((start, end)) => (start <= pc) && (pc <= end),
+      None => true,
+    };
+    range && (self.classes.is_empty() || self.classes.contains(&Class::of(op)))
+  }
+
+  /// `registers` holds R0-R7, S0, S1, LR and F, in that order.
+  pub fn record(&mut self, cycles: u64, pc: u16, op: u16, name: &str, registers: &[u16; 12]) -> Result<()> {
+    if !self.matches(pc, op) {
+      return Ok(())
+    }
+    write!(self.out, "{:10} {:04X} {:04X} {:12}", cycles, pc, op, name)?;
+    for value in registers.iter() {
+      write!(self.out, " {:04X}", value)?;
+    }
+    writeln!(self.out)?;
+    Ok(())
+  }
+
+  pub fn flush(&mut self) -> Result<()> {
+    self.out.flush()?;
+    Ok(())
+  }
+}
+
+impl fmt::Debug for Trace {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "Trace(range:={:?}, classes:={:?})", self.range, self.classes)
+  }
+}
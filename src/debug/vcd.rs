@@ -0,0 +1,197 @@
+use std::fmt;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::fs::File;
+use crate::error::{
+  Result,
+  Error,
+};
+use crate::control::Control;
+
+
+// Value of one signal in one sample; `None` dumps as undriven (`x`).
+type Signal = (&'static str, &'static str, u32, Option<u64>);
+
+fn bit(value: bool) -> Option<u64> {
+  Some(value as u64)
+}
+
+// Every `Control` field, in declaration order, grouped by the component it drives.
+// Enums are dumped as their discriminant: Address A=0 PC=1 S0=2 S1=3, Register 0-7 (None=8),
+// IMode None=0 SignedByte UnsignedByte Bitmask Interrupt Startup, AluMode Add=0 And Or Xor Shift,
+// Condition Always=0 Zero Sign Carry CarryNotZero Overflow OverflowNotZero.
+fn signals(c: &Control, clk: bool, address: u16, data: Option<u16>, cycle: usize, fetch: usize) -> Vec<Signal> {
+  vec![
+    ("cpu", "clk", 1, bit(clk)),
+    ("cpu", "address_bus", 16, Some(address as u64)),
+    ("cpu", "data_bus", 16, data.map(|data| data as u64)),
+    ("cpu", "control_cycle", 32, Some(cycle as u64)),
+    ("cpu", "control_fetch", 32, Some(fetch as u64)),
+
+    ("control", "address", 2, Some(c.address as u64)),
+    ("control", "link", 1, bit(c.link)),
+    ("control", "stack_sequence", 1, bit(c.stack_sequence)),
+    ("control", "halt", 1, bit(c.halt)),
+
+    ("register", "load", 4, Some(c.register.load as u64)),
+    ("register", "out", 4, Some(c.register.out as u64)),
+
+    ("alu", "mode", 3, Some(c.alu.mode as u64)),
+    ("alu", "t0_load", 1, bit(c.alu.t[0].load)),
+    ("alu", "t1_load", 1, bit(c.alu.t[1].load)),
+    ("alu", "out", 1, bit(c.alu.out)),
+    ("alu", "set_flags", 1, bit(c.alu.set_flags)),
+    ("alu", "t0_zero", 1, bit(c.alu.t0_zero)),
+    ("alu", "t1_invert", 1, bit(c.alu.t1_invert)),
+    ("alu", "carry_invert", 1, bit(c.alu.carry_invert)),
+    ("alu", "direction", 1, bit(c.alu.direction)),
+    ("alu", "extend", 1, bit(c.alu.extend)),
+
+    ("flags", "load", 1, bit(c.flags.load)),
+    ("flags", "out", 1, bit(c.flags.out)),
+
+    ("pc", "load", 1, bit(c.pc.load)),
+    ("pc", "out", 1, bit(c.pc.out)),
+    ("pc", "increment", 1, bit(c.pc.increment)),
+
+    ("lr", "load", 1, bit(c.lr.load)),
+    ("lr", "out", 1, bit(c.lr.out)),
+    ("lr", "increment", 1, bit(c.lr.increment)),
+
+    ("s0", "load", 1, bit(c.s[0].load)),
+    ("s0", "out", 1, bit(c.s[0].out)),
+    ("s0", "count", 1, bit(c.s[0].count)),
+    ("s0", "direction", 1, bit(c.s[0].direction)),
+
+    ("s1", "load", 1, bit(c.s[1].load)),
+    ("s1", "out", 1, bit(c.s[1].out)),
+    ("s1", "count", 1, bit(c.s[1].count)),
+    ("s1", "direction", 1, bit(c.s[1].direction)),
+
+    ("a", "load", 1, bit(c.a.load)),
+
+    ("i", "load", 1, bit(c.i.load)),
+    ("i", "mode", 3, Some(c.i.mode as u64)),
+
+    ("memory", "load", 1, bit(c.memory.load)),
+    ("memory", "out", 1, bit(c.memory.out)),
+
+    ("branch", "negate", 1, bit(c.branch.negate)),
+    ("branch", "condition", 3, Some(c.branch.condition as u64)),
+    ("branch", "interrupt_valid", 1, bit(c.branch.interrupt.is_some())),
+    ("branch", "interrupt", 3, c.branch.interrupt.map(|i| i as u64)),
+  ]
+}
+
+// Short printable identifiers: "!", "\"", ... "~", then two characters.
+fn identifier(mut index: usize) -> String {
+  let mut id = String::new();
+  loop {
+    id.push((b'!' + (index % 94) as u8) as char);
+    index /= 94;
+    if index == 0 {
+      return id
+    }
+    index -= 1;
+  }
+}
+
+fn value(width: u32, value: Option<u64>) -> String {
+  match (width, value) {
+    (1, Some(value)) => format!("{}", value),
+    (1, None) => String::from("x"),
+    (_, Some(value)) => format!("b{:b} ", value),
+    (_, None) => String::from("bx "),
+  }
+}
+
+
+/// Value Change Dump of the control word and buses, one sample per half-cycle.
+///
+/// `clk` is high for the decode half (control word and address bus valid) and low for the
+/// transfer half (data bus valid). Time advances half a period of the nominal clock per sample.
+pub struct Vcd {
+  out: Box<dyn Write>,
+  ns: u64,
+  time: u64,
+  previous: Vec<Option<u64>>,
+}
+
+impl Vcd {
+  pub fn new(out: Box<dyn Write>, hz: f64) -> Vcd {
+    Vcd {
+      out,
+      ns: std::cmp::max((500_000_000.0 / hz).round() as u64, 1),
+      time: 0,
+      previous: Vec::new(),
+    }
+  }
+
+  pub fn create(filename: &str, hz: f64) -> Result<Vcd> {
+    match File::create(filename) {
+      Err(error) => Err(Error::File(String::from(filename), error)),
+      Ok(file) => Ok(Vcd::new(Box::new(BufWriter::new(file)), hz)),
+    }
+  }
+
+  fn header(&mut self, signals: &[Signal]) -> Result<()> {
+    writeln!(self.out, "$version cpu-emulator $end")?;
+    writeln!(self.out, "$timescale 1ns $end")?;
+    writeln!(self.out, "$scope module cpu $end")?;
+    let mut scope = "cpu";
+    for (index, (s, name, width, _)) in signals.iter().enumerate() {
+      if *s != scope {
+        if scope != "cpu" {
+          writeln!(self.out, "$upscope $end")?;
+        }
+        writeln!(self.out, "$scope module {} $end", s)?;
+        scope = *s;
+      }
+      writeln!(self.out, "$var wire {} {} {} $end", width, identifier(index), name)?;
+    }
+    if scope != "cpu" {
+      writeln!(self.out, "$upscope $end")?;
+    }
+    writeln!(self.out, "$upscope $end")?;
+    writeln!(self.out, "$enddefinitions $end")?;
+    Ok(())
+  }
+
+  /// Records one half-cycle. `data` is `None` on the decode half, before anything drives the bus.
+  pub fn sample(&mut self, c: &Control, decode: bool, address: u16, data: Option<u16>, cycle: usize, fetch: usize) -> Result<()> {
+    let signals = signals(c, decode, address, data, cycle, fetch);
+    let first = self.previous.is_empty();
+    if first {
+      self.header(&signals)?;
+      writeln!(self.out, "#0")?;
+      writeln!(self.out, "$dumpvars")?;
+    } else {
+      writeln!(self.out, "#{}", self.time)?;
+    }
+
+    self.previous.resize(signals.len(), None);
+    for (index, (_, _, width, v)) in signals.iter().enumerate() {
+      if first || self.previous[index] != *v {
+        writeln!(self.out, "{}{}", value(*width, *v), identifier(index))?;
+        self.previous[index] = *v;
+      }
+    }
+    if first {
+      writeln!(self.out, "$end")?;
+    }
+    self.time += self.ns;
+    Ok(())
+  }
+
+  pub fn flush(&mut self) -> Result<()> {
+    writeln!(self.out, "#{}", self.time)?;
+    self.out.flush()?;
+    Ok(())
+  }
+}
+
+impl fmt::Debug for Vcd {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "Vcd(time:={}ns)", self.time)
+  }
+}
@@ -0,0 +1,120 @@
+use std::fmt;
+
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WatchKind {
+  Read,
+  Write,
+  Access,
+}
+
+// Address ranges are inclusive, so a single word is `start == end`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Watchpoint {
+  pub start: u16,
+  pub end: u16,
+  pub kind: WatchKind,
+}
+
+impl Watchpoint {
+  pub fn new(start: u16, end: u16, kind: WatchKind) -> Watchpoint {
+    Watchpoint { start, end, kind }
+  }
+
+  fn matches(&self, address: u16, write: bool) -> bool {
+    let kind = match self.kind {
+      WatchKind::Read => !write,
+      WatchKind::Write => write,
+      WatchKind::Access => true,
+    };
+    kind && (self.start <= address) && (address <= self.end)
+  }
+}
+
+impl fmt::Display for Watchpoint {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let kind = match self.kind {
+      WatchKind::Read => "read",
+      WatchKind::Write => "write",
+      WatchKind::Access => "access",
+    };
+    if self.start == self.end {
+      write!(f, "{} 0x{:04X}", kind, self.start)
+    } else {
+      write!(f, "{} 0x{:04X}-0x{:04X}", kind, self.start, self.end)
+    }
+  }
+}
+
+
+// For reads `old` and `new` are both the value read.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct WatchHit {
+  pub id: usize,
+  pub address: u16,
+  pub write: bool,
+  pub old: u16,
+  pub new: u16,
+}
+
+impl fmt::Display for WatchHit {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    if self.write {
+      write!(f, "Watchpoint {}: write 0x{:04X} 0x{:04X} -> 0x{:04X}", self.id, self.address, self.old, self.new)
+    } else {
+      write!(f, "Watchpoint {}: read 0x{:04X} => 0x{:04X}", self.id, self.address, self.new)
+    }
+  }
+}
+
+
+#[derive(Debug)]
+pub struct Watchpoints {
+  next: usize,
+  list: Vec<(usize, Watchpoint)>,
+}
+
+impl Default for Watchpoints {
+  fn default() -> Watchpoints {
+    Watchpoints::new()
+  }
+}
+
+impl Watchpoints {
+  pub fn new() -> Watchpoints {
+    Watchpoints {
+      next: 1,
+      list: Vec::new(),
+    }
+  }
+
+  pub fn add(&mut self, watchpoint: Watchpoint) -> usize {
+    let id = self.next;
+    self.next += 1;
+    self.list.push((id, watchpoint));
+    id
+  }
+
+  pub fn remove(&mut self, id: usize) -> Option<Watchpoint> {
+    let index = self.list.iter().position(|(i, _)| *i == id)?;
+    Some(self.list.remove(index).1)
+  }
+
+  pub fn clear(&mut self) {
+    self.list.clear();
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.list.is_empty()
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = &(usize, Watchpoint)> {
+    self.list.iter()
+  }
+
+  pub fn check(&self, address: u16, write: bool, old: u16, new: u16) -> Option<WatchHit> {
+    self.list.iter()
+      .find(|(_, watchpoint)| watchpoint.matches(address, write))
+      .map(|(id, _)| WatchHit { id: *id, address, write, old, new })
+  }
+}
@@ -22,12 +22,32 @@ pub enum Error {
   InvalidInterrupt(u16),
   Impossible(u16, &'static str),
   ParseFloatError(std::num::ParseFloatError),
+  ParseIntError(std::num::ParseIntError),
+  InvalidPacing(String),
+  InvalidHz(String),
   Sdl2StringError(String),
   Sdl2WindowError(sdl2::video::WindowBuildError),
   Sdl2IntegerError(sdl2::IntegerOrSdlError),
   File(String, io::Error),
   Assembler(String, assembler::Error),
   InvalidROM,
+  IncompleteROM(String),
+  InvalidCommand(String),
+  InvalidControlWord(u64),
+  InvalidSnapshot(String),
+  InvalidClass(String),
+  InvalidRange(String),
+  RewindUnavailable(Option<u64>),
+  InvalidRecording(usize, String),
+  InvalidKey(String),
+  InvalidEngine(String),
+  EngineUnavailable(String),
+  InvalidTime(String),
+  InvalidSerial(String),
+  InvalidOpcode(u16),
+  InvalidImage(String),
+  InvalidDefinition(Option<usize>, String),
+  Io(io::Error),
 }
 
 impl error::Error for Error {}
@@ -63,6 +83,12 @@ impl fmt::Display for Error {
         write!(f, "Impossible(0x{:04X}): {}", op, message),
       Error::ParseFloatError(error) =>
         write!(f, "ParseFloatError: {}", error),
+      Error::ParseIntError(error) =>
+        write!(f, "ParseIntError: {}", error),
+      Error::InvalidPacing(value) =>
+        write!(f, "InvalidPacing({}): Expected 'unthrottled', 'realtime' or a positive multiplier of --hz.", value),
+      Error::InvalidHz(value) =>
+        write!(f, "InvalidHz({}): Expected a positive, finite clock rate in Hz.", value),
       Error::Sdl2StringError(error) =>
         write!(f, "Sdl2: {}", error),
       Error::Sdl2WindowError(error) =>
@@ -75,6 +101,44 @@ impl fmt::Display for Error {
         write!(f, "Assembler({}): {}", path, error),
       Error::InvalidROM =>
         write!(f, "InvalidROM: No ROM file provided."),
+      Error::IncompleteROM(path) =>
+        write!(f, "IncompleteROM({}): Invalid ROM file: Incomplete word.", path),
+      Error::InvalidClass(value) =>
+        write!(f, "InvalidClass({}): Expected one of load, alu, bit, branch, stack, interrupt or control.", value),
+      Error::InvalidRange(value) =>
+        write!(f, "InvalidRange({}): Expected <start>-<end>, e.g. 0xE000-0xE0FF.", value),
+      Error::InvalidControlWord(bits) =>
+        write!(f, "InvalidControlWord(0x{:014X}): Not a valid packed control word.", bits),
+      Error::InvalidSnapshot(message) =>
+        write!(f, "InvalidSnapshot: {}", message),
+      Error::RewindUnavailable(Some(cycle)) =>
+        write!(f, "RewindUnavailable: History only reaches back to cycle {}.", cycle),
+      Error::RewindUnavailable(None) =>
+        write!(f, "RewindUnavailable: No history is being recorded."),
+      Error::InvalidRecording(line, text) =>
+        write!(f, "InvalidRecording(line {}): Expected '<half-cycle> key <keycode> <keymod>', '<half-cycle> char <word>' or '<half-cycle> serial <byte>', found '{}'.", line, text),
+      Error::InvalidKey(key) =>
+        write!(f, "InvalidKey({}): Expected \\n, \\t, \\b, \\e, \\\\ or a key like \\<Up>, \\<F1> or \\<C-c>.", key),
+      Error::InvalidEngine(value) =>
+        write!(f, "InvalidEngine({}): Expected 'microcode' or 'fast'.", value),
+      Error::EngineUnavailable(value) =>
+        write!(f, "EngineUnavailable({}): The fast engine only implements the built-in microcode, not a definition file or EEPROM images.", value),
+      Error::InvalidTime(value) =>
+        write!(f, "InvalidTime({}): Expected 'host', 'fixed:<time>' or 'virtual:<time>', with <time> in seconds since 1970 or as YYYY-MM-DDTHH:MM:SS.", value),
+      Error::InvalidSerial(value) =>
+        write!(f, "InvalidSerial({}): Expected 'stdio', 'pty' or 'unix:<path>'.", value),
+      Error::InvalidOpcode(op) =>
+        write!(f, "InvalidOpcode(0x{:04X}): The control store has no micro-steps for this opcode.", op),
+      Error::InvalidImage(message) =>
+        write!(f, "InvalidImage: {}", message),
+      Error::InvalidDefinition(Some(line), message) =>
+        write!(f, "InvalidDefinition(line {}): {}", line, message),
+      Error::InvalidDefinition(None, message) =>
+        write!(f, "InvalidDefinition: {}", message),
+      Error::InvalidCommand(message) =>
+        write!(f, "{}", message),
+      Error::Io(error) =>
+        write!(f, "Io: {}", error),
     }
   }
 }
@@ -85,6 +149,18 @@ impl From<std::num::ParseFloatError> for Error {
   }
 }
 
+impl From<std::num::ParseIntError> for Error {
+  fn from(error: std::num::ParseIntError) -> Error {
+    Error::ParseIntError(error)
+  }
+}
+
+impl From<io::Error> for Error {
+  fn from(error: io::Error) -> Error {
+    Error::Io(error)
+  }
+}
+
 impl From<sdl2::video::WindowBuildError> for Error {
   fn from(error: sdl2::video::WindowBuildError) -> Error {
     Error::Sdl2WindowError(error)
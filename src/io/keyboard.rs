@@ -10,6 +10,11 @@ use crate::error::{
   Result,
   Error,
 };
+use crate::snapshot::{
+  Snapshot,
+  Writer,
+  Reader,
+};
 
 
 // 0xDE04 Keyboard Control/Data
@@ -22,7 +27,6 @@ use crate::error::{
 //   E    KeyCode is "extended" (Non-ascii Character)
 //   K    KeyCode
 
-
 #[derive(Debug)]
 pub struct Keyboard {
   mode: u16,
@@ -30,6 +34,9 @@ pub struct Keyboard {
 }
 
 impl Keyboard {
+  /// Hardware interrupt line raised when a key is queued.
+  pub const INTERRUPT: u16 = 6;
+
   pub fn new() -> Keyboard {
     Keyboard {
       mode: 0x0000,
@@ -153,6 +160,11 @@ impl Keyboard {
 
     self.keys.borrow().len() > 0
   }
+
+  /// Queues an already translated key: the character plus any SCAG modifier bits.
+  pub fn push(&mut self, key: u16) {
+    self.keys.borrow_mut().push(key | 0x0800);
+  }
 }
 
 impl Addressable for Keyboard {
@@ -188,3 +200,16 @@ impl Addressable for Keyboard {
     Ok(())
   }
 }
+
+impl Snapshot for Keyboard {
+  fn save(&self, w: &mut Writer) {
+    w.u16(self.mode);
+    w.words(&self.keys.borrow());
+  }
+
+  fn restore(&mut self, r: &mut Reader) -> Result<()> {
+    self.mode = r.u16()?;
+    *self.keys.borrow_mut() = r.vec()?;
+    Ok(())
+  }
+}
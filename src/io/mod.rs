@@ -3,15 +3,33 @@ use std::fmt;
 
 mod keyboard;
 mod screen;
+mod pic;
+mod timer;
+mod rtc;
+mod uart;
+mod sd;
 
 use crate::memory::Addressable;
 use crate::error::{
   Result,
   Error,
 };
+use crate::snapshot::{
+  Snapshot,
+  Writer,
+  Reader,
+};
 
 pub use screen::Screen;
 pub use keyboard::Keyboard;
+pub use pic::Pic;
+pub use timer::Timer;
+pub use rtc::{
+  Rtc,
+  TimeSource,
+};
+pub use uart::Uart;
+pub use sd::Sd;
 
 
 // 0xC000 0xCBFF   Text (3 screens) (only uses low byte)
@@ -21,12 +39,20 @@ pub use keyboard::Keyboard;
 
 // 0xDE00 0xDE03   SCREEN
 // 0xDE04          KEYBOARD
-
-// SD Card Stuff?
+// 0xDE05 0xDE08   TIMER
+// 0xDE0C 0xDE0E   INTERRUPT CONTROLLER
+// 0xDE10 0xDE15   REAL TIME CLOCK
+// 0xDE18 0xDE1B   SERIAL
+// 0xDE20 0xDE25   SD CARD
 
 pub struct Io {
   screen: Screen,
   keyboard: Keyboard,
+  pic: Pic,
+  timer: Timer,
+  rtc: Rtc,
+  uart: Uart,
+  sd: Sd,
   io: [u16; 0x0200],
 }
 
@@ -35,6 +61,11 @@ impl Io {
     Io {
       screen: Screen::new(),
       keyboard: Keyboard::new(),
+      pic: Pic::new(),
+      timer: Timer::new(),
+      rtc: Rtc::new(),
+      uart: Uart::new(),
+      sd: Sd::new(),
       io: [0x0000; 0x0200],
     }
   }
@@ -46,6 +77,26 @@ impl Io {
   pub fn keyboard(&mut self) -> Result<&mut Keyboard> {
     Ok(&mut self.keyboard)
   }
+
+  pub fn pic(&mut self) -> Result<&mut Pic> {
+    Ok(&mut self.pic)
+  }
+
+  pub fn timer(&mut self) -> Result<&mut Timer> {
+    Ok(&mut self.timer)
+  }
+
+  pub fn rtc(&mut self) -> Result<&mut Rtc> {
+    Ok(&mut self.rtc)
+  }
+
+  pub fn uart(&mut self) -> Result<&mut Uart> {
+    Ok(&mut self.uart)
+  }
+
+  pub fn sd(&mut self) -> Result<&mut Sd> {
+    Ok(&mut self.sd)
+  }
 }
 
 impl Addressable for Io {
@@ -62,6 +113,16 @@ impl Addressable for Io {
       self.screen.read(address)
     } else if self.keyboard.valid(address) {
       self.keyboard.read(address)
+    } else if self.pic.valid(address) {
+      self.pic.read(address)
+    } else if self.timer.valid(address) {
+      self.timer.read(address)
+    } else if self.rtc.valid(address) {
+      self.rtc.read(address)
+    } else if self.uart.valid(address) {
+      self.uart.read(address)
+    } else if self.sd.valid(address) {
+      self.sd.read(address)
     } else {
       Err(Error::InvalidRead(address, "Could not read from IO RAM."))
     }
@@ -72,6 +133,16 @@ impl Addressable for Io {
       self.screen.peek(address)
     } else if self.keyboard.valid(address) {
       self.keyboard.peek(address)
+    } else if self.pic.valid(address) {
+      self.pic.peek(address)
+    } else if self.timer.valid(address) {
+      self.timer.peek(address)
+    } else if self.rtc.valid(address) {
+      self.rtc.peek(address)
+    } else if self.uart.valid(address) {
+      self.uart.peek(address)
+    } else if self.sd.valid(address) {
+      self.sd.peek(address)
     } else {
       Err(Error::InvalidRead(address, "Could not peek from IO RAM."))
     }
@@ -82,6 +153,16 @@ impl Addressable for Io {
       self.screen.write(address, value)
     } else if self.keyboard.valid(address) {
       self.keyboard.write(address, value)
+    } else if self.pic.valid(address) {
+      self.pic.write(address, value)
+    } else if self.timer.valid(address) {
+      self.timer.write(address, value)
+    } else if self.rtc.valid(address) {
+      self.rtc.write(address, value)
+    } else if self.uart.valid(address) {
+      self.uart.write(address, value)
+    } else if self.sd.valid(address) {
+      self.sd.write(address, value)
     } else {
       Err(Error::InvalidWrite(address, "Could not write to IO RAM."))
     }
@@ -90,7 +171,31 @@ impl Addressable for Io {
 
 impl fmt::Debug for Io {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    write!(f, "Io {{ screen: {:?}, keyboard: {:?}, io: {{:?}} }}",
-      self.screen, self.keyboard, /*&self.io[..]*/)
+    write!(f, "Io {{ screen: {:?}, keyboard: {:?}, pic: {:?}, timer: {:?}, rtc: {:?}, uart: {:?}, sd: {:?}, io: {{:?}} }}",
+      self.screen, self.keyboard, self.pic, self.timer, self.rtc, self.uart, self.sd, /*&self.io[..]*/)
+  }
+}
+
+impl Snapshot for Io {
+  fn save(&self, w: &mut Writer) {
+    self.screen.save(w);
+    self.keyboard.save(w);
+    self.pic.save(w);
+    self.timer.save(w);
+    self.rtc.save(w);
+    self.uart.save(w);
+    self.sd.save(w);
+    w.words(&self.io);
+  }
+
+  fn restore(&mut self, r: &mut Reader) -> Result<()> {
+    self.screen.restore(r)?;
+    self.keyboard.restore(r)?;
+    self.pic.restore(r)?;
+    self.timer.restore(r)?;
+    self.rtc.restore(r)?;
+    self.uart.restore(r)?;
+    self.sd.restore(r)?;
+    r.words(&mut self.io)
   }
 }
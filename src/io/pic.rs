@@ -0,0 +1,121 @@
+use crate::components::Flags;
+use crate::memory::Addressable;
+use crate::error::{
+  Result,
+  Error,
+};
+use crate::snapshot::{
+  Snapshot,
+  Writer,
+  Reader,
+};
+
+
+// 0xDE0C Interrupt Pending
+// ........ PPPPPPPP
+//   P    Line raised and not yet taken. Writing 1s drops those requests.
+// 0xDE0D Interrupt In-Service
+// ........ SSSSSSSS
+//   S    Line taken and not yet acknowledged. Writing 1s acknowledges them (end of interrupt).
+// 0xDE0E Interrupt Control
+// ........ .......N
+//   N    Nested: a line in service holds back itself and every lower priority line until acknowledged.
+//
+// Line 0 has the highest priority. A pending line is only taken while InterruptEnable is set and
+// its mask flag (8 + line) is clear; line 7 shares its mask with InterruptEnable, so it never is.
+
+#[derive(Debug, Default)]
+pub struct Pic {
+  pending: u16,
+  service: u16,
+  mode: u16,
+}
+
+impl Pic {
+  pub fn new() -> Pic {
+    Pic {
+      pending: 0x0000,
+      service: 0x0000,
+      mode: 0x0000,
+    }
+  }
+
+  /// Latches `interrupt` as pending until it is taken, however many times it is raised.
+  pub fn raise(&mut self, interrupt: u16) -> Result<()> {
+    if interrupt > 7 {
+      return Err(Error::InvalidInterrupt(interrupt))
+    }
+    self.pending |= 1 << interrupt;
+    Ok(())
+  }
+
+  /// Highest priority line that would be taken with `flags`, if any.
+  pub fn request(&self, flags: &Flags) -> Option<u16> {
+    let nested = (self.mode & 0x0001) != 0;
+    (0..8)
+      .take_while(|i| !nested || (self.service & (1 << i)) == 0)
+      .find(|i| (self.pending & (1 << i)) != 0 && flags.interruptible(*i))
+  }
+
+  /// Moves `interrupt` from pending to in service, as taking it does.
+  pub fn acknowledge(&mut self, interrupt: u16) {
+    self.pending &= !(1 << interrupt);
+    self.service |= 1 << interrupt;
+  }
+
+  pub fn pending(&self) -> u16 {
+    self.pending
+  }
+
+  pub fn in_service(&self) -> u16 {
+    self.service
+  }
+}
+
+impl Addressable for Pic {
+  fn name(&self) -> &'static str {
+    "Pic"
+  }
+
+  fn valid(&self, address: u16) -> bool {
+    (0xDE0C..=0xDE0E).contains(&address)
+  }
+
+  fn read(&self, address: u16) -> Result<u16> {
+    self.peek(address)
+  }
+
+  fn peek(&self, address: u16) -> Result<u16> {
+    match address {
+      0xDE0C => Ok(self.pending),
+      0xDE0D => Ok(self.service),
+      0xDE0E => Ok(self.mode),
+      _ => Err(Error::InvalidRead(address, "Invalid read from Pic.")),
+    }
+  }
+
+  fn write(&mut self, address: u16, value: u16) -> Result<()> {
+    match address {
+      0xDE0C => self.pending &= !value,
+      0xDE0D => self.service &= !value,
+      0xDE0E => self.mode = value & 0x0001,
+      _ => return Err(Error::InvalidWrite(address, "Invalid write to Pic.")),
+    }
+    Ok(())
+  }
+}
+
+impl Snapshot for Pic {
+  fn save(&self, w: &mut Writer) {
+    w.u16(self.pending);
+    w.u16(self.service);
+    w.u16(self.mode);
+  }
+
+  fn restore(&mut self, r: &mut Reader) -> Result<()> {
+    self.pending = r.u16()?;
+    self.service = r.u16()?;
+    self.mode = r.u16()?;
+    Ok(())
+  }
+}
@@ -0,0 +1,255 @@
+use std::fmt;
+use std::str::FromStr;
+use std::cell::Cell;
+use std::time::{
+  SystemTime,
+  UNIX_EPOCH,
+};
+use crate::memory::Addressable;
+use crate::error::{
+  Result,
+  Error,
+};
+use crate::snapshot::{
+  Snapshot,
+  Writer,
+  Reader,
+};
+
+
+// 0xDE10 RTC Seconds (0-59)
+// 0xDE11 RTC Minutes (0-59)
+// 0xDE12 RTC Hours (0-23)
+// 0xDE13 RTC Day (1-31)
+// 0xDE14 RTC Month (1-12)
+// 0xDE15 RTC Year
+//
+// Reading the seconds or the year latches every field, so the rest read back the same instant.
+// Read one of them first: the other fields return the last latched time, zeros after power on.
+// Writing a field sets the clock, keeping the others as they are now. Values out of range, or a
+// day the month doesn't have, are ignored. The time is UTC.
+
+const SECONDS: usize = 0;
+const DAY: usize = 3;
+const MONTH: usize = 4;
+const YEAR: usize = 5;
+
+/// Where the RTC gets the time from.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TimeSource {
+  /// The host's clock.
+  Host,
+  /// Stopped at the given seconds since 1970.
+  Fixed(i64),
+  /// Starts at the given seconds since 1970, and advances with the emulated clock cycles.
+  Virtual(i64),
+}
+
+impl FromStr for TimeSource {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<TimeSource> {
+    let time = |value: &str| parse_time(value).ok_or_else(|| Error::InvalidTime(String::from(s)));
+    match s.split_once(':') {
+      None if s == "host" => Ok(TimeSource::Host),
+      Some(("fixed", value)) => Ok(TimeSource::Fixed(time(value)?)),
+      Some(("virtual", value)) => Ok(TimeSource::Virtual(time(value)?)),
+      _ => Err(Error::InvalidTime(String::from(s))),
+    }
+  }
+}
+
+impl fmt::Display for TimeSource {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      TimeSource::Host => write!(f, "host"),
+      TimeSource::Fixed(time) => write!(f, "fixed:{}", format_time(*time)),
+      TimeSource::Virtual(time) => write!(f, "virtual:{}", format_time(*time)),
+    }
+  }
+}
+
+// Seconds since 1970, or YYYY-MM-DDTHH:MM:SS.
+fn parse_time(s: &str) -> Option<i64> {
+  if let Ok(seconds) = s.parse::<i64>() {
+    return Some(seconds)
+  }
+  let (date, time) = s.split_once(&['T', ' '][..])?;
+  let date = date.splitn(3, '-').map(|v| v.parse::<u16>().ok()).collect::<Option<Vec<u16>>>()?;
+  let time = time.splitn(3, ':').map(|v| v.parse::<u16>().ok()).collect::<Option<Vec<u16>>>()?;
+  if date.len() != 3 || time.len() != 3 {
+    return None
+  }
+  to_seconds(&[time[2], time[1], time[0], date[2], date[1], date[0]])
+}
+
+fn format_time(seconds: i64) -> String {
+  let t = to_fields(seconds);
+  format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", t[YEAR], t[MONTH], t[DAY], t[2], t[1], t[SECONDS])
+}
+
+// Days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+  let y = if month <= 2 { year - 1 } else { year };
+  let era = y.div_euclid(400);
+  let yoe = y - era * 400;
+  let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+  let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+  era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+  let z = days + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z - era * 146097;
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  (if month <= 2 { yoe + era * 400 + 1 } else { yoe + era * 400 }, month, day)
+}
+
+fn to_fields(seconds: i64) -> [u16; 6] {
+  let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+  let time = seconds.rem_euclid(86400);
+  [(time % 60) as u16, (time / 60 % 60) as u16, (time / 3600) as u16, day as u16, month as u16, year as u16]
+}
+
+fn to_seconds(fields: &[u16; 6]) -> Option<i64> {
+  let limits = [59, 59, 23];
+  if fields[..3].iter().zip(limits.iter()).any(|(value, limit)| value > limit) {
+    return None
+  }
+  let (year, month, day) = (fields[YEAR] as i64, fields[MONTH] as i64, fields[DAY] as i64);
+  let days = days_from_civil(year, month, day);
+  if !(1..=12).contains(&month) || day < 1 || civil_from_days(days) != (year, month, day) {
+    return None
+  }
+  Some(days * 86400 + fields[2] as i64 * 3600 + fields[1] as i64 * 60 + fields[SECONDS] as i64)
+}
+
+/// Real time clock. Host time is the default; fixed or virtual time makes runs reproducible.
+#[derive(Debug)]
+pub struct Rtc {
+  source: TimeSource,
+  offset: i64,
+  elapsed: i64,
+  latch: Cell<[u16; 6]>,
+}
+
+impl Default for Rtc {
+  fn default() -> Rtc {
+    Rtc::new()
+  }
+}
+
+impl Rtc {
+  pub fn new() -> Rtc {
+    Rtc {
+      source: TimeSource::Host,
+      offset: 0,
+      elapsed: 0,
+      latch: Cell::new([0x0000; 6]),
+    }
+  }
+
+  pub fn source(&self) -> TimeSource {
+    self.source
+  }
+
+  /// Switches to `source`, dropping any time set by the program.
+  pub fn set_source(&mut self, source: TimeSource) {
+    self.source = source;
+    self.offset = 0;
+  }
+
+  /// Counts `cycle` cycles at `hz` as the emulated time since power on, for virtual time.
+  pub fn advance(&mut self, cycle: u64, hz: f64) {
+    self.elapsed = (cycle as f64 / hz) as i64;
+  }
+
+  /// Seconds since 1970, as the program sees them.
+  pub fn now(&self) -> i64 {
+    self.offset + match self.source {
+      TimeSource::Host => SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0),
+      TimeSource::Fixed(time) => time,
+      TimeSource::Virtual(time) => time + self.elapsed,
+    }
+  }
+}
+
+impl Addressable for Rtc {
+  fn name(&self) -> &'static str {
+    "Rtc"
+  }
+
+  fn valid(&self, address: u16) -> bool {
+    (0xDE10..=0xDE15).contains(&address)
+  }
+
+  fn read(&self, address: u16) -> Result<u16> {
+    if address == 0xDE10 || address == 0xDE15 {
+      self.latch.set(to_fields(self.now()));
+    }
+    self.peek(address)
+  }
+
+  fn peek(&self, address: u16) -> Result<u16> {
+    match address {
+      0xDE10..=0xDE15 => Ok(self.latch.get()[(address - 0xDE10) as usize]),
+      _ => Err(Error::InvalidRead(address, "Invalid read from Rtc.")),
+    }
+  }
+
+  fn write(&mut self, address: u16, value: u16) -> Result<()> {
+    match address {
+      0xDE10..=0xDE15 => {
+        let now = self.now();
+        let mut fields = to_fields(now);
+        fields[(address - 0xDE10) as usize] = value;
+        if let Some(time) = to_seconds(&fields) {
+          self.offset += time - now;
+          self.latch.set(fields);
+        }
+      },
+      _ => return Err(Error::InvalidWrite(address, "Invalid write to Rtc.")),
+    }
+    Ok(())
+  }
+}
+
+impl Snapshot for Rtc {
+  fn save(&self, w: &mut Writer) {
+    let (kind, time) = match self.source {
+      TimeSource::Host => (0, 0),
+      TimeSource::Fixed(time) => (1, time),
+      TimeSource::Virtual(time) => (2, time),
+    };
+    w.u16(kind);
+    w.u64(time as u64);
+    w.u64(self.offset as u64);
+    w.u64(self.elapsed as u64);
+    w.words(&self.latch.get());
+  }
+
+  fn restore(&mut self, r: &mut Reader) -> Result<()> {
+    let kind = r.u16()?;
+    let time = r.u64()? as i64;
+    self.source = match kind {
+      0 => TimeSource::Host,
+      1 => TimeSource::Fixed(time),
+      2 => TimeSource::Virtual(time),
+      _ => return Err(Error::InvalidSnapshot(format!("Unknown RTC time source {}.", kind))),
+    };
+    self.offset = r.u64()? as i64;
+    self.elapsed = r.u64()? as i64;
+    let mut latch = [0x0000; 6];
+    r.words(&mut latch)?;
+    self.latch.set(latch);
+    Ok(())
+  }
+}
@@ -8,6 +8,11 @@ use crate::error::{
   Result,
   Error,
 };
+use crate::snapshot::{
+  Snapshot,
+  Writer,
+  Reader,
+};
 
 // 0xC000 0xCBFF   Text (3 screens) (only uses low byte)
 // 0xCC00 0xCFFF   Character (256 8x8)
@@ -172,3 +177,20 @@ impl fmt::Debug for Screen {
       self.mode, self.cursor_pos[0], self.cursor_pos[1], self.text_start)
   }
 }
+
+impl Snapshot for Screen {
+  fn save(&self, w: &mut Writer) {
+    w.words(&self.data);
+    w.u16(self.mode);
+    w.words(&self.cursor_pos);
+    w.u16(self.text_start);
+  }
+
+  fn restore(&mut self, r: &mut Reader) -> Result<()> {
+    r.words(&mut self.data)?;
+    self.mode = r.u16()?;
+    r.words(&mut self.cursor_pos)?;
+    self.text_start = r.u16()?;
+    Ok(())
+  }
+}
@@ -0,0 +1,255 @@
+use std::cell::Cell;
+use std::io::{
+  prelude::*,
+  SeekFrom,
+};
+use std::fs::{
+  File,
+  OpenOptions,
+};
+use crate::memory::Addressable;
+use crate::error::{
+  Result,
+  Error,
+};
+use crate::snapshot::{
+  Snapshot,
+  Writer,
+  Reader,
+};
+
+
+// 0xDE20 SD Sector High
+// 0xDE21 SD Sector Low
+// 0xDE22 SD Command
+//   0x0001 READ        Sector into the buffer.
+//   0x0002 WRITE       Buffer to the sector.
+//   0x0003 READ DMA    Sector into RAM at the DMA address.
+//   0x0004 WRITE DMA   RAM at the DMA address to the sector.
+//   Every command moves the data pointer back to the start of the buffer.
+// 0xDE23 SD Status
+// X...CCCC ......BP
+//   P    Card present.
+//   B    Busy: a DMA command waits for the end of the instruction that gave it. Commands given meanwhile fail.
+//   C    Error code: 1 no card, 2 sector out of range, 3 host I/O error, 4 invalid command, 5 DMA outside RAM.
+//   X    Last command failed. Writing 1 clears it, and C.
+// 0xDE24 SD Data
+//   Buffer word at the data pointer, which moves on after every read or write.
+// 0xDE25 SD DMA Address
+//
+// A sector is 256 words, 512 bytes of the image, little-endian. Snapshots keep the registers and
+// buffer but not the image, so rewinding doesn't undo writes to it.
+
+const SECTOR: usize = 0x0100;
+
+const PRESENT: u16 = 0x0001;
+const BUSY: u16 = 0x0002;
+const ERROR: u16 = 0x8000;
+
+const NO_CARD: u16 = 1;
+const OUT_OF_RANGE: u16 = 2;
+const HOST: u16 = 3;
+const COMMAND: u16 = 4;
+const DMA_RANGE: u16 = 5;
+
+#[derive(Debug)]
+pub struct Sd {
+  image: Option<File>,
+  sectors: u64,
+  sector: u32,
+  command: u16,
+  status: u16,
+  address: u16,
+  pointer: Cell<u16>,
+  buffer: [u16; SECTOR],
+}
+
+impl Default for Sd {
+  fn default() -> Sd {
+    Sd::new()
+  }
+}
+
+impl Sd {
+  pub fn new() -> Sd {
+    Sd {
+      image: None,
+      sectors: 0,
+      sector: 0,
+      command: 0x0000,
+      status: 0x0000,
+      address: 0x0000,
+      pointer: Cell::new(0),
+      buffer: [0x0000; SECTOR],
+    }
+  }
+
+  /// Inserts the card image in `filename`, which is read and written in place.
+  pub fn open(&mut self, filename: &str) -> Result<()> {
+    let file = OpenOptions::new().read(true).write(true).open(filename)
+      .and_then(|file| file.metadata().map(|metadata| (file, metadata.len())));
+    match file {
+      Ok((file, len)) => {
+        self.image = Some(file);
+        self.sectors = len / (SECTOR as u64 * 2);
+        Ok(())
+      },
+      Err(error) => Err(Error::File(String::from(filename), error)),
+    }
+  }
+
+  /// Number of whole sectors in the image.
+  pub fn sectors(&self) -> u64 {
+    self.sectors
+  }
+
+  fn status(&self) -> u16 {
+    if self.image.is_some() { self.status | PRESENT } else { self.status }
+  }
+
+  fn fail(&mut self, code: u16) {
+    self.status = (self.status & !0x0F00) | ERROR | (code << 8);
+  }
+
+  // Image file at the start of the current sector, or the error code.
+  fn seek(&mut self) -> std::result::Result<&mut File, u16> {
+    let offset = self.sector as u64 * SECTOR as u64 * 2;
+    match &mut self.image {
+      None => Err(NO_CARD),
+      Some(_) if self.sector as u64 >= self.sectors => Err(OUT_OF_RANGE),
+      Some(file) => file.seek(SeekFrom::Start(offset)).map(|_| file).map_err(|_| HOST),
+    }
+  }
+
+  fn read_sector(&mut self) -> std::result::Result<(), u16> {
+    let mut bytes = [0u8; SECTOR * 2];
+    self.seek()?.read_exact(&mut bytes).map_err(|_| HOST)?;
+    for (word, pair) in self.buffer.iter_mut().zip(bytes.chunks(2)) {
+      *word = u16::from_le_bytes([pair[0], pair[1]]);
+    }
+    Ok(())
+  }
+
+  fn write_sector(&mut self) -> std::result::Result<(), u16> {
+    let bytes: Vec<u8> = self.buffer.iter().flat_map(|word| word.to_le_bytes().to_vec()).collect();
+    self.seek()?.write_all(&bytes).map_err(|_| HOST)
+  }
+
+  fn run(&mut self, command: u16) {
+    if (self.status & BUSY) != 0 {
+      return self.fail(COMMAND)
+    }
+    self.command = command;
+    self.pointer.set(0);
+    self.status &= !(ERROR | 0x0F00);
+    let result = match command {
+      0x0001 | 0x0003 => self.read_sector(),
+      0x0002 => self.write_sector(),
+      0x0004 => self.seek().map(|_| ()),
+      _ => Err(COMMAND),
+    };
+    match result {
+      Err(code) => self.fail(code),
+      Ok(()) if command >= 0x0003 => self.status |= BUSY,
+      Ok(()) => (),
+    }
+  }
+
+  /// Finishes a DMA command between the buffer and `ram`, which starts at address 0.
+  pub fn dma(&mut self, ram: &mut [u16]) {
+    if (self.status & BUSY) == 0 {
+      return
+    }
+    self.status &= !BUSY;
+    let start = self.address as usize;
+    if start + SECTOR > ram.len() {
+      return self.fail(DMA_RANGE)
+    }
+    let ram = &mut ram[start..start + SECTOR];
+    if self.command == 0x0003 {
+      ram.copy_from_slice(&self.buffer);
+    } else {
+      self.buffer.copy_from_slice(ram);
+      if let Err(code) = self.write_sector() {
+        self.fail(code);
+      }
+    }
+  }
+}
+
+impl Addressable for Sd {
+  fn name(&self) -> &'static str {
+    "Sd"
+  }
+
+  fn valid(&self, address: u16) -> bool {
+    (0xDE20..=0xDE25).contains(&address)
+  }
+
+  fn read(&self, address: u16) -> Result<u16> {
+    let value = self.peek(address)?;
+    if address == 0xDE24 {
+      self.pointer.set((self.pointer.get() + 1) % SECTOR as u16);
+    }
+    Ok(value)
+  }
+
+  fn peek(&self, address: u16) -> Result<u16> {
+    match address {
+      0xDE20 => Ok((self.sector >> 16) as u16),
+      0xDE21 => Ok(self.sector as u16),
+      0xDE22 => Ok(self.command),
+      0xDE23 => Ok(self.status()),
+      0xDE24 => Ok(self.buffer[self.pointer.get() as usize]),
+      0xDE25 => Ok(self.address),
+      _ => Err(Error::InvalidRead(address, "Invalid read from Sd.")),
+    }
+  }
+
+  fn write(&mut self, address: u16, value: u16) -> Result<()> {
+    match address {
+      0xDE20 => self.sector = (self.sector & 0x0000FFFF) | ((value as u32) << 16),
+      0xDE21 => self.sector = (self.sector & 0xFFFF0000) | (value as u32),
+      0xDE22 => self.run(value),
+      0xDE23 => if (value & ERROR) != 0 {
+        self.status &= !(ERROR | 0x0F00);
+      },
+      0xDE24 => {
+        let pointer = self.pointer.get();
+        self.buffer[pointer as usize] = value;
+        self.pointer.set((pointer + 1) % SECTOR as u16);
+      },
+      0xDE25 => self.address = value,
+      _ => return Err(Error::InvalidWrite(address, "Invalid write to Sd.")),
+    }
+    Ok(())
+  }
+}
+
+impl Snapshot for Sd {
+  fn save(&self, w: &mut Writer) {
+    w.u64(self.sector as u64);
+    w.u16(self.command);
+    w.u16(self.status);
+    w.u16(self.address);
+    w.u16(self.pointer.get());
+    w.words(&self.buffer);
+  }
+
+  fn restore(&mut self, r: &mut Reader) -> Result<()> {
+    let sector = r.u64()?;
+    if sector > u32::MAX as u64 {
+      return Err(Error::InvalidSnapshot(format!("Sd sector 0x{:X} is out of range.", sector)))
+    }
+    self.sector = sector as u32;
+    self.command = r.u16()?;
+    self.status = r.u16()?;
+    self.address = r.u16()?;
+    let pointer = r.u16()?;
+    if pointer as usize >= SECTOR {
+      return Err(Error::InvalidSnapshot(format!("Sd data pointer 0x{:04X} is past the end of the buffer.", pointer)))
+    }
+    self.pointer.set(pointer);
+    r.words(&mut self.buffer)
+  }
+}
@@ -0,0 +1,153 @@
+use crate::memory::Addressable;
+use crate::error::{
+  Result,
+  Error,
+};
+use crate::snapshot::{
+  Snapshot,
+  Writer,
+  Reader,
+};
+
+
+// 0xDE05 Timer Control/Status
+// X....... .LLL.IPE
+//   E    Counting.
+//   P    Periodic: reload the counter and keep counting when it expires, otherwise stop (clears E).
+//   I    Raise interrupt line L when the counter expires.
+//   L    Interrupt line.
+//   X    Expired since last cleared. Writing 1 clears it.
+// 0xDE06 Timer Reload
+//   Loaded into the counter when written, and when a periodic timer expires.
+// 0xDE07 Timer Counter
+//   Counts down once per tick, and expires on reaching 0.
+// 0xDE08 Timer Prescaler
+//   Clock cycles per tick, minus one.
+//
+// The timer is brought up to date at every instruction boundary, so reads during an instruction
+// see the count as of its start, on either engine.
+
+const ENABLE: u16 = 0x0001;
+const PERIODIC: u16 = 0x0002;
+const INTERRUPT: u16 = 0x0004;
+const EXPIRED: u16 = 0x8000;
+
+#[derive(Debug, Default)]
+pub struct Timer {
+  control: u16,
+  reload: u16,
+  counter: u16,
+  prescaler: u16,
+  prescale: u64,
+  cycle: u64,
+}
+
+impl Timer {
+  pub fn new() -> Timer {
+    Timer {
+      control: 0x0000,
+      reload: 0x0000,
+      counter: 0x0000,
+      prescaler: 0x0000,
+      prescale: 0,
+      cycle: 0,
+    }
+  }
+
+  fn line(&self) -> u16 {
+    (self.control >> 4) & 0x0007
+  }
+
+  /// Counts the cycles since the last call, up to `cycle`. Returns the line to raise if it expired.
+  pub fn advance(&mut self, cycle: u64) -> Option<u16> {
+    let elapsed = cycle.saturating_sub(self.cycle);
+    self.cycle = cycle;
+    if (self.control & ENABLE) == 0 {
+      return None
+    }
+
+    let period = self.prescaler as u64 + 1;
+    let ticks = (self.prescale + elapsed) / period;
+    self.prescale = (self.prescale + elapsed) % period;
+    let mut expired = false;
+    for _ in 0..ticks {
+      self.counter = self.counter.wrapping_sub(1);
+      if self.counter == 0 {
+        self.control |= EXPIRED;
+        expired = true;
+        if (self.control & PERIODIC) != 0 {
+          self.counter = self.reload;
+        } else {
+          self.control &= !ENABLE;
+          break;
+        }
+      }
+    }
+    if expired && (self.control & INTERRUPT) != 0 { Some(self.line()) } else { None }
+  }
+}
+
+impl Addressable for Timer {
+  fn name(&self) -> &'static str {
+    "Timer"
+  }
+
+  fn valid(&self, address: u16) -> bool {
+    (0xDE05..=0xDE08).contains(&address)
+  }
+
+  fn read(&self, address: u16) -> Result<u16> {
+    self.peek(address)
+  }
+
+  fn peek(&self, address: u16) -> Result<u16> {
+    match address {
+      0xDE05 => Ok(self.control),
+      0xDE06 => Ok(self.reload),
+      0xDE07 => Ok(self.counter),
+      0xDE08 => Ok(self.prescaler),
+      _ => Err(Error::InvalidRead(address, "Invalid read from Timer.")),
+    }
+  }
+
+  fn write(&mut self, address: u16, value: u16) -> Result<()> {
+    match address {
+      0xDE05 => {
+        if (self.control & ENABLE) == 0 && (value & ENABLE) != 0 {
+          self.prescale = 0;
+        }
+        let expired = self.control & EXPIRED & !value;
+        self.control = (value & 0x0077) | expired;
+      },
+      0xDE06 => {
+        self.reload = value;
+        self.counter = value;
+      },
+      0xDE07 => self.counter = value,
+      0xDE08 => self.prescaler = value,
+      _ => return Err(Error::InvalidWrite(address, "Invalid write to Timer.")),
+    }
+    Ok(())
+  }
+}
+
+impl Snapshot for Timer {
+  fn save(&self, w: &mut Writer) {
+    w.u16(self.control);
+    w.u16(self.reload);
+    w.u16(self.counter);
+    w.u16(self.prescaler);
+    w.u64(self.prescale);
+    w.u64(self.cycle);
+  }
+
+  fn restore(&mut self, r: &mut Reader) -> Result<()> {
+    self.control = r.u16()?;
+    self.reload = r.u16()?;
+    self.counter = r.u16()?;
+    self.prescaler = r.u16()?;
+    self.prescale = r.u64()?;
+    self.cycle = r.u64()?;
+    Ok(())
+  }
+}
@@ -0,0 +1,186 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use crate::memory::Addressable;
+use crate::error::{
+  Result,
+  Error,
+};
+use crate::snapshot::{
+  Snapshot,
+  Writer,
+  Reader,
+};
+
+
+// 0xDE18 UART Data
+// ........ DDDDDDDD
+//   Reading takes the oldest received byte (0 when there is none), writing queues a byte to send.
+//   Bytes written while the transmit FIFO is full are dropped.
+// 0xDE19 UART Status
+// ........ .....FTR
+//   R    Received data waiting.
+//   T    Transmit FIFO empty.
+//   F    Transmit FIFO full.
+// 0xDE1A UART Interrupt Enable
+// ........ .LLL..TR
+//   R    Raise interrupt line L when received data starts waiting.
+//   T    Raise interrupt line L when the transmit FIFO runs empty.
+//   L    Interrupt line.
+// 0xDE1B UART Divisor
+//   Clock cycles per byte sent and received, minus one.
+//
+// Interrupts are raised when either condition becomes true, including by enabling it, so a handler
+// should empty the receive FIFO before returning. Bytes from the host wait while the receive FIFO is full.
+
+const FIFO: usize = 16;
+
+const RECEIVED: u16 = 0x0001;
+const EMPTY: u16 = 0x0002;
+
+#[derive(Debug, Default)]
+pub struct Uart {
+  enable: u16,
+  divisor: u16,
+  rx: RefCell<VecDeque<u8>>,
+  tx: VecDeque<u8>,
+  input: VecDeque<u8>,
+  output: Vec<u8>,
+  // Enabled conditions that were true at the last `advance`, each edge-detected on its own.
+  active: u16,
+  prescale: u64,
+  cycle: u64,
+}
+
+impl Uart {
+  pub fn new() -> Uart {
+    Uart {
+      enable: 0x0000,
+      divisor: 0x0000,
+      rx: RefCell::new(VecDeque::new()),
+      tx: VecDeque::new(),
+      input: VecDeque::new(),
+      output: Vec::new(),
+      active: 0x0000,
+      prescale: 0,
+      cycle: 0,
+    }
+  }
+
+  fn status(&self) -> u16 {
+    (!self.rx.borrow().is_empty() as u16) |
+      (self.tx.is_empty() as u16) << 1 |
+      ((self.tx.len() >= FIFO) as u16) << 2
+  }
+
+  /// Queues `byte` from the host, to arrive once the receive FIFO has room.
+  pub fn receive(&mut self, byte: u8) {
+    self.input.push_back(byte);
+  }
+
+  /// Takes the bytes sent since the last call.
+  pub fn transmitted(&mut self) -> Vec<u8> {
+    std::mem::take(&mut self.output)
+  }
+
+  /// Moves bytes in and out up to `cycle`. Returns the line to raise if an enabled condition became true.
+  pub fn advance(&mut self, cycle: u64) -> Option<u16> {
+    let elapsed = cycle.saturating_sub(self.cycle);
+    self.cycle = cycle;
+
+    let period = self.divisor as u64 + 1;
+    let ticks = (self.prescale + elapsed) / period;
+    self.prescale = (self.prescale + elapsed) % period;
+    let rx = self.rx.get_mut();
+    for _ in 0..ticks {
+      if self.tx.is_empty() && (self.input.is_empty() || rx.len() >= FIFO) {
+        break;
+      }
+      if let Some(byte) = self.tx.pop_front() {
+        self.output.push(byte);
+      }
+      if rx.len() < FIFO {
+        if let Some(byte) = self.input.pop_front() {
+          rx.push_back(byte);
+        }
+      }
+    }
+
+    let active = self.enable & self.status() & (RECEIVED | EMPTY);
+    let rising = active & !self.active;
+    self.active = active;
+    if rising != 0 { Some((self.enable >> 4) & 0x0007) } else { None }
+  }
+}
+
+impl Addressable for Uart {
+  fn name(&self) -> &'static str {
+    "Uart"
+  }
+
+  fn valid(&self, address: u16) -> bool {
+    (0xDE18..=0xDE1B).contains(&address)
+  }
+
+  fn read(&self, address: u16) -> Result<u16> {
+    match address {
+      0xDE18 => Ok(self.rx.borrow_mut().pop_front().unwrap_or(0x00) as u16),
+      _ => self.peek(address),
+    }
+  }
+
+  fn peek(&self, address: u16) -> Result<u16> {
+    match address {
+      0xDE18 => Ok(self.rx.borrow().front().copied().unwrap_or(0x00) as u16),
+      0xDE19 => Ok(self.status()),
+      0xDE1A => Ok(self.enable),
+      0xDE1B => Ok(self.divisor),
+      _ => Err(Error::InvalidRead(address, "Invalid read from Uart.")),
+    }
+  }
+
+  fn write(&mut self, address: u16, value: u16) -> Result<()> {
+    match address {
+      0xDE18 => if self.tx.len() < FIFO {
+        self.tx.push_back(value as u8);
+      },
+      0xDE19 => (),
+      0xDE1A => self.enable = value & 0x0073,
+      0xDE1B => self.divisor = value,
+      _ => return Err(Error::InvalidWrite(address, "Invalid write to Uart.")),
+    }
+    Ok(())
+  }
+}
+
+fn words(bytes: &VecDeque<u8>) -> Vec<u16> {
+  bytes.iter().map(|byte| *byte as u16).collect()
+}
+
+fn bytes(words: Vec<u16>) -> VecDeque<u8> {
+  words.into_iter().map(|word| word as u8).collect()
+}
+
+impl Snapshot for Uart {
+  fn save(&self, w: &mut Writer) {
+    w.u16(self.enable);
+    w.u16(self.divisor);
+    w.words(&words(&self.rx.borrow()));
+    w.words(&words(&self.tx));
+    w.words(&words(&self.input));
+    w.u16(self.active);
+    w.u64(self.prescale);
+    w.u64(self.cycle);
+  }
+
+  fn restore(&mut self, r: &mut Reader) -> Result<()> {
+    self.enable = r.u16()?;
+    self.divisor = r.u16()?;
+    self.rx = RefCell::new(bytes(r.vec()?));
+    self.tx = bytes(r.vec()?);
+    self.input = bytes(r.vec()?);
+    self.active = r.u16()?;
+    self.prescale = r.u64()?;
+    self.cycle = r.u64()?;
+    Ok(())
+  }
+}
@@ -0,0 +1,97 @@
+//! Microcode-accurate emulator for the cpu-emulator 16-bit CPU.
+//!
+//! A machine is built from a ROM image with [`Cpu::new`], which maps the
+//! words at `0xE000` and boots through the reset vector at `0xFFFF`:
+//!
+//! ```no_run
+//! use cpu::{Cpu, Pacing, Reg};
+//!
+//! let rom = cpu::assemble("assets/screen-test.a")?;
+//! let mut cpu = Cpu::new(48.0, rom)?;
+//! cpu.set_pacing(Pacing::Unthrottled);
+//! while !cpu.halted() {
+//!   cpu.step_instruction()?;
+//! }
+//! println!("X = 0x{:04X}", cpu.register(Reg::R5));
+//! # Ok::<(), cpu::Error>(())
+//! ```
+//!
+//! [`Engine::Fast`] runs whole instructions at a time instead, with the same results;
+//! [`debug::Lockstep`] checks it against the microcode.
+
+extern crate assembler;
+extern crate sdl2;
+#[cfg(unix)]
+extern crate libc;
+
+#[macro_use]
+pub mod error;
+pub mod components;
+pub mod memory;
+pub mod io;
+pub mod control;
+pub mod clock;
+pub mod debug;
+pub mod snapshot;
+#[cfg(unix)]
+pub mod serial;
+mod cpu;
+
+use std::io::prelude::*;
+use std::fs::File;
+
+pub use crate::error::{
+  Result,
+  Error,
+};
+pub use crate::cpu::{
+  Cpu,
+  Engine,
+  Reg,
+  Phase,
+  Step,
+  Stop,
+};
+pub use crate::clock::{
+  Clock,
+  Pacing,
+};
+pub use crate::debug::{
+  Breakpoint,
+  Watchpoint,
+  WatchKind,
+  History,
+};
+pub use crate::memory::Memory;
+pub use crate::control::{
+  ControlLogic,
+  Class,
+};
+
+
+/// Reads a little-endian binary ROM image, as written by the `assembler` binary.
+pub fn load_rom(filename: &str) -> Result<Vec<u16>> {
+  let mut file = Vec::new();
+  if let Err(error) = File::open(filename).and_then(|mut f| f.read_to_end(&mut file)) {
+    return Err(Error::File(String::from(filename), error))
+  }
+
+  let words = file.chunks(2)
+    .map(|chunk| {
+      match chunk {
+        &[l, h] => Ok(u16::from_le_bytes([l, h])),
+        _ => Err(Error::IncompleteROM(String::from(filename))),
+      }
+    })
+    .collect::<Result<Vec<u16>>>()?;
+
+  Ok(words)
+}
+
+/// Assembles a source file (and its `@` includes) into a ROM image.
+pub fn assemble(filename: &str) -> Result<Vec<u16>> {
+  match assembler::from_file(filename) {
+    Err(error) => Err(Error::Assembler(String::from(filename), error)),
+    Ok(rom) => Ok(rom),
+  }
+}
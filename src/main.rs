@@ -1,18 +1,13 @@
 
-extern crate assembler;
+#[macro_use]
+extern crate cpu;
 extern crate sdl2;
 extern crate clap;
 
-#[macro_use]
-mod error;
-mod components;
-mod memory;
-mod io;
-mod control;
-mod cpu;
-
-use std::io::prelude::*;
-use std::fs::File;
+use std::time::{
+  Duration,
+  Instant,
+};
 use clap::{
   App,
   Arg,
@@ -25,38 +20,81 @@ use sdl2::{
   rect::Rect,
 };
 
-use crate::error::{
+#[cfg(unix)]
+use cpu::serial::{
+  Bridge,
+  Serial,
+};
+use cpu::{
   Result,
   Error,
+  Cpu,
+  ControlLogic,
+  Engine,
+  Pacing,
+  Class,
+  control::Definition,
+  io::TimeSource,
+  debug::{
+    self,
+    Lockstep,
+    Monitor,
+    GdbStub,
+    History,
+    Recorder,
+    Replay,
+    Script,
+    Trace,
+    Vcd,
+  },
 };
-use crate::cpu::Cpu;
 
 
 const FPS: f64 = 12.0;
 const DEFAULT_HZ: &'static str = "48.0";
+const DEFAULT_PACING: &'static str = "realtime";
+const DEFAULT_ENGINE: &'static str = "microcode";
+const UNTHROTTLED_CYCLES: u32 = 4096;
 
 const WIDTH:  u32 = 240;
 const HEIGHT: u32 = 128;
 const BORDER: i32 = 1;
 const SCALE:  f32 = 4.0;
 
-const KB_INT: u16 = 6;
+// Debuggers keep a rewind snapshot every HISTORY_CYCLES cycles, HISTORY_LEN of them (~128 KiB each).
+const HISTORY_CYCLES: u64 = 10_000;
+const HISTORY_LEN: usize = 128;
 
+const EXIT_HALTED:  i32 = 0;
+const EXIT_CYCLES:  i32 = 2;
+const EXIT_TIMEOUT: i32 = 3;
+const EXIT_DIVERGED: i32 = 4;
 
-fn load_rom(filename: &str) -> std::io::Result<Vec<u16>> {
-  let mut file = Vec::new();
-  File::open(filename)?.read_to_end(&mut file)?;
 
-  let words = file.chunks(2)
-    .map(|chunk| {
-      match chunk {
-        &[l, h] => u16::from_le_bytes([l, h]),
-        _ => panic!("Invalid ROM file: Incomplete word."),
-      }
-    })
-    .collect::<Vec<u16>>();
+fn cycles_per_frame(cpu: &Cpu) -> u32 {
+  match cpu.rate() {
+    Some(rate) => std::cmp::max((rate / FPS) as u32, 1),
+    None => UNTHROTTLED_CYCLES,
+  }
+}
+
+fn run_frame(cpu: &mut Cpu) -> Result<()> {
+  if cpu.rate().is_some() {
+    cpu.run(cycles_per_frame(cpu)).result()?;
+    return Ok(())
+  }
 
-  Ok(words)
+  // Unthrottled frames run for a frame's worth of wall-clock time instead of a fixed number of cycles.
+  let frame = Duration::from_secs_f64(1.0 / FPS);
+  let start = Instant::now();
+  while start.elapsed() < frame {
+    if cpu.halted() {
+      std::thread::sleep(frame.saturating_sub(start.elapsed()));
+      break;
+    }
+    cpu.run(UNTHROTTLED_CYCLES).result()?;
+  }
+  Ok(())
 }
 
 fn run(cpu: &mut Cpu) -> Result<()> {
@@ -77,9 +115,8 @@ fn run(cpu: &mut Cpu) -> Result<()> {
   sdl_e!(canvas.set_scale(SCALE, SCALE))?;
   canvas.set_viewport(Rect::new(BORDER, BORDER, WIDTH, HEIGHT));
 
-  let cycles_per_frame = std::cmp::max((cpu.hz() / FPS) as u32, 1);
   'running: loop {
-    cpu.run(cycles_per_frame)?;
+    run_frame(cpu)?;
 
     for event in event_pump.poll_iter() {
       match event {
@@ -87,9 +124,7 @@ fn run(cpu: &mut Cpu) -> Result<()> {
         Event::KeyDown { keycode: Some(Keycode::ScrollLock), .. } => break 'running,
         Event::KeyDown { keycode: Some(Keycode::Pause), .. } => cpu.pause(),
         Event::KeyDown { keycode: Some(key), keymod, .. } => {
-          if cpu.keyboard()?.pressed(key, keymod) {
-            cpu.interrupt(KB_INT)?;
-          }
+          cpu.press(key, keymod)?;
         },
         _ => (),
       }
@@ -106,7 +141,92 @@ fn run(cpu: &mut Cpu) -> Result<()> {
   Ok(())
 }
 
-fn init() -> Result<()> {
+fn run_headless(cpu: &mut Cpu, max_cycles: Option<u64>, timeout: Option<Duration>) -> Result<i32> {
+  let start = Instant::now();
+  let cycles_per_frame = cycles_per_frame(cpu) as u64;
+  let code = loop {
+    let cycles = cpu.cycles();
+    if cpu.halted() {
+      println!("Halted after {} cycles.", cycles);
+      break EXIT_HALTED;
+    }
+    let budget = match max_cycles {
+      Some(max) if cycles >= max => {
+        println!("Cycle limit of {} reached.", max);
+        break EXIT_CYCLES;
+      },
+      Some(max) => std::cmp::min(cycles_per_frame, max - cycles),
+      None => cycles_per_frame,
+    };
+    if let Some(timeout) = timeout {
+      if start.elapsed() >= timeout {
+        println!("Timed out after {} cycles ({:?}).", cycles, timeout);
+        break EXIT_TIMEOUT;
+      }
+    }
+
+    cpu.run(budget as u32).result()?;
+  };
+
+  println!("\n\nFinal CPU State:\n{}", cpu);
+  Ok(code)
+}
+
+// Like `run_headless`, but with the fast engine checked against the microcode after every instruction.
+fn run_lockstep(cpu: &mut Cpu, max_cycles: Option<u64>, timeout: Option<Duration>) -> Result<i32> {
+  let start = Instant::now();
+  let mut lockstep = Lockstep::new(cpu)?;
+  let code = loop {
+    let cycles = lockstep.cycles();
+    if lockstep.halted() {
+      println!("Halted after {} cycles, no divergence.", cycles);
+      break EXIT_HALTED;
+    }
+    let limit = match max_cycles {
+      Some(max) if cycles >= max => {
+        println!("Cycle limit of {} reached, no divergence.", max);
+        break EXIT_CYCLES;
+      },
+      Some(max) => std::cmp::min(cycles + UNTHROTTLED_CYCLES as u64, max),
+      None => cycles + UNTHROTTLED_CYCLES as u64,
+    };
+    if let Some(timeout) = timeout {
+      if start.elapsed() >= timeout {
+        println!("Timed out after {} cycles ({:?}), no divergence.", cycles, timeout);
+        break EXIT_TIMEOUT;
+      }
+    }
+
+    if let Some(divergence) = lockstep.run(limit)? {
+      println!("{}", divergence);
+      break EXIT_DIVERGED;
+    }
+  };
+
+  println!("\n\nFinal CPU State:\n{}", cpu);
+  Ok(code)
+}
+
+#[cfg(unix)]
+fn open_serial(cpu: &mut Cpu, bridge: &str, debug: bool) -> Result<()> {
+  let bridge = bridge.parse::<Bridge>()?;
+  if bridge == Bridge::Stdio && debug {
+    return Err(Error::InvalidCommand(String::from("--debug reads commands from stdin, so needs --serial pty or unix:<path>.")))
+  }
+  let serial = Serial::open(&bridge)?;
+  if bridge != Bridge::Stdio {
+    println!("Serial port on {}", serial.name());
+  }
+  cpu.set_serial(serial);
+  Ok(())
+}
+
+#[cfg(not(unix))]
+fn open_serial(_: &mut Cpu, _: &str, _: bool) -> Result<()> {
+  Err(Error::InvalidCommand(String::from("--serial is only supported on Unix hosts.")))
+}
+
+fn init() -> Result<i32> {
   let args = App::new("cpu-emulator")
     .arg(Arg::with_name("asm")
       .long("asm")
@@ -123,44 +243,213 @@ fn init() -> Result<()> {
       .long("hz")
       .short("c")
       .takes_value(true))
+    .arg(Arg::with_name("pacing")
+      .long("pacing")
+      .short("p")
+      .takes_value(true))
+    .arg(Arg::with_name("headless")
+      .long("headless"))
+    .arg(Arg::with_name("engine")
+      .long("engine")
+      .takes_value(true))
+    .arg(Arg::with_name("rtc")
+      .long("rtc")
+      .takes_value(true))
+    .arg(Arg::with_name("serial")
+      .long("serial")
+      .takes_value(true)
+      .conflicts_with("lockstep"))
+    .arg(Arg::with_name("sd")
+      .long("sd")
+      .takes_value(true)
+      .conflicts_with("lockstep"))
+    .arg(Arg::with_name("eeprom")
+      .long("eeprom")
+      .takes_value(true))
+    .arg(Arg::with_name("microcode")
+      .long("microcode")
+      .takes_value(true)
+      .conflicts_with("eeprom"))
+    .arg(Arg::with_name("lockstep")
+      .long("lockstep")
+      .requires("headless")
+      .conflicts_with_all(&["engine", "microcode", "eeprom", "record", "replay", "type"]))
+    .arg(Arg::with_name("debug")
+      .long("debug")
+      .short("d")
+      .conflicts_with("headless"))
+    .arg(Arg::with_name("trace")
+      .long("trace")
+      .takes_value(true))
+    .arg(Arg::with_name("trace-range")
+      .long("trace-range")
+      .takes_value(true)
+      .requires("trace"))
+    .arg(Arg::with_name("trace-class")
+      .long("trace-class")
+      .takes_value(true)
+      .requires("trace"))
+    .arg(Arg::with_name("vcd")
+      .long("vcd")
+      .takes_value(true))
+    .arg(Arg::with_name("record")
+      .long("record")
+      .takes_value(true))
+    .arg(Arg::with_name("replay")
+      .long("replay")
+      .takes_value(true))
+    .arg(Arg::with_name("type")
+      .long("type")
+      .takes_value(true))
+    .arg(Arg::with_name("type-rate")
+      .long("type-rate")
+      .takes_value(true)
+      .requires("type"))
+    .arg(Arg::with_name("load-state")
+      .long("load-state")
+      .takes_value(true))
+    .arg(Arg::with_name("save-state")
+      .long("save-state")
+      .takes_value(true))
+    .arg(Arg::with_name("gdb")
+      .long("gdb")
+      .takes_value(true)
+      .conflicts_with_all(&["headless", "debug"]))
+    .arg(Arg::with_name("cycles")
+      .long("cycles")
+      .takes_value(true)
+      .requires("headless"))
+    .arg(Arg::with_name("timeout")
+      .long("timeout")
+      .takes_value(true)
+      .requires("headless"))
     .get_matches();
 
-  let hz = args.value_of("hz").unwrap_or(DEFAULT_HZ).parse::<f64>()?;
-  let rom = if args.is_present("asm") {
-    match args.value_of("asm") {
-      None => return Err(Error::InvalidROM),
-      Some(filename) => {
-        match assembler::from_file(filename) {
-          Err(error) => return Err(Error::Assembler(String::from(filename), error)),
-          Ok(rom) => rom,
-        }
-      },
+  let hz = args.value_of("hz").unwrap_or(DEFAULT_HZ);
+  let hz = match hz.parse::<f64>()? {
+    hz if hz.is_finite() && hz > 0.0 => hz,
+    _ => return Err(Error::InvalidHz(String::from(hz))),
+  };
+  let rom = if let Some(filename) = args.value_of("asm") {
+    cpu::assemble(filename)?
+  } else if let Some(filename) = args.value_of("rom") {
+    cpu::load_rom(filename)?
+  } else {
+    return Err(Error::InvalidROM)
+  };
+
+  let pacing = args.value_of("pacing").unwrap_or(DEFAULT_PACING).parse::<Pacing>()?;
+  let engine = args.value_of("engine").unwrap_or(DEFAULT_ENGINE).parse::<Engine>()?;
+  if engine == Engine::Fast && args.is_present("vcd") {
+    return Err(Error::InvalidCommand(String::from("--vcd samples every half-cycle, so needs --engine microcode.")))
+  }
+  if engine == Engine::Fast && (args.is_present("microcode") || args.is_present("eeprom")) {
+    return Err(Error::InvalidCommand(String::from("--engine fast only implements the built-in microcode, so needs --engine microcode.")))
+  }
+  let mut cpu = if let Some(prefix) = args.value_of("eeprom") {
+    Cpu::with_control(hz, rom, ControlLogic::from_images(&cpu::control::read_images(prefix)?)?)
+  } else if let Some(filename) = args.value_of("microcode") {
+    Cpu::with_control(hz, rom, ControlLogic::from_definition(Definition::load(filename)?)?)
+  } else {
+    Cpu::new(hz, rom)?
+  };
+  cpu.set_pacing(pacing);
+  cpu.set_engine(engine)?;
+  if let Some(filename) = args.value_of("load-state") {
+    cpu.load_state_file(filename)?;
+  }
+  // Given after --load-state, as the snapshot carries the RTC's own time source.
+  if let Some(source) = args.value_of("rtc") {
+    cpu.set_time_source(source.parse::<TimeSource>()?)?;
+  }
+  if let Some(filename) = args.value_of("trace") {
+    let mut trace = Trace::create(filename)?;
+    if let Some(range) = args.value_of("trace-range") {
+      let (start, end) = debug::parse_range(range)?;
+      trace.set_range(start, end);
+    }
+    if let Some(classes) = args.value_of("trace-class") {
+      trace.set_classes(classes.split(',').map(|class| class.parse::<Class>()).collect::<Result<Vec<Class>>>()?);
+    }
+    cpu.set_trace(trace);
+  }
+  if let Some(filename) = args.value_of("vcd") {
+    cpu.set_vcd(Vcd::create(filename, hz)?);
+  }
+  if let Some(filename) = args.value_of("record") {
+    cpu.set_recorder(Recorder::create(filename)?);
+  }
+  if let Some(filename) = args.value_of("replay") {
+    cpu.set_replay(Replay::load(filename)?);
+  }
+  if let Some(filename) = args.value_of("type") {
+    if filename == "-" && (args.is_present("debug") || args.value_of("serial") == Some("stdio")) {
+      return Err(Error::InvalidCommand(String::from("--type - reads stdin, as do --debug and --serial stdio, so needs a file.")))
     }
-  } else if args.is_present("rom") {
-    match args.value_of("rom") {
-      None => return Err(Error::InvalidROM),
-      Some(filename) => {
-        match load_rom(filename) {
-          Err(error) => return Err(Error::File(String::from(filename), error)),
-          Ok(rom) => rom,
-        }
+    let interval = match args.value_of("type-rate") {
+      Some(cycles) => cycles.parse::<u64>()?,
+      None => cycles_per_frame(&cpu) as u64,
+    };
+    cpu.set_script(Script::load(filename, interval)?);
+  }
+  if let Some(filename) = args.value_of("sd") {
+    cpu.sd()?.open(filename)?;
+  }
+  if let Some(bridge) = args.value_of("serial") {
+    open_serial(&mut cpu, bridge, args.is_present("debug"))?;
+  }
+  if args.is_present("gdb") || args.is_present("debug") {
+    cpu.set_history(History::new(HISTORY_CYCLES, HISTORY_LEN));
+  }
+  let result = if args.is_present("headless") {
+    let cycles = match args.value_of("cycles") {
+      None => None,
+      Some(cycles) => Some(cycles.parse::<u64>()?),
+    };
+    let timeout = match args.value_of("timeout") {
+      None => None,
+      Some(seconds) => match Duration::try_from_secs_f64(seconds.parse::<f64>()?) {
+        Ok(timeout) => Some(timeout),
+        Err(_) => return Err(Error::InvalidCommand(format!("--timeout {}: Expected a number of seconds from 0 to 2^64.", seconds))),
       },
+    };
+    if args.is_present("lockstep") {
+      run_lockstep(&mut cpu, cycles, timeout)
+    } else {
+      run_headless(&mut cpu, cycles, timeout)
     }
+  } else if let Some(port) = args.value_of("gdb") {
+    GdbStub::new(&mut cpu).serve(port.parse::<u16>()?).map(|_| EXIT_HALTED)
+  } else if args.is_present("debug") {
+    let stdin = std::io::stdin();
+    Monitor::new(&mut cpu).run(stdin.lock(), std::io::stdout()).map(|_| EXIT_HALTED)
   } else {
-    return Err(Error::InvalidROM)
+    run(&mut cpu).map(|_| EXIT_HALTED)
   };
-
-  let mut cpu = Cpu::new(hz, rom)?;
-  let result = run(&mut cpu);
   if let Err(_) = result {
     println!("\n\nLast CPU State:\n{}", cpu);
   }
+  if let Some(filename) = args.value_of("save-state") {
+    cpu.save_state_file(filename)?;
+  }
+  if let Some(mut recorder) = cpu.take_recorder() {
+    recorder.flush()?;
+  }
+  if let Some(mut trace) = cpu.take_trace() {
+    trace.flush()?;
+  }
+  if let Some(mut vcd) = cpu.take_vcd() {
+    vcd.flush()?;
+  }
   result
 }
 
 fn main() {
-  if let Err(error) = init() {
-    eprintln!("Error:\n\t{}", error);
-    std::process::exit(1)
+  match init() {
+    Err(error) => {
+      eprintln!("Error:\n\t{}", error);
+      std::process::exit(1)
+    },
+    Ok(code) => std::process::exit(code),
   }
 }
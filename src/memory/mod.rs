@@ -1,5 +1,6 @@
 
 use std::fmt;
+use std::cell::Cell;
 
 use crate::control::Control;
 use crate::components::BusComponent;
@@ -7,11 +8,25 @@ use crate::io::{
   Io,
   Screen,
   Keyboard,
+  Pic,
+  Timer,
+  Rtc,
+  Uart,
+  Sd,
+};
+use crate::debug::{
+  Watchpoints,
+  WatchHit,
 };
 use crate::error::{
   Result,
   Error,
 };
+use crate::snapshot::{
+  Snapshot,
+  Writer,
+  Reader,
+};
 
 mod ram;
 mod rom;
@@ -64,6 +79,8 @@ pub struct Memory {
   ram: Ram,
   rom: Rom,
   io: Io,
+  watchpoints: Watchpoints,
+  hit: Cell<Option<WatchHit>>,
 }
 
 impl Memory {
@@ -74,6 +91,8 @@ impl Memory {
       ram: Ram::new(),
       rom: Rom::new(rom),
       io: Io::new(),
+      watchpoints: Watchpoints::new(),
+      hit: Cell::new(None),
     }
   }
 
@@ -89,6 +108,87 @@ impl Memory {
     self.io.keyboard()
   }
 
+  pub fn pic(&mut self) -> Result<&mut Pic> {
+    self.io.pic()
+  }
+
+  pub fn timer(&mut self) -> Result<&mut Timer> {
+    self.io.timer()
+  }
+
+  pub fn rtc(&mut self) -> Result<&mut Rtc> {
+    self.io.rtc()
+  }
+
+  pub fn uart(&mut self) -> Result<&mut Uart> {
+    self.io.uart()
+  }
+
+  pub fn sd(&mut self) -> Result<&mut Sd> {
+    self.io.sd()
+  }
+
+  /// Finishes the SD card's pending DMA command, if any. Bypasses watchpoints.
+  pub fn dma(&mut self) -> Result<()> {
+    self.io.sd()?.dma(self.ram.words_mut());
+    Ok(())
+  }
+
+  pub fn watchpoints(&self) -> &Watchpoints {
+    &self.watchpoints
+  }
+
+  pub fn watchpoints_mut(&mut self) -> &mut Watchpoints {
+    &mut self.watchpoints
+  }
+
+  // The first watchpoint hit since the last call, if any.
+  pub fn take_hit(&self) -> Option<WatchHit> {
+    self.hit.take()
+  }
+
+  fn watch(&self, address: u16, write: bool, old: u16, new: u16) {
+    if let Some(hit) = self.watchpoints.check(address, write, old, new) {
+      if self.hit.get().is_none() {
+        self.hit.set(Some(hit));
+      }
+    }
+  }
+
+  pub fn peek(&self, address: u16) -> Result<u16> {
+    self.component(address)?.peek(address)
+  }
+
+  pub fn poke(&mut self, address: u16, value: u16) -> Result<()> {
+    self.component_mut(address)?.write(address, value)
+  }
+
+  /// Reads `address` as the bus would, with side effects and watchpoints.
+  pub fn read(&self, address: u16) -> Result<u16> {
+    let value = self.component(address)?.read(address)?;
+    if !self.watchpoints.is_empty() {
+      self.watch(address, false, value, value);
+    }
+    Ok(value)
+  }
+
+  /// Writes `address` as the bus would, with watchpoints.
+  pub fn write(&mut self, address: u16, value: u16) -> Result<()> {
+    if !self.watchpoints.is_empty() {
+      let old = self.component(address)?.peek(address).unwrap_or(0x0000);
+      self.watch(address, true, old, value);
+    }
+    self.component_mut(address)?.write(address, value)
+  }
+
+  /// Lowest RAM or IO address that reads differently in `other`. ROM is assumed to match.
+  pub fn difference(&self, other: &Memory) -> Option<u16> {
+    if let Some(address) = self.ram.difference(&other.ram) {
+      return Some(address)
+    }
+    (0xC000..0xE000).find(|&address| self.peek(address).ok() != other.peek(address).ok())
+  }
+
   fn component(&self, address: u16) -> Result<&dyn Addressable> {
     if self.ram.valid(address) {
       Ok(&self.ram)
@@ -125,8 +225,7 @@ impl BusComponent for Memory {
 
   fn load(&mut self, value: u16) -> Result<()> {
     if self.control.memory.load {
-      let address = self.address;
-      self.component_mut(address)?.write(address, value)
+      self.write(self.address, value)
     } else {
       Ok(())
     }
@@ -134,8 +233,7 @@ impl BusComponent for Memory {
 
   fn data(&self) -> Result<Option<u16>> {
     if self.control.memory.out {
-      let address = self.address;
-      Ok(Some(self.component(address)?.read(address)?))
+      Ok(Some(self.read(self.address)?))
     } else {
       Ok(None)
     }
@@ -159,3 +257,24 @@ impl fmt::Display for Memory {
     Ok(())
   }
 }
+
+// Watchpoints are debugger configuration, not machine state, so they are left as they are.
+impl Snapshot for Memory {
+  fn save(&self, w: &mut Writer) {
+    w.control(&self.control);
+    w.u16(self.address);
+    self.ram.save(w);
+    self.rom.save(w);
+    self.io.save(w);
+  }
+
+  fn restore(&mut self, r: &mut Reader) -> Result<()> {
+    self.control = r.control()?;
+    self.address = r.u16()?;
+    self.ram.restore(r)?;
+    self.rom.restore(r)?;
+    self.io.restore(r)?;
+    self.hit.set(None);
+    Ok(())
+  }
+}
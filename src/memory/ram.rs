@@ -2,6 +2,11 @@
 use std::fmt;
 use super::Addressable;
 use crate::error::Result;
+use crate::snapshot::{
+  Snapshot,
+  Writer,
+  Reader,
+};
 
 
 const RAM_SIZE: usize   = 0xC000;
@@ -15,6 +20,19 @@ impl Ram {
   pub fn new() -> Ram {
     Ram { data: [0x0000; RAM_SIZE] }
   }
+
+  // Every word, from address 0, for DMA.
+  pub fn words_mut(&mut self) -> &mut [u16] {
+    &mut self.data[..]
+  }
+
+  // Lowest address holding a different word in `other`.
+  pub fn difference(&self, other: &Ram) -> Option<u16> {
+    if self.data[..] == other.data[..] {
+      return None
+    }
+    self.data.iter().zip(other.data.iter()).position(|(a, b)| a != b).map(|i| (i + RAM_OFFSET) as u16)
+  }
 }
 
 impl Addressable for Ram {
@@ -41,3 +59,13 @@ impl fmt::Debug for Ram {
     fmt::Debug::fmt(&self.data[..], f)
   }
 }
+
+impl Snapshot for Ram {
+  fn save(&self, w: &mut Writer) {
+    w.words(&self.data);
+  }
+
+  fn restore(&mut self, r: &mut Reader) -> Result<()> {
+    r.words(&mut self.data)
+  }
+}
@@ -5,6 +5,11 @@ use crate::error::{
   Result,
   Error,
 };
+use crate::snapshot::{
+  Snapshot,
+  Writer,
+  Reader,
+};
 
 
 const ROM_SIZE: usize   = 0x2000;
@@ -47,3 +52,13 @@ impl fmt::Debug for Rom {
     fmt::Debug::fmt(&self.data[..], f)
   }
 }
+
+impl Snapshot for Rom {
+  fn save(&self, w: &mut Writer) {
+    w.words(&self.data);
+  }
+
+  fn restore(&mut self, r: &mut Reader) -> Result<()> {
+    r.words(&mut self.data)
+  }
+}
@@ -0,0 +1,216 @@
+use std::fmt;
+use std::str::FromStr;
+use std::thread;
+use std::ffi::CStr;
+use std::fs::{
+  File,
+  OpenOptions,
+};
+use std::mem::MaybeUninit;
+use std::io::{
+  self,
+  prelude::*,
+};
+use std::os::unix::io::{
+  AsRawFd,
+  FromRawFd,
+};
+use std::os::unix::net::{
+  UnixListener,
+  UnixStream,
+};
+use std::sync::{
+  Arc,
+  Mutex,
+};
+use std::sync::mpsc::{
+  self,
+  Receiver,
+  Sender,
+};
+use crate::error::{
+  Result,
+  Error,
+};
+
+
+/// Host end of the UART.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Bridge {
+  /// This process's stdin and stdout.
+  Stdio,
+  /// A new pseudo-terminal, for `screen`, `minicom` and the like.
+  Pty,
+  /// A Unix socket at the given path, taking one connection at a time.
+  Socket(String),
+}
+
+impl FromStr for Bridge {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Bridge> {
+    match s {
+      "stdio" => Ok(Bridge::Stdio),
+      "pty" => Ok(Bridge::Pty),
+      _ if s.starts_with("unix:") && s.len() > 5 => Ok(Bridge::Socket(String::from(&s[5..]))),
+      _ => Err(Error::InvalidSerial(String::from(s))),
+    }
+  }
+}
+
+/// Bytes to and from the host. Reads happen on a background thread, so the machine never waits for input.
+pub struct Serial {
+  name: String,
+  rx: Receiver<u8>,
+  out: Box<dyn Write>,
+  // Keeps a PTY open while no client has it, so reading the master end doesn't fail.
+  _slave: Option<File>,
+}
+
+impl Serial {
+  pub fn open(bridge: &Bridge) -> Result<Serial> {
+    match bridge {
+      Bridge::Stdio => Ok(Serial::stdio()),
+      Bridge::Pty => Serial::pty(),
+      Bridge::Socket(path) => Serial::socket(path),
+    }
+  }
+
+  fn new(name: String, reader: impl Read + Send + 'static, out: Box<dyn Write>) -> Serial {
+    let (received, rx) = mpsc::channel();
+    thread::spawn(move || read_into(reader, &received));
+    Serial { name, rx, out, _slave: None }
+  }
+
+  pub fn stdio() -> Serial {
+    Serial::new(String::from("stdio"), io::stdin(), Box::new(io::stdout()))
+  }
+
+  pub fn pty() -> Result<Serial> {
+    let fd = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY) };
+    if fd < 0 {
+      return Err(Error::Io(io::Error::last_os_error()))
+    }
+    let master = unsafe { File::from_raw_fd(fd) };
+    let name = unsafe {
+      if libc::grantpt(fd) != 0 || libc::unlockpt(fd) != 0 {
+        return Err(Error::Io(io::Error::last_os_error()))
+      }
+      let name = libc::ptsname(fd);
+      if name.is_null() {
+        return Err(Error::Io(io::Error::last_os_error()))
+      }
+      CStr::from_ptr(name).to_string_lossy().into_owned()
+    };
+    // Raw, so the line discipline neither echoes the machine's output back to it nor rewrites bytes.
+    let slave = OpenOptions::new().read(true).write(true).open(&name)?;
+    unsafe {
+      let mut termios = MaybeUninit::<libc::termios>::uninit();
+      if libc::tcgetattr(slave.as_raw_fd(), termios.as_mut_ptr()) != 0 {
+        return Err(Error::Io(io::Error::last_os_error()))
+      }
+      let mut termios = termios.assume_init();
+      libc::cfmakeraw(&mut termios);
+      if libc::tcsetattr(slave.as_raw_fd(), libc::TCSANOW, &termios) != 0 {
+        return Err(Error::Io(io::Error::last_os_error()))
+      }
+    }
+    let (tx, sent) = mpsc::channel::<Vec<u8>>();
+    let mut writer = master.try_clone()?;
+    thread::spawn(move || {
+      for bytes in sent {
+        if writer.write_all(&bytes).is_err() {
+          break;
+        }
+      }
+    });
+    Ok(Serial { _slave: Some(slave), ..Serial::new(name, master, Box::new(Background(tx))) })
+  }
+
+  pub fn socket(path: &str) -> Result<Serial> {
+    let listener = UnixListener::bind(path)?;
+    let (received, rx) = mpsc::channel();
+    let (tx, sent) = mpsc::channel::<Vec<u8>>();
+    let client: Arc<Mutex<Option<UnixStream>>> = Arc::new(Mutex::new(None));
+
+    let connected = Arc::clone(&client);
+    thread::spawn(move || {
+      for stream in listener.incoming() {
+        let stream = match stream {
+          Ok(stream) => stream,
+          Err(_) => continue,
+        };
+        if let Ok(clone) = stream.try_clone() {
+          *connected.lock().unwrap() = Some(clone);
+        }
+        if !read_into(stream, &received) {
+          break;
+        }
+        *connected.lock().unwrap() = None;
+      }
+    });
+    // Output with nobody connected is dropped.
+    thread::spawn(move || {
+      for bytes in sent {
+        let mut client = client.lock().unwrap();
+        if let Some(stream) = client.as_mut() {
+          if stream.write_all(&bytes).is_err() {
+            *client = None;
+          }
+        }
+      }
+    });
+    Ok(Serial { name: String::from(path), rx, out: Box::new(Background(tx)), _slave: None })
+  }
+
+  /// Where the host end is, e.g. the PTY's device path.
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  /// Next byte from the host, if one has arrived.
+  pub fn receive(&mut self) -> Option<u8> {
+    self.rx.try_recv().ok()
+  }
+
+  pub fn send(&mut self, bytes: &[u8]) -> Result<()> {
+    if !bytes.is_empty() {
+      self.out.write_all(bytes)?;
+      self.out.flush()?;
+    }
+    Ok(())
+  }
+}
+
+// Hands writes to a background thread, so a host end nobody is reading can't stall the machine.
+struct Background(Sender<Vec<u8>>);
+
+impl Write for Background {
+  fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+    let _ = self.0.send(bytes.to_vec());
+    Ok(bytes.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+impl fmt::Debug for Serial {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "Serial {{ name: {:?} }}", self.name)
+  }
+}
+
+// Reads until end of file or an error. Returns false once nobody is receiving any more.
+fn read_into(mut reader: impl Read, received: &Sender<u8>) -> bool {
+  let mut buffer = [0u8; 256];
+  loop {
+    match reader.read(&mut buffer) {
+      Ok(0) | Err(_) => return true,
+      Ok(n) => if buffer[..n].iter().any(|byte| received.send(*byte).is_err()) {
+        return false
+      },
+    }
+  }
+}
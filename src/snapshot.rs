@@ -0,0 +1,169 @@
+use std::io::prelude::*;
+use std::fs::File;
+use crate::error::{
+  Result,
+  Error,
+};
+use crate::control::Control;
+
+
+// File layout: MAGIC, VERSION (u16), then every `Snapshot` section in the order `Cpu::save_state`
+// writes them. All integers are little-endian. Bump VERSION whenever the layout changes.
+const MAGIC: &[u8; 8] = b"CPUSNAP\0";
+pub const VERSION: u16 = 7;
+
+
+/// State that can be written to and read back from a machine snapshot.
+pub trait Snapshot {
+  fn save(&self, w: &mut Writer);
+  fn restore(&mut self, r: &mut Reader) -> Result<()>;
+}
+
+
+pub struct Writer {
+  bytes: Vec<u8>,
+}
+
+impl Default for Writer {
+  fn default() -> Writer {
+    Writer::new()
+  }
+}
+
+impl Writer {
+  pub fn new() -> Writer {
+    let mut w = Writer { bytes: Vec::new() };
+    w.bytes.extend_from_slice(MAGIC);
+    w.u16(VERSION);
+    w
+  }
+
+  pub fn bool(&mut self, value: bool) {
+    self.bytes.push(value as u8);
+  }
+
+  pub fn u16(&mut self, value: u16) {
+    self.bytes.extend_from_slice(&value.to_le_bytes());
+  }
+
+  pub fn u64(&mut self, value: u64) {
+    self.bytes.extend_from_slice(&value.to_le_bytes());
+  }
+
+  pub fn option(&mut self, value: Option<u16>) {
+    self.bool(value.is_some());
+    self.u16(value.unwrap_or(0x0000));
+  }
+
+  pub fn words(&mut self, words: &[u16]) {
+    self.u64(words.len() as u64);
+    for word in words {
+      self.u16(*word);
+    }
+  }
+
+  pub fn control(&mut self, c: &Control) {
+    self.u64(c.bits());
+  }
+
+  pub fn into_bytes(self) -> Vec<u8> {
+    self.bytes
+  }
+
+  pub fn write_file(self, filename: &str) -> Result<()> {
+    match File::create(filename).and_then(|mut f| f.write_all(&self.bytes)) {
+      Err(error) => Err(Error::File(String::from(filename), error)),
+      Ok(()) => Ok(()),
+    }
+  }
+}
+
+
+pub struct Reader<'a> {
+  bytes: &'a [u8],
+  position: usize,
+}
+
+impl<'a> Reader<'a> {
+  pub fn new(bytes: &'a [u8]) -> Result<Reader<'a>> {
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+      return Err(Error::InvalidSnapshot(String::from("Not a snapshot file.")))
+    }
+    let mut r = Reader { bytes, position: MAGIC.len() };
+    let version = r.u16()?;
+    if version != VERSION {
+      return Err(Error::InvalidSnapshot(format!("Snapshot version {} is not supported (expected {}).", version, VERSION)))
+    }
+    Ok(r)
+  }
+
+  fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+    if self.position + n > self.bytes.len() {
+      return Err(Error::InvalidSnapshot(String::from("Snapshot is truncated.")))
+    }
+    let slice = &self.bytes[self.position..self.position + n];
+    self.position += n;
+    Ok(slice)
+  }
+
+  pub fn bool(&mut self) -> Result<bool> {
+    match self.take(1)?[0] {
+      0 => Ok(false),
+      1 => Ok(true),
+      value => Err(Error::InvalidSnapshot(format!("Invalid boolean {} at offset {}.", value, self.position - 1))),
+    }
+  }
+
+  pub fn u16(&mut self) -> Result<u16> {
+    let bytes = self.take(2)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+  }
+
+  pub fn u64(&mut self) -> Result<u64> {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(self.take(8)?);
+    Ok(u64::from_le_bytes(bytes))
+  }
+
+  pub fn option(&mut self) -> Result<Option<u16>> {
+    let some = self.bool()?;
+    let value = self.u16()?;
+    Ok(if some { Some(value) } else { None })
+  }
+
+  // Reads a word array, which must be exactly `words.len()` long.
+  pub fn words(&mut self, words: &mut [u16]) -> Result<()> {
+    let len = self.u64()? as usize;
+    if len != words.len() {
+      return Err(Error::InvalidSnapshot(format!("Expected {} words, found {}.", words.len(), len)))
+    }
+    for word in words.iter_mut() {
+      *word = self.u16()?;
+    }
+    Ok(())
+  }
+
+  pub fn vec(&mut self) -> Result<Vec<u16>> {
+    let len = self.u64()? as usize;
+    (0..len).map(|_| self.u16()).collect()
+  }
+
+  pub fn control(&mut self) -> Result<Control> {
+    Control::from_bits(self.u64()?)
+  }
+
+  pub fn finish(self) -> Result<()> {
+    if self.position != self.bytes.len() {
+      return Err(Error::InvalidSnapshot(format!("{} unexpected bytes at the end.", self.bytes.len() - self.position)))
+    }
+    Ok(())
+  }
+}
+
+pub fn read_file(filename: &str) -> Result<Vec<u8>> {
+  let mut bytes = Vec::new();
+  match File::open(filename).and_then(|mut f| f.read_to_end(&mut bytes)) {
+    Err(error) => Err(Error::File(String::from(filename), error)),
+    Ok(_) => Ok(bytes),
+  }
+}
@@ -0,0 +1,54 @@
+extern crate cpu;
+
+use cpu::{
+  Cpu,
+  ControlLogic,
+  Engine,
+  Error,
+};
+use cpu::control::Definition;
+
+
+// The built-in definition, padded with HLT steps up to `last` and with NOP moved to run that one.
+fn padded(last: usize) -> Definition {
+  let text = Definition::built_in().to_string();
+  let (microcode, instructions) = text.split_at(text.find("[instructions]").unwrap());
+  let steps = microcode.lines().filter(|line| line.starts_with(|c: char| c.is_ascii_digit())).count();
+  let padding: String = (steps..=last).map(|i| format!("{} halt\n", i)).collect();
+  let instructions = instructions.replace("NOP: 44", &format!("NOP: {}", last));
+  Definition::parse(&format!("{}{}{}", microcode, padding, instructions)).unwrap()
+}
+
+#[test]
+fn images_round_trip_the_highest_index() {
+  let logic = ControlLogic::from_definition(padded(0x3F)).unwrap();
+  let images = logic.images().unwrap();
+  let read = ControlLogic::from_images(&images).unwrap();
+  assert_eq!(read.images().unwrap(), images);
+}
+
+#[test]
+fn images_reject_an_index_above_63() {
+  let logic = ControlLogic::from_definition(padded(0x40)).unwrap();
+  match logic.images() {
+    Err(Error::InvalidImage(message)) => assert!(message.contains("NOP uses microcode step 64"), "{}", message),
+    Err(error) => panic!("expected InvalidImage, got {}", error),
+    Ok(_) => panic!("expected InvalidImage, got images"),
+  }
+}
+
+#[test]
+fn fast_engine_needs_the_built_in_control_store() {
+  let images = ControlLogic::new().unwrap().images().unwrap();
+  let mut cpu = Cpu::with_control(48.0, Vec::new(), ControlLogic::from_images(&images).unwrap());
+  assert!(!cpu.fast_available());
+  match cpu.set_engine(Engine::Fast) {
+    Err(Error::EngineUnavailable(_)) => (),
+    result => panic!("expected EngineUnavailable, got {:?}", result),
+  }
+  assert_eq!(cpu.engine(), Engine::Microcode);
+
+  let mut cpu = Cpu::new(48.0, Vec::new()).unwrap();
+  cpu.set_engine(Engine::Fast).unwrap();
+  assert_eq!(cpu.engine(), Engine::Fast);
+}
@@ -0,0 +1,122 @@
+extern crate cpu;
+extern crate assembler;
+
+use cpu::{
+  Cpu,
+  Engine,
+  Pacing,
+  Reg,
+};
+use cpu::debug::Lockstep;
+
+
+const REGISTERS: [Reg; 14] = [
+  Reg::R0, Reg::R1, Reg::R2, Reg::R3, Reg::R4, Reg::R5, Reg::R6, Reg::R7,
+  Reg::S0, Reg::S1, Reg::LR, Reg::F, Reg::A, Reg::I,
+];
+
+fn machine(rom: Vec<u16>) -> Cpu {
+  let mut cpu = Cpu::new(48.0, rom).unwrap();
+  cpu.set_pacing(Pacing::Unthrottled);
+  cpu
+}
+
+// Runs `rom` beside the microcode to `cycles`. Errors are only returned when both engines raise them alike.
+fn lockstep(rom: Vec<u16>, cycles: u64) -> Cpu {
+  let mut cpu = machine(rom);
+  let outcome = Lockstep::new(&mut cpu).unwrap().run(cycles);
+  match outcome {
+    Ok(Some(divergence)) => panic!("{}", divergence),
+    Ok(None) | Err(_) => cpu,
+  }
+}
+
+#[test]
+fn keyboard_test_has_no_divergence() {
+  let cpu = lockstep(cpu::assemble("assets/keyboard-test.a").unwrap(), 5_000);
+  assert!(cpu.cycles() >= 5_000);
+}
+
+#[test]
+fn screen_test_has_no_divergence() {
+  let cpu = lockstep(cpu::assemble("assets/screen-test.a").unwrap(), 50_000);
+  assert!(cpu.halted());
+  let cpu = lockstep(cpu::load_rom("assets/screen-test.rom").unwrap(), 50_000);
+  assert!(cpu.halted());
+}
+
+#[test]
+fn forth_rom_has_no_divergence() {
+  lockstep(cpu::load_rom("assets/rom.rom").unwrap(), 5_000);
+}
+
+// Opcodes the fast engine leaves to the microcode: conditions that fail to decode, unused unary
+// operations, BRK, HLT and the NOP group's other encodings.
+const FALLBACK: [u16; 10] = [0x5001, 0xE001, 0xA001, 0x4001, 0x4101, 0x6410, 0x6418, 0x6428, 0x0480, 0x0001];
+
+#[derive(Debug, PartialEq)]
+struct Outcome {
+  result: String,
+  halted: bool,
+  cycles: u64,
+  instructions: u64,
+  pc: u16,
+  registers: Vec<u16>,
+}
+
+fn run(op: u16, engine: Engine) -> Outcome {
+  let program = format!("
+#define * = 0xE000
+INIT:
+  LD A,1
+  LD B,2
+  #word 0x{:04X}
+  ADD A,B
+  HLT
+#define * = 0xFFFF
+#word INIT
+", op);
+  let mut cpu = machine(assembler::from_string(&program).unwrap());
+  cpu.set_engine(engine).unwrap();
+  let mut result = String::new();
+  for _ in 0..8 {
+    match cpu.step_instruction() {
+      Ok(step) => if let Some(stop) = step.stop {
+        result = stop.to_string();
+        break
+      },
+      Err(error) => {
+        result = error.to_string();
+        break
+      },
+    }
+    if cpu.halted() {
+      break
+    }
+  }
+  Outcome {
+    result,
+    halted: cpu.halted(),
+    cycles: cpu.cycles(),
+    instructions: cpu.instructions(),
+    pc: cpu.instruction_pc(),
+    registers: REGISTERS.iter().map(|r| cpu.register(*r)).collect(),
+  }
+}
+
+#[test]
+fn uninterpretable_opcodes_fall_back_to_the_microcode() {
+  for op in FALLBACK.iter() {
+    let microcode = run(*op, Engine::Microcode);
+    assert_eq!(run(*op, Engine::Fast), microcode, "opcode 0x{:04X}", op);
+  }
+  assert!(run(0x0080, Engine::Fast).halted);
+}
+
+#[test]
+fn fallback_opcodes_match_in_lockstep() {
+  for op in FALLBACK.iter() {
+    let program = format!("#define * = 0xE000\nINIT:\n  LD A,1\n  #word 0x{:04X}\n  HLT\n#define * = 0xFFFF\n#word INIT\n", op);
+    lockstep(assembler::from_string(&program).unwrap(), 1_000);
+  }
+}
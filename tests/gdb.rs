@@ -0,0 +1,120 @@
+extern crate cpu;
+extern crate assembler;
+
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::{
+  TcpListener,
+  TcpStream,
+};
+use std::thread;
+use cpu::{
+  Cpu,
+  Pacing,
+};
+use cpu::debug::GdbStub;
+
+
+const PROGRAM: &str = "
+#define * = 0xE000
+INIT:
+  LD A,1
+  LD B,2
+  ADD A,B
+STOP:
+  NOP
+  HLT
+#define * = 0xFFFF
+#word INIT
+";
+
+// The register order is R0-R7, S0, S1, PC, LR, F, four hex digits each.
+const PC: usize = 10 * 4;
+
+struct Client {
+  input: BufReader<TcpStream>,
+  output: TcpStream,
+}
+
+impl Client {
+  fn connect(port: u16) -> Client {
+    let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    Client { input: BufReader::new(stream.try_clone().unwrap()), output: stream }
+  }
+
+  fn byte(&mut self) -> u8 {
+    let mut byte = [0u8; 1];
+    self.input.read_exact(&mut byte).unwrap();
+    byte[0]
+  }
+
+  // Sends `packet` and returns the body of the reply, checking the ack and checksum on the way.
+  fn request(&mut self, packet: &str) -> String {
+    let sum = packet.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+    write!(self.output, "${}#{:02x}", packet, sum).unwrap();
+    assert_eq!(self.byte(), b'+', "ack for {}", packet);
+    assert_eq!(self.byte(), b'$', "reply to {}", packet);
+
+    let mut body = Vec::new();
+    loop {
+      match self.byte() {
+        b'#' => break,
+        b => body.push(b),
+      }
+    }
+    let checksum = [self.byte(), self.byte()];
+    let expected = u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap();
+    assert_eq!(body.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)), expected, "checksum of reply to {}", packet);
+    self.output.write_all(b"+").unwrap();
+    String::from_utf8(body).unwrap()
+  }
+
+  fn kill(&mut self) {
+    self.output.write_all(b"$k#6b").unwrap();
+    assert_eq!(self.byte(), b'+');
+  }
+}
+
+#[test]
+fn scripted_session() {
+  let rom = assembler::from_string(PROGRAM).unwrap();
+  let mut cpu = Cpu::new(48.0, rom).unwrap();
+  cpu.set_pacing(Pacing::Unthrottled);
+
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let port = listener.local_addr().unwrap().port();
+  let client = thread::spawn(move || {
+    let mut gdb = Client::connect(port);
+
+    let registers = gdb.request("g");
+    assert_eq!(registers.len(), 13 * 4);
+
+    let mut changed = registers.clone();
+    changed.replace_range(7 * 4..8 * 4, "3412");
+    assert_eq!(gdb.request(&format!("G{}", changed)), "OK");
+    assert_eq!(gdb.request("p7"), "3412");
+
+    // Words go out low byte first: LD A,1 is 0xD808, LD B,2 is 0xD811.
+    assert_eq!(gdb.request("me000,4"), "08d811d8");
+    assert_eq!(gdb.request("M0100,2:cdab"), "OK");
+    assert_eq!(gdb.request("m0100,2"), "cdab");
+    assert_eq!(gdb.request("m0,ffffffffffffffff"), "E01");
+    assert_eq!(gdb.request("m10000,2"), "E01");
+    assert_eq!(gdb.request("Mffffffffffffffff,2:cdab"), "E01");
+
+    assert_eq!(gdb.request("Z0,e003,1"), "OK");
+    assert!(gdb.request("c").starts_with("T05swbreak"));
+    let registers = gdb.request("g");
+    assert_eq!(&registers[0..4], "0300");
+    assert_eq!(&registers[PC..PC + 4], "03e0");
+
+    assert_eq!(gdb.request("s"), "S05");
+    assert_eq!(&gdb.request("g")[PC..PC + 4], "04e0");
+
+    gdb.kill();
+  });
+
+  GdbStub::new(&mut cpu).serve_listener(listener).unwrap();
+  client.join().unwrap();
+  assert_eq!(cpu.register(cpu::Reg::R7), 0x1234);
+}
@@ -0,0 +1,99 @@
+extern crate cpu;
+extern crate assembler;
+
+use cpu::{
+  Cpu,
+  Pacing,
+  History,
+};
+use cpu::debug::{
+  Replay,
+  Script,
+};
+use cpu::memory::Addressable;
+
+
+const PROGRAM: &str = "
+#define * = 0xE000
+INIT:
+  NOP
+  JMP INIT
+#define * = 0xFFFF
+#word INIT
+";
+
+fn machine() -> Cpu {
+  let rom = assembler::from_string(PROGRAM).unwrap();
+  let mut cpu = Cpu::new(48.0, rom).unwrap();
+  cpu.set_pacing(Pacing::Unthrottled);
+  cpu.set_history(History::new(64, 64));
+  cpu
+}
+
+fn run_to(cpu: &mut Cpu, cycles: u64) {
+  while cpu.cycles() < cycles {
+    cpu.step_cycle().unwrap();
+  }
+}
+
+// Everything waiting in the keyboard FIFO, oldest first.
+fn typed(cpu: &mut Cpu) -> String {
+  let keyboard = cpu.keyboard().unwrap();
+  std::iter::from_fn(|| match keyboard.read(0xDE04).unwrap() {
+    0x0000 => None,
+    key => Some(key as u8 as char),
+  }).collect()
+}
+
+#[test]
+fn replay_resumes_from_the_rewound_half_cycle() {
+  let mut cpu = machine();
+  cpu.set_replay(Replay::new("100 char 0x61\n200 char 0x62\n300 char 0x63\n").unwrap());
+  run_to(&mut cpu, 125);
+  cpu.rewind_cycles(50).unwrap();
+  assert_eq!(cpu.cycles(), 75);
+  run_to(&mut cpu, 200);
+  assert_eq!(typed(&mut cpu), "abc");
+}
+
+#[test]
+fn replay_follows_load_state() {
+  let mut cpu = machine();
+  cpu.set_replay(Replay::new("100 char 0x61\n200 char 0x62\n300 char 0x63\n").unwrap());
+  run_to(&mut cpu, 75);
+  let state = cpu.save_state();
+  run_to(&mut cpu, 200);
+  assert_eq!(typed(&mut cpu), "abc");
+
+  cpu.load_state(&state).unwrap();
+  run_to(&mut cpu, 200);
+  assert_eq!(typed(&mut cpu), "abc");
+}
+
+#[test]
+fn script_resumes_from_the_rewound_half_cycle() {
+  let mut cpu = machine();
+  // A key every 50 cycles from the first half-cycle: at cycles 50, 100 and 150.
+  cpu.set_script(Script::new("abc", 50).unwrap());
+  run_to(&mut cpu, 125);
+  cpu.rewind_cycles(50).unwrap();
+  assert_eq!(cpu.cycles(), 75);
+  run_to(&mut cpu, 200);
+  assert_eq!(typed(&mut cpu), "abc");
+}
+
+#[test]
+fn script_follows_load_state() {
+  let mut cpu = machine();
+  cpu.set_script(Script::new("abc", 50).unwrap());
+  run_to(&mut cpu, 75);
+  let state = cpu.save_state();
+  run_to(&mut cpu, 200);
+  assert_eq!(typed(&mut cpu), "abc");
+
+  cpu.load_state(&state).unwrap();
+  run_to(&mut cpu, 120);
+  assert_eq!(typed(&mut cpu), "ab");
+  run_to(&mut cpu, 200);
+  assert_eq!(typed(&mut cpu), "c");
+}
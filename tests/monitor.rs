@@ -0,0 +1,57 @@
+extern crate cpu;
+extern crate assembler;
+
+use cpu::{
+  Cpu,
+  Pacing,
+  Reg,
+};
+use cpu::debug::Monitor;
+
+
+const PROGRAM: &str = "
+#define * = 0xE000
+INIT:
+  LD A,1
+  LD B,2
+  ADD A,B
+  HLT
+#define * = 0xFFFF
+#word INIT
+";
+
+fn machine() -> Cpu {
+  let rom = assembler::from_string(PROGRAM).unwrap();
+  let mut cpu = Cpu::new(48.0, rom).unwrap();
+  cpu.set_pacing(Pacing::Unthrottled);
+  cpu
+}
+
+fn monitor(cpu: &mut Cpu, commands: &str) -> String {
+  let mut output = Vec::new();
+  Monitor::new(cpu).run(commands.as_bytes(), &mut output).unwrap();
+  String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn set_pc_then_step_runs_the_new_instruction() {
+  let mut cpu = machine();
+  while cpu.instruction_pc() != 0xE002 {
+    cpu.step_instruction().unwrap();
+  }
+  assert_eq!(cpu.register(Reg::R0), 0x0001);
+
+  // ADD A,B is already fetched; the step has to run LD A,1 from 0xE000 instead.
+  monitor(&mut cpu, "set a 5\nset pc 0xE000\nstep\n");
+  assert_eq!(cpu.register(Reg::R0), 0x0001);
+  assert_eq!(cpu.instruction_pc(), 0xE001);
+}
+
+#[test]
+fn set_writes_registers() {
+  let mut cpu = machine();
+  let output = monitor(&mut cpu, "set x 0x1234\nset s1 0x0700\nset q 1\n");
+  assert_eq!(cpu.register(Reg::R5), 0x1234);
+  assert_eq!(cpu.register(Reg::S1), 0x0700);
+  assert!(output.contains("Unknown register 'q'."), "{}", output);
+}
@@ -0,0 +1,104 @@
+extern crate cpu;
+
+use cpu::Error;
+use cpu::components::{
+  Flag,
+  Flags,
+};
+use cpu::io::Pic;
+use cpu::memory::Addressable;
+
+
+fn enabled() -> Flags {
+  let mut flags = Flags::new();
+  flags.set(Flag::InterruptEnable, true);
+  flags
+}
+
+#[test]
+fn lower_lines_take_priority() {
+  let mut pic = Pic::new();
+  let flags = enabled();
+  assert_eq!(pic.request(&flags), None);
+  pic.raise(5).unwrap();
+  pic.raise(2).unwrap();
+  pic.raise(2).unwrap();
+  assert_eq!(pic.read(0xDE0C).unwrap(), 0x0024);
+  assert_eq!(pic.request(&flags), Some(2));
+
+  pic.acknowledge(2);
+  assert_eq!(pic.pending(), 0x0020);
+  assert_eq!(pic.in_service(), 0x0004);
+  assert_eq!(pic.request(&flags), Some(5));
+  pic.acknowledge(5);
+  assert_eq!(pic.request(&flags), None);
+}
+
+#[test]
+fn interrupt_enable_and_masks_hold_lines_back() {
+  let mut pic = Pic::new();
+  pic.raise(1).unwrap();
+  pic.raise(3).unwrap();
+  assert_eq!(pic.request(&Flags::new()), None);
+
+  let mut flags = enabled();
+  flags.set(Flag::InterruptOne, true);
+  assert_eq!(pic.request(&flags), Some(3));
+  flags.set(Flag::InterruptThree, true);
+  assert_eq!(pic.request(&flags), None);
+  assert_eq!(pic.pending(), 0x000A);
+  flags.set(Flag::InterruptOne, false);
+  assert_eq!(pic.request(&flags), Some(1));
+}
+
+#[test]
+fn line_seven_is_never_taken() {
+  let mut pic = Pic::new();
+  pic.raise(7).unwrap();
+  assert_eq!(pic.request(&enabled()), None);
+  assert_eq!(pic.pending(), 0x0080);
+}
+
+#[test]
+fn nested_mode_holds_back_lower_priorities() {
+  let mut pic = Pic::new();
+  let flags = enabled();
+  pic.raise(3).unwrap();
+  pic.acknowledge(3);
+  pic.raise(3).unwrap();
+  pic.raise(4).unwrap();
+  pic.raise(1).unwrap();
+  assert_eq!(pic.request(&flags), Some(1));
+  pic.acknowledge(1);
+  assert_eq!(pic.request(&flags), Some(3));
+
+  pic.write(0xDE0E, 0xFFFF).unwrap();
+  assert_eq!(pic.read(0xDE0E).unwrap(), 0x0001);
+  assert_eq!(pic.request(&flags), None);
+  pic.write(0xDE0D, 0x0002).unwrap();
+  assert_eq!(pic.in_service(), 0x0008);
+  assert_eq!(pic.request(&flags), None);
+  pic.write(0xDE0D, 0x0008).unwrap();
+  assert_eq!(pic.request(&flags), Some(3));
+}
+
+#[test]
+fn writes_drop_pending_requests() {
+  let mut pic = Pic::new();
+  pic.raise(0).unwrap();
+  pic.raise(6).unwrap();
+  pic.write(0xDE0C, 0x0001).unwrap();
+  assert_eq!(pic.pending(), 0x0040);
+  assert_eq!(pic.request(&enabled()), Some(6));
+}
+
+#[test]
+fn rejects_invalid_lines_and_addresses() {
+  let mut pic = Pic::new();
+  match pic.raise(8) {
+    Err(Error::InvalidInterrupt(8)) => (),
+    other => panic!("expected InvalidInterrupt, got {:?}", other),
+  }
+  assert!(pic.read(0xDE0F).is_err());
+  assert!(pic.write(0xDE0F, 0).is_err());
+}
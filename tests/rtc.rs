@@ -0,0 +1,121 @@
+extern crate cpu;
+
+use cpu::Error;
+use cpu::io::{
+  Rtc,
+  TimeSource,
+};
+use cpu::memory::Addressable;
+
+
+// 2024-02-29T12:34:56 UTC.
+const LEAP_DAY: i64 = 1709210096;
+
+fn fields(rtc: &Rtc) -> Vec<u16> {
+  (0xDE10..=0xDE15).map(|address| rtc.read(address).unwrap()).collect()
+}
+
+fn fixed(time: i64) -> Rtc {
+  let mut rtc = Rtc::new();
+  rtc.set_source(TimeSource::Fixed(time));
+  rtc
+}
+
+#[test]
+fn parses_time_sources() {
+  assert_eq!("host".parse::<TimeSource>().unwrap(), TimeSource::Host);
+  assert_eq!("fixed:2024-02-29T12:34:56".parse::<TimeSource>().unwrap(), TimeSource::Fixed(LEAP_DAY));
+  assert_eq!("virtual:2024-02-29 12:34:56".parse::<TimeSource>().unwrap(), TimeSource::Virtual(LEAP_DAY));
+  assert_eq!("fixed:-1".parse::<TimeSource>().unwrap(), TimeSource::Fixed(-1));
+  assert_eq!(TimeSource::Fixed(LEAP_DAY).to_string(), "fixed:2024-02-29T12:34:56");
+  assert_eq!(TimeSource::Virtual(0).to_string(), "virtual:1970-01-01T00:00:00");
+}
+
+#[test]
+fn rejects_invalid_times() {
+  for s in &[
+    "fixed:2023-02-29T00:00:00",
+    "fixed:2024-04-31T00:00:00",
+    "fixed:2024-13-01T00:00:00",
+    "fixed:2024-00-01T00:00:00",
+    "fixed:2024-01-01T24:00:00",
+    "fixed:2024-01-01T00:60:00",
+    "fixed:2024-01-01T00:00:60",
+    "fixed:2024-01-01",
+    "virtual:",
+    "fixed",
+    "now",
+  ] {
+    match s.parse::<TimeSource>() {
+      Err(Error::InvalidTime(value)) => assert_eq!(&value, s),
+      other => panic!("expected InvalidTime for {}, got {:?}", s, other),
+    }
+  }
+}
+
+#[test]
+fn reads_the_fields() {
+  assert_eq!(fields(&fixed(LEAP_DAY)), [56, 34, 12, 29, 2, 2024]);
+}
+
+#[test]
+fn reading_seconds_or_year_latches() {
+  let mut rtc = fixed(LEAP_DAY);
+  assert_eq!(rtc.read(0xDE11).unwrap(), 0);
+
+  assert_eq!(rtc.read(0xDE15).unwrap(), 2024);
+  rtc.set_source(TimeSource::Fixed(LEAP_DAY + 60));
+  assert_eq!(rtc.read(0xDE11).unwrap(), 34);
+  assert_eq!(rtc.read(0xDE10).unwrap(), 56);
+  assert_eq!(rtc.read(0xDE11).unwrap(), 35);
+  assert_eq!(rtc.peek(0xDE11).unwrap(), 35);
+}
+
+#[test]
+fn writes_set_the_clock() {
+  let mut rtc = fixed(LEAP_DAY);
+  rtc.write(0xDE12, 23).unwrap();
+  assert_eq!(rtc.now(), LEAP_DAY + 11 * 3600);
+  rtc.write(0xDE15, 2025).unwrap();
+  assert_eq!(fields(&rtc), [56, 34, 23, 29, 2, 2024]);
+  rtc.write(0xDE13, 28).unwrap();
+  rtc.write(0xDE15, 2025).unwrap();
+  assert_eq!(fields(&rtc), [56, 34, 23, 28, 2, 2025]);
+}
+
+#[test]
+fn writes_out_of_range_are_ignored() {
+  let mut rtc = fixed(LEAP_DAY);
+  for (address, value) in &[(0xDE10, 60), (0xDE11, 60), (0xDE12, 24), (0xDE13, 30), (0xDE13, 0), (0xDE14, 13), (0xDE14, 0)] {
+    rtc.write(*address, *value).unwrap();
+    assert_eq!(rtc.now(), LEAP_DAY, "writing {} to 0x{:04X}", value, address);
+  }
+}
+
+#[test]
+fn set_source_drops_the_program_time() {
+  let mut rtc = fixed(LEAP_DAY);
+  rtc.write(0xDE12, 0).unwrap();
+  rtc.set_source(TimeSource::Fixed(LEAP_DAY));
+  assert_eq!(rtc.now(), LEAP_DAY);
+}
+
+#[test]
+fn virtual_time_advances_with_cycles() {
+  let mut rtc = Rtc::new();
+  rtc.set_source(TimeSource::Virtual(LEAP_DAY));
+  assert_eq!(rtc.now(), LEAP_DAY);
+  rtc.advance(47, 48.0);
+  assert_eq!(rtc.now(), LEAP_DAY);
+  rtc.advance(48 * 4, 48.0);
+  assert_eq!(rtc.now(), LEAP_DAY + 4);
+  assert_eq!(fields(&rtc), [0, 35, 12, 29, 2, 2024]);
+
+  rtc.write(0xDE11, 0).unwrap();
+  rtc.advance(48 * 64, 48.0);
+  assert_eq!(fields(&rtc), [0, 1, 12, 29, 2, 2024]);
+
+  let mut fixed = fixed(LEAP_DAY);
+  fixed.advance(48 * 4, 48.0);
+  assert_eq!(fixed.now(), LEAP_DAY);
+}
@@ -0,0 +1,209 @@
+extern crate cpu;
+
+use std::fs;
+use std::path::PathBuf;
+use cpu::Error;
+use cpu::io::Sd;
+use cpu::memory::Addressable;
+use cpu::snapshot::{
+  Snapshot,
+  Writer,
+  Reader,
+};
+
+
+const PRESENT: u16 = 0x0001;
+const BUSY: u16 = 0x0002;
+const ERROR: u16 = 0x8000;
+
+// Removes the image when the test is done with it, pass or fail.
+struct Image(PathBuf);
+
+impl Drop for Image {
+  fn drop(&mut self) {
+    let _ = fs::remove_file(&self.0);
+  }
+}
+
+// A card of `sectors` sectors, each word holding its sector in the high byte and index in the low.
+fn card(name: &str, sectors: u16) -> (Sd, Image) {
+  let path = std::env::temp_dir().join(format!("cpu-sd-{}-{}.img", name, std::process::id()));
+  let bytes: Vec<u8> = (0..sectors)
+    .flat_map(|sector| (0..256u16).map(move |i| (sector << 8) | i))
+    .flat_map(|word| word.to_le_bytes().to_vec())
+    .collect();
+  fs::write(&path, bytes).unwrap();
+  let mut sd = Sd::new();
+  sd.open(path.to_str().unwrap()).unwrap();
+  (sd, Image(path))
+}
+
+fn error(sd: &Sd) -> u16 {
+  let status = sd.peek(0xDE23).unwrap();
+  if (status & ERROR) != 0 { (status >> 8) & 0x000F } else { 0 }
+}
+
+#[test]
+fn no_card_fails() {
+  let mut sd = Sd::new();
+  assert_eq!(sd.read(0xDE23).unwrap(), 0x0000);
+  sd.write(0xDE22, 0x0001).unwrap();
+  assert_eq!(sd.read(0xDE23).unwrap(), ERROR | 0x0100);
+  sd.write(0xDE23, ERROR).unwrap();
+  assert_eq!(sd.read(0xDE23).unwrap(), 0x0000);
+}
+
+#[test]
+fn reads_and_writes_sectors() {
+  let (mut sd, image) = card("sectors", 3);
+  assert_eq!(sd.sectors(), 3);
+  assert_eq!(sd.read(0xDE23).unwrap(), PRESENT);
+
+  sd.write(0xDE21, 2).unwrap();
+  sd.write(0xDE22, 0x0001).unwrap();
+  assert_eq!(error(&sd), 0);
+  assert_eq!(sd.read(0xDE24).unwrap(), 0x0200);
+  assert_eq!(sd.read(0xDE24).unwrap(), 0x0201);
+  assert_eq!(sd.peek(0xDE24).unwrap(), 0x0202);
+
+  sd.write(0xDE21, 0).unwrap();
+  sd.write(0xDE22, 0x0001).unwrap();
+  sd.write(0xDE24, 0xBEEF).unwrap();
+  sd.write(0xDE21, 1).unwrap();
+  sd.write(0xDE22, 0x0002).unwrap();
+  assert_eq!(error(&sd), 0);
+
+  let bytes = fs::read(&image.0).unwrap();
+  assert_eq!(&bytes[512..516], &[0xEF, 0xBE, 0x01, 0x00]);
+  assert_eq!(&bytes[1024..1026], &[0x00, 0x02]);
+}
+
+#[test]
+fn data_pointer_wraps_at_the_end_of_the_buffer() {
+  let (mut sd, _image) = card("pointer", 1);
+  sd.write(0xDE22, 0x0001).unwrap();
+  for _ in 0..256 {
+    sd.read(0xDE24).unwrap();
+  }
+  assert_eq!(sd.read(0xDE24).unwrap(), 0x0000);
+}
+
+#[test]
+fn error_codes() {
+  let (mut sd, _image) = card("errors", 2);
+  sd.write(0xDE21, 2).unwrap();
+  sd.write(0xDE22, 0x0001).unwrap();
+  assert_eq!(error(&sd), 2);
+  sd.write(0xDE20, 1).unwrap();
+  sd.write(0xDE21, 0).unwrap();
+  assert_eq!(sd.read(0xDE20).unwrap(), 1);
+  sd.write(0xDE22, 0x0002).unwrap();
+  assert_eq!(error(&sd), 2);
+
+  sd.write(0xDE20, 0).unwrap();
+  sd.write(0xDE22, 0x0009).unwrap();
+  assert_eq!(error(&sd), 4);
+  sd.write(0xDE22, 0x0001).unwrap();
+  assert_eq!(error(&sd), 0);
+}
+
+#[test]
+fn dma_is_busy_until_the_transfer() {
+  let (mut sd, _image) = card("busy", 2);
+  let mut ram = vec![0x0000; 0x1000];
+  sd.write(0xDE21, 1).unwrap();
+  sd.write(0xDE25, 0x0100).unwrap();
+  sd.write(0xDE22, 0x0003).unwrap();
+  assert_eq!(sd.read(0xDE23).unwrap(), PRESENT | BUSY);
+
+  sd.write(0xDE22, 0x0001).unwrap();
+  assert_eq!(error(&sd), 4);
+  assert_eq!(sd.read(0xDE23).unwrap() & BUSY, BUSY);
+  assert_eq!(sd.read(0xDE22).unwrap(), 0x0003);
+
+  sd.dma(&mut ram);
+  assert_eq!(sd.read(0xDE23).unwrap() & BUSY, 0);
+  assert_eq!(ram[0x00FF], 0x0000);
+  assert_eq!(ram[0x0100], 0x0100);
+  assert_eq!(ram[0x01FF], 0x01FF);
+  assert_eq!(ram[0x0200], 0x0000);
+
+  // Without a command outstanding there is nothing to transfer.
+  ram[0x0100] = 0xFFFF;
+  sd.dma(&mut ram);
+  assert_eq!(ram[0x0100], 0xFFFF);
+}
+
+#[test]
+fn write_dma_copies_ram_to_the_sector() {
+  let (mut sd, image) = card("write-dma", 1);
+  let mut ram = vec![0x1234; 0x1000];
+  sd.write(0xDE25, 0x0F00).unwrap();
+  sd.write(0xDE22, 0x0004).unwrap();
+  sd.dma(&mut ram);
+  assert_eq!(error(&sd), 0);
+  assert!(fs::read(&image.0).unwrap().chunks(2).all(|pair| pair == [0x34, 0x12]));
+}
+
+#[test]
+fn dma_outside_ram_fails() {
+  let (mut sd, _image) = card("dma-range", 1);
+  let mut ram = vec![0x0000; 0x1000];
+  sd.write(0xDE25, 0x0F01).unwrap();
+  sd.write(0xDE22, 0x0003).unwrap();
+  sd.dma(&mut ram);
+  assert_eq!(error(&sd), 5);
+  assert_eq!(sd.read(0xDE23).unwrap() & BUSY, 0);
+  assert!(ram.iter().all(|word| *word == 0x0000));
+
+  sd.write(0xDE25, 0xFFFF).unwrap();
+  sd.write(0xDE22, 0x0004).unwrap();
+  sd.dma(&mut ram);
+  assert_eq!(error(&sd), 5);
+}
+
+fn state(sector: u64, pointer: u16) -> Vec<u8> {
+  let mut w = Writer::new();
+  w.u64(sector);
+  w.u16(0x0001);
+  w.u16(0x0000);
+  w.u16(0x0000);
+  w.u16(pointer);
+  w.words(&[0x0000; 256]);
+  w.into_bytes()
+}
+
+fn restore(bytes: &[u8]) -> cpu::Result<Sd> {
+  let mut sd = Sd::new();
+  sd.restore(&mut Reader::new(bytes)?)?;
+  Ok(sd)
+}
+
+#[test]
+fn snapshot_round_trips() {
+  let (mut sd, _image) = card("snapshot", 2);
+  sd.write(0xDE21, 1).unwrap();
+  sd.write(0xDE22, 0x0001).unwrap();
+  sd.read(0xDE24).unwrap();
+  let mut w = Writer::new();
+  sd.save(&mut w);
+
+  let restored = restore(&w.into_bytes()).unwrap();
+  for address in 0xDE20..=0xDE25 {
+    assert_eq!(restored.peek(address).unwrap() & !PRESENT, sd.peek(address).unwrap() & !PRESENT);
+  }
+  assert_eq!(restored.peek(0xDE24).unwrap(), 0x0101);
+}
+
+#[test]
+fn restore_rejects_invalid_state() {
+  assert!(restore(&state(0xFFFF_FFFF, 0x00FF)).is_ok());
+  match restore(&state(0x1_0000_0000, 0)) {
+    Err(Error::InvalidSnapshot(message)) => assert!(message.contains("sector"), "{}", message),
+    other => panic!("expected InvalidSnapshot, got {:?}", other.map(|_| ())),
+  }
+  match restore(&state(0, 0x0100)) {
+    Err(Error::InvalidSnapshot(message)) => assert!(message.contains("pointer"), "{}", message),
+    other => panic!("expected InvalidSnapshot, got {:?}", other.map(|_| ())),
+  }
+}
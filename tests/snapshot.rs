@@ -0,0 +1,115 @@
+extern crate cpu;
+extern crate assembler;
+
+use cpu::{
+  Cpu,
+  Error,
+  Pacing,
+  Reg,
+};
+use cpu::debug::Replay;
+
+
+// Copies each key from the keyboard FIFO into RAM from 0x0100, counting them in B.
+const PROGRAM: &str = "
+#define * = 0xE000
+INIT:
+  LD X,0x0100
+LOOP:
+  LD A,(0xDE04)
+  CMP A,0x0000
+E.JMP LOOP
+  LD (X),A
+  ADD X,1
+  ADD B,1
+  JMP LOOP
+#define * = 0xFFFF
+#word INIT
+";
+
+
+fn machine(rom: Vec<u16>) -> Cpu {
+  let mut cpu = Cpu::new(48.0, rom).unwrap();
+  cpu.set_pacing(Pacing::Unthrottled);
+  cpu
+}
+
+fn run_to(cpu: &mut Cpu, cycles: u64) {
+  while cpu.cycles() < cycles {
+    cpu.step_cycle().unwrap();
+  }
+}
+
+// Having copied two keys, stopped halfway through a cycle.
+fn typed() -> Cpu {
+  let mut cpu = machine(assembler::from_string(PROGRAM).unwrap());
+  cpu.set_replay(Replay::new("100 char 0x68\n200 char 0x69\n").unwrap());
+  run_to(&mut cpu, 1000);
+  for _ in 0..3 {
+    cpu.step_half_cycle().unwrap();
+  }
+  cpu
+}
+
+fn assert_rejected(cpu: &mut Cpu, bytes: &[u8], expected: &str) {
+  match cpu.load_state(bytes) {
+    Err(Error::InvalidSnapshot(message)) => assert!(message.contains(expected), "{}", message),
+    Err(error) => panic!("expected InvalidSnapshot, got {}", error),
+    Ok(()) => panic!("expected InvalidSnapshot, got a machine"),
+  }
+}
+
+#[test]
+fn round_trip_restores_the_machine() {
+  let mut cpu = typed();
+  assert_eq!(cpu.peek(0x0100).unwrap() & 0x00FF, 0x0068);
+  assert_eq!(cpu.peek(0x0101).unwrap() & 0x00FF, 0x0069);
+  assert_eq!(cpu.register(Reg::R1), 2);
+  let state = cpu.save_state();
+
+  let mut restored = machine(Vec::new());
+  restored.load_state(&state).unwrap();
+  assert_eq!(restored.save_state(), state);
+  assert_eq!(restored.cycles(), cpu.cycles());
+  assert_eq!(restored.instruction_pc(), cpu.instruction_pc());
+  assert_eq!(restored.memory().difference(cpu.memory()), None);
+
+  cpu.keyboard().unwrap().push(0x0821);
+  restored.keyboard().unwrap().push(0x0821);
+  run_to(&mut cpu, 5000);
+  run_to(&mut restored, 5000);
+  assert_eq!(cpu.register(Reg::R1), 3);
+  assert_eq!(restored.save_state(), cpu.save_state());
+}
+
+#[test]
+fn rejects_a_bad_magic() {
+  let mut cpu = machine(Vec::new());
+  let mut state = typed().save_state();
+  state[0] = b'X';
+  assert_rejected(&mut cpu, &state, "Not a snapshot file.");
+  assert_rejected(&mut cpu, b"CPUSNAP", "Not a snapshot file.");
+  assert_rejected(&mut cpu, b"", "Not a snapshot file.");
+}
+
+#[test]
+fn rejects_another_version() {
+  let mut cpu = machine(Vec::new());
+  let mut state = typed().save_state();
+  let version = cpu::snapshot::VERSION.wrapping_add(1).to_le_bytes();
+  state[8..10].copy_from_slice(&version);
+  assert_rejected(&mut cpu, &state, "is not supported");
+}
+
+#[test]
+fn rejects_a_truncated_or_padded_stream() {
+  let mut cpu = machine(Vec::new());
+  let state = typed().save_state();
+  for len in [9, 10, 11, 64, state.len() / 2, state.len() - 1].iter() {
+    assert_rejected(&mut cpu, &state[..*len], "Snapshot is truncated.");
+  }
+  let mut padded = state.clone();
+  padded.push(0);
+  assert_rejected(&mut cpu, &padded, "unexpected bytes at the end");
+  cpu.load_state(&state).unwrap();
+}
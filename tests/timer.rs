@@ -0,0 +1,97 @@
+extern crate cpu;
+
+use cpu::io::Timer;
+use cpu::memory::Addressable;
+
+
+const ENABLE: u16 = 0x0001;
+const PERIODIC: u16 = 0x0002;
+const INTERRUPT: u16 = 0x0004;
+const EXPIRED: u16 = 0x8000;
+
+// Line 3, with the reload loaded into the counter.
+fn timer(control: u16, reload: u16, prescaler: u16) -> Timer {
+  let mut timer = Timer::new();
+  timer.write(0xDE06, reload).unwrap();
+  timer.write(0xDE08, prescaler).unwrap();
+  timer.write(0xDE05, control | 0x0030).unwrap();
+  timer
+}
+
+#[test]
+fn disabled_timer_does_not_count() {
+  let mut timer = timer(INTERRUPT, 3, 0);
+  assert_eq!(timer.advance(100), None);
+  assert_eq!(timer.read(0xDE07).unwrap(), 3);
+  assert_eq!(timer.read(0xDE05).unwrap() & EXPIRED, 0);
+}
+
+#[test]
+fn one_shot_stops_when_it_expires() {
+  let mut timer = timer(ENABLE | INTERRUPT, 3, 0);
+  assert_eq!(timer.advance(2), None);
+  assert_eq!(timer.read(0xDE07).unwrap(), 1);
+  assert_eq!(timer.advance(10), Some(3));
+  assert_eq!(timer.read(0xDE07).unwrap(), 0);
+  assert_eq!(timer.read(0xDE05).unwrap(), EXPIRED | 0x0030 | INTERRUPT);
+
+  assert_eq!(timer.advance(100), None);
+  assert_eq!(timer.read(0xDE07).unwrap(), 0);
+}
+
+#[test]
+fn periodic_reloads_and_keeps_counting() {
+  let mut timer = timer(ENABLE | PERIODIC | INTERRUPT, 4, 0);
+  assert_eq!(timer.advance(4), Some(3));
+  assert_eq!(timer.read(0xDE07).unwrap(), 4);
+  assert_eq!(timer.advance(6), None);
+  assert_eq!(timer.read(0xDE07).unwrap(), 2);
+  // Several expiries in one call still raise the line once, and leave the remainder counted.
+  assert_eq!(timer.advance(17), Some(3));
+  assert_eq!(timer.read(0xDE07).unwrap(), 3);
+  assert_eq!(timer.read(0xDE05).unwrap() & (ENABLE | EXPIRED), ENABLE | EXPIRED);
+}
+
+#[test]
+fn reload_register_is_loaded_on_write_and_expiry() {
+  let mut timer = timer(ENABLE | PERIODIC, 10, 0);
+  assert_eq!(timer.advance(3), None);
+  timer.write(0xDE06, 2).unwrap();
+  assert_eq!(timer.read(0xDE07).unwrap(), 2);
+  timer.write(0xDE07, 1).unwrap();
+  assert_eq!(timer.read(0xDE06).unwrap(), 2);
+  assert_eq!(timer.advance(4), None);
+  assert_eq!(timer.read(0xDE07).unwrap(), 2);
+  assert_ne!(timer.read(0xDE05).unwrap() & EXPIRED, 0);
+}
+
+#[test]
+fn prescaler_divides_the_clock() {
+  let mut timer = timer(ENABLE | INTERRUPT, 2, 3);
+  assert_eq!(timer.advance(7), None);
+  assert_eq!(timer.read(0xDE07).unwrap(), 1);
+  assert_eq!(timer.advance(8), Some(3));
+}
+
+#[test]
+fn enabling_restarts_the_prescaler() {
+  let mut timer = timer(ENABLE, 5, 3);
+  assert_eq!(timer.advance(3), None);
+  timer.write(0xDE05, 0x0000).unwrap();
+  timer.write(0xDE05, ENABLE).unwrap();
+  assert_eq!(timer.advance(6), None);
+  assert_eq!(timer.read(0xDE07).unwrap(), 5);
+  assert_eq!(timer.advance(7), None);
+  assert_eq!(timer.read(0xDE07).unwrap(), 4);
+}
+
+#[test]
+fn expired_is_cleared_by_writing_one() {
+  let mut timer = timer(ENABLE, 1, 0);
+  assert_eq!(timer.advance(1), None);
+  assert_eq!(timer.read(0xDE05).unwrap(), EXPIRED | 0x0030);
+  timer.write(0xDE05, 0x0030).unwrap();
+  assert_eq!(timer.read(0xDE05).unwrap(), EXPIRED | 0x0030);
+  timer.write(0xDE05, EXPIRED | 0x0030).unwrap();
+  assert_eq!(timer.read(0xDE05).unwrap(), 0x0030);
+}
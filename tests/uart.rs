@@ -0,0 +1,71 @@
+extern crate cpu;
+
+use cpu::io::Uart;
+use cpu::memory::Addressable;
+
+
+const DATA: u16 = 0xDE18;
+const STATUS: u16 = 0xDE19;
+const ENABLE: u16 = 0xDE1A;
+
+#[test]
+fn bytes_go_out_and_come_in_one_per_period() {
+  let mut uart = Uart::new();
+  uart.write(0xDE1B, 1).unwrap();
+  uart.write(DATA, b'h' as u16).unwrap();
+  uart.write(DATA, b'i' as u16).unwrap();
+  uart.receive(b'!');
+  assert_eq!(uart.read(STATUS).unwrap(), 0x0000);
+
+  uart.advance(2);
+  assert_eq!(uart.transmitted(), b"h");
+  assert_eq!(uart.read(STATUS).unwrap(), 0x0001);
+  uart.advance(4);
+  assert_eq!(uart.transmitted(), b"i");
+  assert_eq!(uart.read(STATUS).unwrap(), 0x0003);
+
+  assert_eq!(uart.peek(DATA).unwrap(), b'!' as u16);
+  assert_eq!(uart.read(DATA).unwrap(), b'!' as u16);
+  assert_eq!(uart.read(DATA).unwrap(), 0x0000);
+  assert_eq!(uart.read(STATUS).unwrap(), 0x0002);
+}
+
+#[test]
+fn transmit_fifo_drops_bytes_when_full() {
+  let mut uart = Uart::new();
+  for byte in 0..20 {
+    uart.write(DATA, byte).unwrap();
+  }
+  assert_eq!(uart.read(STATUS).unwrap(), 0x0004);
+  uart.advance(100);
+  assert_eq!(uart.transmitted(), (0..16).collect::<Vec<u8>>());
+}
+
+#[test]
+fn receive_interrupt_is_raised_while_transmit_stays_empty() {
+  let mut uart = Uart::new();
+  // Both conditions on line 5; the transmit FIFO is already empty, so enabling raises at once.
+  uart.write(ENABLE, 0x0053).unwrap();
+  assert_eq!(uart.advance(1), Some(5));
+  assert_eq!(uart.advance(2), None);
+
+  uart.receive(b'x');
+  assert_eq!(uart.advance(3), Some(5));
+  assert_eq!(uart.advance(4), None);
+
+  assert_eq!(uart.read(DATA).unwrap(), b'x' as u16);
+  assert_eq!(uart.advance(5), None);
+  uart.receive(b'y');
+  assert_eq!(uart.advance(6), Some(5));
+}
+
+#[test]
+fn transmit_interrupt_is_raised_when_the_fifo_runs_empty() {
+  let mut uart = Uart::new();
+  uart.write(ENABLE, 0x0012).unwrap();
+  assert_eq!(uart.advance(1), Some(1));
+  uart.write(DATA, b'a' as u16).unwrap();
+  assert_eq!(uart.advance(1), None);
+  assert_eq!(uart.advance(2), Some(1));
+  assert_eq!(uart.transmitted(), b"a");
+}
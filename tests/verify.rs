@@ -0,0 +1,30 @@
+extern crate cpu;
+
+use cpu::ControlLogic;
+use cpu::control::{
+  Definition,
+  Status,
+};
+
+
+#[test]
+fn built_in_microcode_has_no_broken_opcodes() {
+  let report = ControlLogic::new().unwrap().verify();
+  assert!(!report.broken(), "{}", report);
+  // NOP and HLT move nothing, so every variant of them is legal.
+  assert!(report.opcodes()[0x0000..=0x03FF].iter().all(|verdict| verdict.status == Status::Legal));
+}
+
+#[test]
+fn load_with_nothing_driving_the_bus_is_broken() {
+  // NOP's only step loads A, but nothing drives the data bus.
+  let text = Definition::built_in().to_string().replace("\n44 halt ", "\n44 data=None,A halt ");
+  let logic = ControlLogic::from_definition(Definition::parse(&text).unwrap()).unwrap();
+  let report = logic.verify();
+  assert!(report.broken());
+  assert_eq!(report.count(Status::Broken), 0x0400);
+  for verdict in &report.opcodes()[0x0000..=0x03FF] {
+    assert_eq!(verdict.status, Status::Broken);
+    assert_eq!(verdict.problems, vec!["step 0 (44): loads from the data bus, but nothing drives it"]);
+  }
+}